@@ -1,22 +1,24 @@
 use bench_helpers::collect_corpus_files;
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
-use hledger_fmt::{format_parsed_journal, parse_content};
+use hledger_fmt::{format_parsed, parse_journal_bytes, FormatJournalOptions};
 use std::fs;
 
 fn benchmark_formatter(c: &mut Criterion) {
     let mut corpus_files = collect_corpus_files();
     corpus_files.sort();
 
-    let mut group = c.benchmark_group("format_parsed_journal");
+    let mut group = c.benchmark_group("format_parsed");
     for file_path in corpus_files.iter() {
         let content = fs::read(file_path).unwrap();
-        let journal = parse_content(&content).unwrap();
+        let journal = parse_journal_bytes(&content).unwrap();
         let file_name = file_path.file_name().unwrap().to_str().unwrap();
 
         group.bench_with_input(
             BenchmarkId::from_parameter(file_name),
             &journal,
-            |b, input| b.iter(|| format_parsed_journal(std::hint::black_box(input))),
+            |b, input| {
+                b.iter(|| format_parsed(std::hint::black_box(input), FormatJournalOptions::new()))
+            },
         );
     }
     group.finish();
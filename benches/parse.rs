@@ -1,6 +1,6 @@
 use bench_helpers::collect_corpus_files;
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
-use hledger_fmt::parse_content;
+use hledger_fmt::parse_journal_bytes;
 use std::fs;
 
 fn benchmark_parser(c: &mut Criterion) {
@@ -15,7 +15,7 @@ fn benchmark_parser(c: &mut Criterion) {
         group.bench_with_input(
             BenchmarkId::from_parameter(file_name),
             &content,
-            |b, input| b.iter(|| parse_content(std::hint::black_box(input))),
+            |b, input| b.iter(|| parse_journal_bytes(std::hint::black_box(input))),
         );
     }
     group.finish();
@@ -9,4 +9,11 @@ fuzz_target!(|data: &[u8]| {
         // The formatted output should be parseable
         let _ = hledger_fmt::format_journal_bytes(&formatted);
     }
+
+    // Formatting the formatted output should be a no-op
+    if let Err(hledger_fmt::IdempotencyError::Mismatch { .. }) =
+        hledger_fmt::check_idempotent(data, hledger_fmt::FormatJournalOptions::new())
+    {
+        panic!("formatting is not idempotent");
+    }
 });
@@ -37,7 +37,7 @@ case "$BENCH_TYPE" in
 esac
 
 echo "1. Running baseline benchmark..."
-cargo bench --bench "$BENCH_NAME" --features bench 2>&1 | grep -A 2 "time:"
+cargo bench --bench "$BENCH_NAME" 2>&1 | grep -A 2 "time:"
 
 echo ""
 echo "2. Generating flamegraph for $BENCH_NAME..."
@@ -45,7 +45,7 @@ echo "   This will take a few moments..."
 
 # Generate flamegraph
 mkdir -p reports
-cargo flamegraph --bench "$BENCH_NAME" --features bench -o "reports/flamegraph-$BENCH_NAME.svg" -- --bench
+cargo flamegraph --bench "$BENCH_NAME" -o "reports/flamegraph-$BENCH_NAME.svg" -- --bench
 
 echo ""
 echo "=== Profiling complete! ==="
@@ -1,4 +1,7 @@
-#[cfg_attr(any(test, feature = "tracing"), derive(PartialEq))]
+/// Slice of the original journal content borrowed by the parsed nodes.
+///
+/// Dereferences to `[u8]`, so every slice method is available.
+#[derive(PartialEq)]
 pub struct ByteStr<'a> {
     bytes: &'a [u8],
 }
@@ -6,6 +9,7 @@ pub struct ByteStr<'a> {
 impl<'a> core::ops::Deref for ByteStr<'a> {
     type Target = [u8];
 
+    #[inline(always)]
     fn deref(&self) -> &Self::Target {
         self.bytes
     }
@@ -27,9 +31,22 @@ impl<'a> From<&'a [u8]> for ByteStr<'a> {
 
 impl<'a> ByteStr<'a> {
     /// Returns the number of characters, handling UTF-8 correctly
+    #[inline]
     pub fn chars_count(&self) -> usize {
         utf8_chars_count(self.bytes)
     }
+
+    /// Returns the underlying bytes, with the lifetime of the original content.
+    #[inline]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns the slice as a string slice if it is valid UTF-8.
+    #[inline]
+    pub fn to_str(&self) -> Result<&'a str, core::str::Utf8Error> {
+        core::str::from_utf8(self.bytes)
+    }
 }
 
 #[cfg(test)]
@@ -42,10 +59,10 @@ impl<'a> From<&'a str> for ByteStr<'a> {
     }
 }
 
-#[cfg(any(test, feature = "tracing"))]
-impl<'a> std::fmt::Debug for ByteStr<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match std::str::from_utf8(self.bytes) {
+impl<'a> core::fmt::Debug for ByteStr<'a> {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match core::str::from_utf8(self.bytes) {
             Ok(s) => write!(f, "{:?}", s),
             Err(_) => write!(f, "{:?}", self.bytes),
         }
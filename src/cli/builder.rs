@@ -15,7 +15,7 @@ pub fn build() -> Command {
             \n\
             If not defined, hledger-fmt will search for hledger files in the \
             current directory and its subdirectories (those that have the \
-            extensions '.journal', '.hledger' or '.j'). \
+            extensions '.journal', '.hledger', '.j' or '.timeclock'). \
             If the paths passed are directories, hledger-fmt will search for \
             hledger files in those directories and their subdirectories.",
                 )
@@ -47,12 +47,68 @@ pub fn build() -> Command {
             .action(ArgAction::SetTrue),
     );
 
-    let cmd = cmd.arg(
-        Arg::new("exit-zero-on-changes")
-            .long("exit-zero-on-changes")
-            .help("Exit with code 0, even if files have been formatted.")
-            .action(ArgAction::SetTrue),
-    );
+    let cmd = cmd
+        .arg(
+            Arg::new("exit-zero-on-changes")
+                .long("exit-zero-on-changes")
+                .help("Exit with code 0, even if files have been formatted.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("verify")
+                .long("verify")
+                .help(
+                    "Check that the formatted content is equivalent to the original \
+                     one ignoring whitespace. Files that don't pass the check are \
+                     reported and not written.",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("check-idempotent")
+                .long("check-idempotent")
+                .help(
+                    "Check that formatting the formatted content again doesn't \
+                     change it. Files that don't pass the check are reported with \
+                     their first differing line and not written.",
+                )
+                .action(ArgAction::SetTrue)
+                .conflicts_with("lines"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .help(
+                    "Path to a configuration file to use for all the files. If not \
+                     passed, hledger-fmt uses the nearest '.hledger-fmt.toml' file \
+                     found in the directory of each file or its parents.",
+                )
+                .action(ArgAction::Set)
+                .value_parser(value_parser!(String))
+                .value_name("CONFIG"),
+        )
+        .arg(
+            Arg::new("lines")
+                .long("lines")
+                .help(
+                    "Only format the transactions, directives and comments that \
+                     overlap the lines START to END (1-based, inclusive) of each \
+                     file, leaving the rest of the content unchanged.",
+                )
+                .action(ArgAction::Set)
+                .value_parser(value_parser!(String))
+                .value_name("START:END"),
+        );
+
+    #[cfg(feature = "lsp")]
+    let cmd = cmd
+        .override_usage("hledger-fmt [OPTIONS] [FILES]...\n       hledger-fmt lsp\n")
+        .args_conflicts_with_subcommands(true)
+        .disable_help_subcommand(true)
+        .subcommand(
+            Command::new("lsp")
+                .about("Run a language server over STDIO to format files from editors."),
+        );
 
     #[cfg(feature = "tracing")]
     let cmd = cmd.arg(
@@ -0,0 +1,471 @@
+//! Configuration files.
+//!
+//! Settings are read from the `.hledger-fmt.toml` files found in the
+//! directory of each formatted file and its parents. The settings of the
+//! nearest files override the ones of their parents, so each directory only
+//! needs to define the settings it changes. The `--config` option overrides
+//! the discovery. Settings can be defined at the top level of the file or inside
+//! a `[tool.hledger-fmt]` table. When a file has that table, the top level and
+//! other tables are ignored so the settings can live in a file shared with other
+//! tools.
+//!
+//! Only the subset of TOML needed by the settings is supported: comments,
+//! table headers and `key = value` pairs with integer, single line string or
+//! boolean values.
+
+#[cfg(test)]
+mod tests;
+
+use crate::{
+    AlignmentScope, CommentAlignment, CommentPrefix, DateFormat, FormatJournalOptions, NodeKind,
+    PostingIndent,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Name of the configuration files searched for
+pub(crate) const CONFIG_FILE_NAME: &str = ".hledger-fmt.toml";
+
+/// Settings read from a configuration file
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Config {
+    entry_spacing: Option<usize>,
+    normalize_transaction_headers: Option<bool>,
+    date_format: Option<DateFormat>,
+    normalize_amount_styles: Option<bool>,
+    alignment_scope: Option<AlignmentScope>,
+    amount_column: Option<usize>,
+    posting_indent: Option<PostingIndent>,
+    /// `Some(None)` preserves the prefixes
+    comment_prefix: Option<Option<CommentPrefix>>,
+    comment_alignment: Option<CommentAlignment>,
+    max_blank_lines: Option<usize>,
+    /// Indexed by [`NodeKind`]
+    blank_lines_between: [Option<usize>; 3],
+    trim_blank_lines: Option<bool>,
+    align_virtual_account_names: Option<bool>,
+}
+
+impl Config {
+    /// Read a configuration file
+    pub(crate) fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading config file {}: {e}", path.display()))?;
+        Self::from_toml(&content)
+            .map_err(|e| format!("Error parsing config file {}:{e}", path.display()))
+    }
+
+    /// Parse the content of a configuration file.
+    ///
+    /// Errors are prefixed by the number of the line where they happened.
+    pub(crate) fn from_toml(content: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        // the top level of files shared with other tools belongs to them
+        let shared = content.lines().any(|line| {
+            strip_comment(line)
+                .trim()
+                .strip_prefix('[')
+                .and_then(|table| table.strip_suffix(']'))
+                .is_some_and(is_settings_table)
+        });
+        let mut in_settings_table = !shared;
+        for (i, line) in content.lines().enumerate() {
+            let lineno = i + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(table) = line.strip_prefix('[') {
+                let table = table
+                    .strip_suffix(']')
+                    .ok_or_else(|| format!("{lineno}: expected ']' at the end of table header"))?;
+                in_settings_table = is_settings_table(table);
+                continue;
+            } else if !in_settings_table {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("{lineno}: expected 'key = value'"))?;
+            let key = unquote_key(key);
+            let value = parse_value(value.trim()).map_err(|e| format!("{lineno}: {e}"))?;
+            config
+                .set(&key, value)
+                .map_err(|e| format!("{lineno}: {e}"))?;
+        }
+        Ok(config)
+    }
+
+    fn set(&mut self, key: &str, value: Value) -> Result<(), String> {
+        match key {
+            "entry_spacing" => self.entry_spacing = Some(value.into_usize(key)?),
+            "amount_column" => self.amount_column = Some(value.into_usize(key)?),
+            "normalize_transaction_headers" => {
+                self.normalize_transaction_headers = Some(value.into_bool(key)?);
+            }
+            "normalize_amount_styles" => {
+                self.normalize_amount_styles = Some(value.into_bool(key)?);
+            }
+            "posting_indent" => {
+                self.posting_indent = Some(match value {
+                    Value::String(value) if value == "tab" => PostingIndent::Tab,
+                    Value::String(value) => {
+                        return Err(format!(
+                            "'{key}' must be a number of spaces or 'tab', found '{value}'"
+                        ))
+                    }
+                    value => PostingIndent::Spaces(value.into_usize(key)?),
+                });
+            }
+            "date_format" => {
+                self.date_format = Some(match value.into_string(key)?.as_str() {
+                    "YYYY-MM-DD" => DateFormat::Iso,
+                    "YYYY/MM/DD" => DateFormat::Slash,
+                    "YYYY.MM.DD" => DateFormat::Dot,
+                    value => {
+                        return Err(format!(
+                            "'{key}' must be one of 'YYYY-MM-DD', 'YYYY/MM/DD' or \
+                             'YYYY.MM.DD', found '{value}'"
+                        ))
+                    }
+                });
+            }
+            "alignment_scope" => {
+                self.alignment_scope = Some(match value.into_string(key)?.as_str() {
+                    "transaction" => AlignmentScope::Transaction,
+                    "block" => AlignmentScope::Block,
+                    "file" => AlignmentScope::File,
+                    value => {
+                        return Err(format!(
+                            "'{key}' must be one of 'transaction', 'block' or 'file', \
+                             found '{value}'"
+                        ))
+                    }
+                });
+            }
+            "comment_prefix" => {
+                self.comment_prefix = Some(match value.into_string(key)?.as_str() {
+                    "preserve" => None,
+                    "semicolon" => Some(CommentPrefix::Semicolon),
+                    "hash" => Some(CommentPrefix::Hash),
+                    value => {
+                        return Err(format!(
+                            "'{key}' must be one of 'preserve', 'semicolon' or 'hash', \
+                             found '{value}'"
+                        ))
+                    }
+                });
+            }
+            "comment_alignment" => {
+                self.comment_alignment = Some(match value {
+                    Value::String(value) => match value.as_str() {
+                        "group" => CommentAlignment::Group,
+                        "file" => CommentAlignment::File,
+                        "single-space" => CommentAlignment::SingleSpace,
+                        _ => {
+                            return Err(format!(
+                                "'{key}' must be a column or one of 'group', 'file' or \
+                                 'single-space', found '{value}'"
+                            ))
+                        }
+                    },
+                    value => CommentAlignment::Column(value.into_usize(key)?),
+                });
+            }
+            "max_blank_lines" => self.max_blank_lines = Some(value.into_usize(key)?),
+            "blank_lines_between_transactions" => {
+                self.blank_lines_between[NodeKind::Transaction as usize] =
+                    Some(value.into_usize(key)?);
+            }
+            "blank_lines_between_directives" => {
+                self.blank_lines_between[NodeKind::DirectivesGroup as usize] =
+                    Some(value.into_usize(key)?);
+            }
+            "blank_lines_between_comments" => {
+                self.blank_lines_between[NodeKind::Comment as usize] = Some(value.into_usize(key)?);
+            }
+            "trim_blank_lines" => self.trim_blank_lines = Some(value.into_bool(key)?),
+            "align_virtual_account_names" => {
+                self.align_virtual_account_names = Some(value.into_bool(key)?);
+            }
+            _ => return Err(format!("unknown setting '{key}'")),
+        }
+        Ok(())
+    }
+
+    /// Settings of this configuration, taking the ones it doesn't define
+    /// from `parent`
+    fn or(self, parent: &Self) -> Self {
+        let mut blank_lines_between = self.blank_lines_between;
+        for (blank_lines, parent_blank_lines) in blank_lines_between
+            .iter_mut()
+            .zip(parent.blank_lines_between)
+        {
+            *blank_lines = blank_lines.or(parent_blank_lines);
+        }
+        Self {
+            entry_spacing: self.entry_spacing.or(parent.entry_spacing),
+            normalize_transaction_headers: self
+                .normalize_transaction_headers
+                .or(parent.normalize_transaction_headers),
+            date_format: self.date_format.or(parent.date_format),
+            normalize_amount_styles: self
+                .normalize_amount_styles
+                .or(parent.normalize_amount_styles),
+            alignment_scope: self.alignment_scope.or(parent.alignment_scope),
+            amount_column: self.amount_column.or(parent.amount_column),
+            posting_indent: self.posting_indent.or(parent.posting_indent),
+            comment_prefix: self.comment_prefix.or(parent.comment_prefix),
+            comment_alignment: self.comment_alignment.or(parent.comment_alignment),
+            max_blank_lines: self.max_blank_lines.or(parent.max_blank_lines),
+            blank_lines_between,
+            trim_blank_lines: self.trim_blank_lines.or(parent.trim_blank_lines),
+            align_virtual_account_names: self
+                .align_virtual_account_names
+                .or(parent.align_virtual_account_names),
+        }
+    }
+
+    /// Apply the settings to formatting options
+    pub(crate) fn apply(&self, mut options: FormatJournalOptions) -> FormatJournalOptions {
+        if let Some(entry_spacing) = self.entry_spacing {
+            options = options.with_entry_spacing(entry_spacing);
+        }
+        if let Some(normalize) = self.normalize_transaction_headers {
+            options = options.with_normalize_transaction_headers(normalize);
+        }
+        if let Some(date_format) = self.date_format {
+            options = options.with_date_format(Some(date_format));
+        }
+        if let Some(normalize) = self.normalize_amount_styles {
+            options = options.with_normalize_amount_styles(normalize);
+        }
+        if let Some(alignment_scope) = self.alignment_scope {
+            options = options.with_alignment_scope(alignment_scope);
+        }
+        if let Some(amount_column) = self.amount_column {
+            options = options.with_amount_column(Some(amount_column));
+        }
+        if let Some(posting_indent) = self.posting_indent {
+            options = options.with_posting_indent(Some(posting_indent));
+        }
+        if let Some(comment_prefix) = self.comment_prefix {
+            options = options.with_comment_prefix(comment_prefix);
+        }
+        if let Some(comment_alignment) = self.comment_alignment {
+            options = options.with_comment_alignment(comment_alignment);
+        }
+        if let Some(max_blank_lines) = self.max_blank_lines {
+            options = options.with_max_blank_lines(Some(max_blank_lines));
+        }
+        for (kind, blank_lines) in [
+            NodeKind::Transaction,
+            NodeKind::DirectivesGroup,
+            NodeKind::Comment,
+        ]
+        .into_iter()
+        .zip(self.blank_lines_between)
+        {
+            if blank_lines.is_some() {
+                options = options.with_blank_lines_between(kind, blank_lines);
+            }
+        }
+        if let Some(trim_blank_lines) = self.trim_blank_lines {
+            options = options.with_trim_blank_lines(trim_blank_lines);
+        }
+        if let Some(align) = self.align_virtual_account_names {
+            options = options.with_align_virtual_account_names(align);
+        }
+        options
+    }
+}
+
+/// Resolve the configuration of each formatted file
+pub(crate) struct ConfigResolver {
+    /// Configuration passed with `--config`, used for all files
+    config: Option<Rc<Config>>,
+    /// Configuration of each visited directory
+    cache: HashMap<PathBuf, Option<Rc<Config>>>,
+}
+
+impl ConfigResolver {
+    pub(crate) fn new(config: Option<Config>) -> Self {
+        Self {
+            config: config.map(Rc::new),
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Configuration for the file at `path`, or for the current directory
+    /// when reading from STDIN (`None`).
+    pub(crate) fn resolve(&mut self, path: Option<&Path>) -> Result<Option<Rc<Config>>, String> {
+        if let Some(config) = &self.config {
+            return Ok(Some(Rc::clone(config)));
+        }
+
+        let directory = match path.and_then(Path::parent) {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let directory = match std::env::current_dir() {
+            Ok(current_dir) if directory.is_relative() => current_dir.join(directory),
+            _ => directory,
+        };
+        self.resolve_directory(&directory)
+    }
+
+    fn resolve_directory(&mut self, directory: &Path) -> Result<Option<Rc<Config>>, String> {
+        if let Some(config) = self.cache.get(directory) {
+            return Ok(config.clone());
+        }
+
+        let parent_config = match directory.parent() {
+            Some(parent) => self.resolve_directory(parent)?,
+            None => None,
+        };
+        let config_path = directory.join(CONFIG_FILE_NAME);
+        let config = if config_path.is_file() {
+            let config = Config::from_file(&config_path)?;
+            Some(Rc::new(match &parent_config {
+                Some(parent_config) => config.or(parent_config),
+                None => config,
+            }))
+        } else {
+            parent_config
+        };
+        self.cache.insert(directory.to_path_buf(), config.clone());
+        Ok(config)
+    }
+}
+
+#[derive(Debug)]
+enum Value {
+    Integer(i64),
+    String(String),
+    Boolean(bool),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+        }
+    }
+
+    fn into_usize(self, key: &str) -> Result<usize, String> {
+        match self {
+            Value::Integer(value) => usize::try_from(value)
+                .map_err(|_| format!("'{key}' must be a non-negative integer, found {value}")),
+            value => Err(format!(
+                "'{key}' must be an integer, found {}",
+                value.type_name()
+            )),
+        }
+    }
+
+    fn into_bool(self, key: &str) -> Result<bool, String> {
+        match self {
+            Value::Boolean(value) => Ok(value),
+            value => Err(format!(
+                "'{key}' must be a boolean, found {}",
+                value.type_name()
+            )),
+        }
+    }
+
+    fn into_string(self, key: &str) -> Result<String, String> {
+        match self {
+            Value::String(value) => Ok(value),
+            value => Err(format!(
+                "'{key}' must be a string, found {}",
+                value.type_name()
+            )),
+        }
+    }
+}
+
+/// Remove a comment from a line, ignoring `#` inside strings
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+/// If a table header names the `[tool.hledger-fmt]` table
+fn is_settings_table(table: &str) -> bool {
+    table
+        .split('.')
+        .map(unquote_key)
+        .eq(["tool", "hledger-fmt"])
+}
+
+fn unquote_key(key: &str) -> String {
+    let key = key.trim();
+    match parse_value(key) {
+        Ok(Value::String(key)) => key,
+        _ => key.to_string(),
+    }
+}
+
+fn parse_value(value: &str) -> Result<Value, String> {
+    if value.starts_with("\"\"\"") || value.starts_with("'''") {
+        return Err("multiline strings are not supported".to_string());
+    }
+    if let Some(string) = value
+        .strip_prefix('\'')
+        .and_then(|value| value.strip_suffix('\''))
+    {
+        return Ok(Value::String(string.to_string()));
+    } else if let Some(string) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        let mut unescaped = String::with_capacity(string.len());
+        let mut chars = string.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('"') => unescaped.push('"'),
+                Some('\\') => unescaped.push('\\'),
+                Some('n') => unescaped.push('\n'),
+                Some('t') => unescaped.push('\t'),
+                Some(c) => return Err(format!("unsupported escape sequence '\\{c}'")),
+                None => return Err("unterminated escape sequence".to_string()),
+            }
+        }
+        return Ok(Value::String(unescaped));
+    }
+
+    match value {
+        "true" => return Ok(Value::Boolean(true)),
+        "false" => return Ok(Value::Boolean(false)),
+        _ => {}
+    }
+
+    value
+        .replace('_', "")
+        .parse::<i64>()
+        .map(Value::Integer)
+        .map_err(|_| format!("unsupported value '{value}'"))
+}
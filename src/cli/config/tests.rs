@@ -0,0 +1,334 @@
+use super::*;
+
+#[test]
+fn empty() {
+    assert_eq!(Config::from_toml(""), Ok(Config::default()));
+    assert_eq!(
+        Config::from_toml("# comment\n\n  \n"),
+        Ok(Config::default())
+    );
+}
+
+#[test]
+fn top_level_settings() {
+    assert_eq!(
+        Config::from_toml("entry_spacing = 4  # comment\n"),
+        Ok(Config {
+            entry_spacing: Some(4),
+            ..Config::default()
+        })
+    );
+    assert_eq!(
+        Config::from_toml("\"entry_spacing\"=4"),
+        Ok(Config {
+            entry_spacing: Some(4),
+            ..Config::default()
+        })
+    );
+}
+
+#[test]
+fn tool_table() {
+    let content = r#"
+[project]
+name = "foo"
+entry_spacing = "ignored"
+
+[tool.hledger-fmt]
+entry_spacing = 3
+
+[tool.other]
+entry_spacing = [1, 2]
+"#;
+    assert_eq!(
+        Config::from_toml(content),
+        Ok(Config {
+            entry_spacing: Some(3),
+            ..Config::default()
+        })
+    );
+    assert_eq!(
+        Config::from_toml("[tool.\"hledger-fmt\"]\nentry_spacing = 3"),
+        Ok(Config {
+            entry_spacing: Some(3),
+            ..Config::default()
+        })
+    );
+}
+
+#[test]
+fn shared_file() {
+    let content = r#"
+line-length = 88
+entry_spacing = "ignored"
+
+[tool.hledger-fmt]
+entry_spacing = 3
+"#;
+    assert_eq!(
+        Config::from_toml(content),
+        Ok(Config {
+            entry_spacing: Some(3),
+            ..Config::default()
+        })
+    );
+}
+
+#[test]
+fn unknown_setting() {
+    assert_eq!(
+        Config::from_toml("entry_spacing = 2\nfoo = 1\n"),
+        Err("2: unknown setting 'foo'".to_string())
+    );
+    assert_eq!(
+        Config::from_toml("[tool.hledger-fmt]\nfoo = 1\n"),
+        Err("2: unknown setting 'foo'".to_string())
+    );
+}
+
+#[test]
+fn invalid_values() {
+    assert_eq!(
+        Config::from_toml("entry_spacing = \"2\""),
+        Err("1: 'entry_spacing' must be an integer, found string".to_string())
+    );
+    assert_eq!(
+        Config::from_toml("entry_spacing = -2"),
+        Err("1: 'entry_spacing' must be a non-negative integer, found -2".to_string())
+    );
+    assert_eq!(
+        Config::from_toml("\n\nentry_spacing = [2]"),
+        Err("3: unsupported value '[2]'".to_string())
+    );
+    assert_eq!(
+        Config::from_toml("date_format = \"\"\"YYYY-MM-DD\"\"\""),
+        Err("1: multiline strings are not supported".to_string())
+    );
+    assert_eq!(
+        Config::from_toml("entry_spacing"),
+        Err("1: expected 'key = value'".to_string())
+    );
+    assert_eq!(
+        Config::from_toml("[tool.hledger-fmt"),
+        Err("1: expected ']' at the end of table header".to_string())
+    );
+}
+
+#[test]
+fn normalize_transaction_headers() {
+    assert_eq!(
+        Config::from_toml("normalize_transaction_headers = true"),
+        Ok(Config {
+            normalize_transaction_headers: Some(true),
+            ..Config::default()
+        })
+    );
+    assert_eq!(
+        Config::from_toml("normalize_transaction_headers = 'yes'"),
+        Err("1: 'normalize_transaction_headers' must be a boolean, found string".to_string())
+    );
+}
+
+#[test]
+fn normalize_amount_styles() {
+    assert_eq!(
+        Config::from_toml("normalize_amount_styles = true"),
+        Ok(Config {
+            normalize_amount_styles: Some(true),
+            ..Config::default()
+        })
+    );
+}
+
+#[test]
+fn amount_column() {
+    assert_eq!(
+        Config::from_toml("amount_column = 60\nentry_spacing = 4"),
+        Ok(Config {
+            entry_spacing: Some(4),
+            amount_column: Some(60),
+            ..Config::default()
+        })
+    );
+}
+
+#[test]
+fn posting_indent() {
+    assert_eq!(
+        Config::from_toml("posting_indent = 2"),
+        Ok(Config {
+            posting_indent: Some(PostingIndent::Spaces(2)),
+            ..Config::default()
+        })
+    );
+    assert_eq!(
+        Config::from_toml("posting_indent = 'tab'"),
+        Ok(Config {
+            posting_indent: Some(PostingIndent::Tab),
+            ..Config::default()
+        })
+    );
+    assert_eq!(
+        Config::from_toml("posting_indent = 'spaces'"),
+        Err("1: 'posting_indent' must be a number of spaces or 'tab', found 'spaces'".to_string())
+    );
+}
+
+#[test]
+fn comment_prefix() {
+    assert_eq!(
+        Config::from_toml("comment_prefix = 'hash'"),
+        Ok(Config {
+            comment_prefix: Some(Some(CommentPrefix::Hash)),
+            ..Config::default()
+        })
+    );
+    assert_eq!(
+        Config::from_toml("comment_prefix = 'preserve'"),
+        Ok(Config {
+            comment_prefix: Some(None),
+            ..Config::default()
+        })
+    );
+    assert_eq!(
+        Config::from_toml("comment_prefix = '#'"),
+        Err(
+            "1: 'comment_prefix' must be one of 'preserve', 'semicolon' or 'hash', found '#'"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn comment_alignment() {
+    assert_eq!(
+        Config::from_toml("comment_alignment = 60"),
+        Ok(Config {
+            comment_alignment: Some(CommentAlignment::Column(60)),
+            ..Config::default()
+        })
+    );
+    assert_eq!(
+        Config::from_toml("comment_alignment = 'single-space'"),
+        Ok(Config {
+            comment_alignment: Some(CommentAlignment::SingleSpace),
+            ..Config::default()
+        })
+    );
+    assert_eq!(
+        Config::from_toml("comment_alignment = 'column'"),
+        Err(
+            "1: 'comment_alignment' must be a column or one of 'group', 'file' or \
+             'single-space', found 'column'"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn blank_lines() {
+    assert_eq!(
+        Config::from_toml(
+            "max_blank_lines = 2\nblank_lines_between_transactions = 1\n\
+             blank_lines_between_comments = 0\ntrim_blank_lines = true"
+        ),
+        Ok(Config {
+            max_blank_lines: Some(2),
+            blank_lines_between: [Some(1), None, Some(0)],
+            trim_blank_lines: Some(true),
+            ..Config::default()
+        })
+    );
+    assert_eq!(
+        Config::from_toml("trim_blank_lines = 1"),
+        Err("1: 'trim_blank_lines' must be a boolean, found integer".to_string())
+    );
+    assert_eq!(
+        Config::from_toml("max_blank_lines = false"),
+        Err("1: 'max_blank_lines' must be an integer, found boolean".to_string())
+    );
+}
+
+#[test]
+fn align_virtual_account_names() {
+    assert_eq!(
+        Config::from_toml("align_virtual_account_names = true"),
+        Ok(Config {
+            align_virtual_account_names: Some(true),
+            ..Config::default()
+        })
+    );
+}
+
+#[test]
+fn date_format() {
+    assert_eq!(
+        Config::from_toml("date_format = 'YYYY/MM/DD'"),
+        Ok(Config {
+            date_format: Some(DateFormat::Slash),
+            ..Config::default()
+        })
+    );
+    assert_eq!(
+        Config::from_toml("date_format = 'DD/MM/YYYY'"),
+        Err(
+            "1: 'date_format' must be one of 'YYYY-MM-DD', 'YYYY/MM/DD' or \
+             'YYYY.MM.DD', found 'DD/MM/YYYY'"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        Config::from_toml("date_format = 1"),
+        Err("1: 'date_format' must be a string, found integer".to_string())
+    );
+}
+
+#[test]
+fn alignment_scope() {
+    assert_eq!(
+        Config::from_toml("[tool.hledger-fmt]\nalignment_scope = \"block\""),
+        Ok(Config {
+            alignment_scope: Some(AlignmentScope::Block),
+            ..Config::default()
+        })
+    );
+    assert_eq!(
+        Config::from_toml("alignment_scope = 'journal'"),
+        Err(
+            "1: 'alignment_scope' must be one of 'transaction', 'block' or 'file', \
+             found 'journal'"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn strings() {
+    assert!(matches!(parse_value("'a # b'"), Ok(Value::String(s)) if s == "a # b"));
+    assert!(matches!(parse_value(r#""a \"b\"\n""#), Ok(Value::String(s)) if s == "a \"b\"\n"));
+    assert!(parse_value(r#""\x""#).is_err());
+    assert_eq!(
+        strip_comment(r#"key = "a # \" b" # comment"#),
+        r#"key = "a # \" b" "#
+    );
+}
+
+#[test]
+fn merge_with_parent() {
+    let parent = Config::from_toml(
+        "entry_spacing = 4\ndate_format = 'YYYY-MM-DD'\nblank_lines_between_comments = 1\n",
+    )
+    .unwrap();
+    let config = Config::from_toml("entry_spacing = 3\nblank_lines_between_transactions = 2\n")
+        .unwrap()
+        .or(&parent);
+    assert_eq!(
+        config,
+        Config {
+            entry_spacing: Some(3),
+            date_format: Some(DateFormat::Iso),
+            blank_lines_between: [Some(2), None, Some(1)],
+            ..Config::default()
+        }
+    );
+}
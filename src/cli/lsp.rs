@@ -0,0 +1,415 @@
+//! Language Server Protocol server over stdio.
+//!
+//! Supports full document synchronization, formatting, range formatting
+//! and syntax errors as diagnostics. Documents with the `.timeclock`
+//! extension are handled as timeclock files. Messages are framed with
+//! `Content-Length` headers as the specification requires.
+
+use super::config::ConfigResolver;
+use super::Parsed;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+/// JSON-RPC error code for messages that are not valid JSON
+const PARSE_ERROR: i64 = -32700;
+/// JSON-RPC error code for unknown methods
+const METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC error code for invalid parameters
+const INVALID_PARAMS: i64 = -32602;
+/// LSP error code for valid requests that failed
+const REQUEST_FAILED: i64 = -32803;
+
+/// Run the language server reading from STDIN and writing to STDOUT
+/// until the `exit` notification is received. Returns the exit code.
+#[cold]
+pub(crate) fn run() -> i32 {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut server = Server {
+        reader: stdin.lock(),
+        writer: stdout.lock(),
+        documents: HashMap::new(),
+        shutdown_requested: false,
+    };
+    match server.serve() {
+        Ok(exitcode) => exitcode,
+        Err(e) => {
+            eprintln!("hledger-fmt lsp error: {e}");
+            1
+        }
+    }
+}
+
+struct Server<R, W> {
+    reader: R,
+    writer: W,
+    /// Content of the open documents by URI
+    documents: HashMap<String, String>,
+    shutdown_requested: bool,
+}
+
+impl<R: BufRead, W: Write> Server<R, W> {
+    fn serve(&mut self) -> io::Result<i32> {
+        while let Some(message) = self.read_message()? {
+            let message = match message {
+                Ok(message) => message,
+                Err(error) => {
+                    // the id of the request can't be known
+                    self.respond(Value::Null, Err((PARSE_ERROR, error)))?;
+                    continue;
+                }
+            };
+            let method = message["method"].as_str().unwrap_or_default();
+            let id = message.get("id").cloned();
+            let params = &message["params"];
+
+            match (method, id) {
+                ("exit", _) => return Ok(if self.shutdown_requested { 0 } else { 1 }),
+                ("initialize", Some(id)) => {
+                    self.respond(id, Ok(initialize_result()))?;
+                }
+                ("shutdown", Some(id)) => {
+                    self.shutdown_requested = true;
+                    self.respond(id, Ok(Value::Null))?;
+                }
+                ("textDocument/formatting", Some(id)) => {
+                    let result = self.format(params, None);
+                    self.respond(id, result)?;
+                }
+                ("textDocument/rangeFormatting", Some(id)) => {
+                    let result = lines_range(&params["range"])
+                        .ok_or_else(|| invalid_params("missing range"))
+                        .and_then(|lines| self.format(params, Some(lines)));
+                    self.respond(id, result)?;
+                }
+                (_, Some(id)) => {
+                    let error = (METHOD_NOT_FOUND, format!("Method not found: {method}"));
+                    self.respond(id, Err(error))?;
+                }
+                ("textDocument/didOpen", None) => {
+                    let document = &params["textDocument"];
+                    if let (Some(uri), Some(text)) =
+                        (document["uri"].as_str(), document["text"].as_str())
+                    {
+                        self.documents.insert(uri.to_string(), text.to_string());
+                        self.publish_diagnostics(uri)?;
+                    }
+                }
+                ("textDocument/didChange", None) => {
+                    let uri = params["textDocument"]["uri"].as_str();
+                    // full synchronization, the last change holds the whole content
+                    let text = params["contentChanges"]
+                        .as_array()
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change["text"].as_str());
+                    if let (Some(uri), Some(text)) = (uri, text) {
+                        self.documents.insert(uri.to_string(), text.to_string());
+                        self.publish_diagnostics(uri)?;
+                    }
+                }
+                ("textDocument/didClose", None) => {
+                    if let Some(uri) = params["textDocument"]["uri"].as_str() {
+                        self.documents.remove(uri);
+                        self.notify(
+                            "textDocument/publishDiagnostics",
+                            json!({"uri": uri, "diagnostics": []}),
+                        )?;
+                    }
+                }
+                // other notifications are ignored
+                (_, None) => {}
+            }
+        }
+
+        // STDIN closed without `exit` notification
+        Ok(1)
+    }
+
+    /// Format a document, optionally only the lines of a range (1-based, inclusive).
+    fn format(
+        &self,
+        params: &Value,
+        lines: Option<(usize, usize)>,
+    ) -> Result<Value, (i64, String)> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .ok_or_else(|| invalid_params("missing textDocument.uri"))?;
+        let text = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| invalid_params(&format!("unknown document {uri}")))?;
+
+        // documents with syntax errors are not formatted
+        let parsed = if is_timeclock_uri(uri) {
+            match crate::parser::timeclock::parse_timeclock_content(text.as_bytes()) {
+                Ok(nodes) => Parsed::Timeclock(nodes),
+                Err(_) => return Ok(Value::Null),
+            }
+        } else {
+            match crate::parser::parse_content(text.as_bytes()) {
+                Ok(nodes) => Parsed::Journal(nodes),
+                Err(_) => return Ok(Value::Null),
+            }
+        };
+        let mut format_opts = crate::FormatJournalOptions::new().with_estimated_length(text.len());
+        // configuration files are read again for each request as they can change,
+        // documents that are not files use the configuration of the current directory
+        let path = uri_to_path(uri);
+        match ConfigResolver::new(None).resolve(path.as_deref()) {
+            Ok(Some(config)) => format_opts = config.apply(format_opts),
+            Ok(None) => {}
+            Err(e) => return Err((REQUEST_FAILED, e)),
+        }
+        let buffer = match (&parsed, lines) {
+            (Parsed::Journal(nodes), Some((start, end))) => {
+                crate::formatter::format_content_range_with_options(
+                    nodes,
+                    text.as_bytes(),
+                    start..=end,
+                    &format_opts,
+                )
+            }
+            (Parsed::Journal(nodes), None) => {
+                crate::formatter::format_content_with_options(nodes, &format_opts)
+            }
+            (Parsed::Timeclock(nodes), Some((start, end))) => {
+                crate::formatter::timeclock::format_timeclock_content_range_with_options(
+                    nodes,
+                    text.as_bytes(),
+                    start..=end,
+                    &format_opts,
+                )
+            }
+            (Parsed::Timeclock(nodes), None) => {
+                crate::formatter::timeclock::format_timeclock_content_with_options(
+                    nodes,
+                    &format_opts,
+                )
+            }
+        };
+        if buffer == text.as_bytes() {
+            return Ok(json!([]));
+        }
+
+        let buffer = String::from_utf8_lossy(&buffer);
+        if lines.is_some() {
+            return Ok(json!([changed_lines_edit(text, &buffer)]));
+        }
+        Ok(json!([{
+            "range": {
+                "start": {"line": 0, "character": 0},
+                "end": end_position(text),
+            },
+            "newText": buffer,
+        }]))
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+        let text = &self.documents[uri];
+        let errors = if is_timeclock_uri(uri) {
+            crate::parser::timeclock::parse_timeclock_content_recovering(text.as_bytes()).1
+        } else {
+            crate::parser::parse_content_recovering(text.as_bytes()).1
+        };
+        let diagnostics = errors
+            .iter()
+            .map(|error| {
+                let line = text.split('\n').nth(error.lineno - 1).unwrap_or_default();
+                json!({
+                    "range": {
+                        "start": {
+                            "line": error.lineno - 1,
+                            "character": utf16_column(line, error.colno_start - 1),
+                        },
+                        "end": {
+                            "line": error.lineno - 1,
+                            "character": utf16_column(line, error.colno_end - 1),
+                        },
+                    },
+                    "severity": 1,
+                    "source": "hledger-fmt",
+                    "message": format!("{}\nExpected {}", error.message, error.expected),
+                })
+            })
+            .collect::<Vec<_>>();
+        self.notify(
+            "textDocument/publishDiagnostics",
+            json!({"uri": uri, "diagnostics": diagnostics}),
+        )
+    }
+
+    fn respond(&mut self, id: Value, result: Result<Value, (i64, String)>) -> io::Result<()> {
+        let message = match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": code, "message": message},
+            }),
+        };
+        self.write_message(&message)
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        self.write_message(&json!({"jsonrpc": "2.0", "method": method, "params": params}))
+    }
+
+    /// Read the next message, returning `None` when the input is closed.
+    ///
+    /// Messages whose content is not valid JSON are returned as parse errors.
+    /// Without a valid `Content-Length` header the end of the message can't
+    /// be known, so the input can't be read further and an error is returned.
+    fn read_message(&mut self) -> io::Result<Option<Result<Value, String>>> {
+        let mut content_length = None;
+        let mut headers_read = false;
+        let mut header = String::new();
+        loop {
+            header.clear();
+            if self.reader.read_line(&mut header)? == 0 {
+                return Ok(None);
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                if headers_read {
+                    break;
+                }
+                continue;
+            }
+            headers_read = true;
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("Content-Length") {
+                    content_length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+
+        let Some(content_length) = content_length else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "missing or invalid Content-Length header",
+            ));
+        };
+        let mut body = vec![0; content_length];
+        self.reader.read_exact(&mut body)?;
+        Ok(Some(
+            serde_json::from_slice(&body).map_err(|e| format!("Parse error: {e}")),
+        ))
+    }
+
+    fn write_message(&mut self, message: &Value) -> io::Result<()> {
+        let body = message.to_string();
+        write!(self.writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.writer.flush()
+    }
+}
+
+fn initialize_result() -> Value {
+    json!({
+        "capabilities": {
+            // full document synchronization
+            "textDocumentSync": 1,
+            "documentFormattingProvider": true,
+            "documentRangeFormattingProvider": true,
+        },
+        "serverInfo": {
+            "name": "hledger-fmt",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+    })
+}
+
+fn invalid_params(message: &str) -> (i64, String) {
+    (INVALID_PARAMS, format!("Invalid params: {message}"))
+}
+
+/// Convert an LSP range (0-based lines) to a range of lines (1-based, inclusive).
+///
+/// A range ending at the start of a line, like when selecting whole lines,
+/// doesn't include that line.
+fn lines_range(range: &Value) -> Option<(usize, usize)> {
+    let start = range["start"]["line"].as_u64()? as usize;
+    let end = range["end"]["line"].as_u64()? as usize;
+    let end_character = range["end"]["character"].as_u64()?;
+    let end = if end > start && end_character == 0 {
+        end
+    } else {
+        end + 1
+    };
+    Some((start + 1, end))
+}
+
+/// If a document is a timeclock file, by the `.timeclock` extension of its URI
+fn is_timeclock_uri(uri: &str) -> bool {
+    std::path::Path::new(uri).extension() == Some(std::ffi::OsStr::new("timeclock"))
+}
+
+/// Path of a `file://` URI
+fn uri_to_path(uri: &str) -> Option<std::path::PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    // Windows paths are like `file:///C:/foo`
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => &path[1..],
+        _ => path,
+    };
+
+    // decode percent-encoded bytes
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            decoded.push(byte);
+        }
+    }
+    String::from_utf8(decoded)
+        .ok()
+        .map(std::path::PathBuf::from)
+}
+
+/// Position just after the last character of a text.
+fn end_position(text: &str) -> Value {
+    let line = text.matches('\n').count();
+    let last_line = text.rsplit('\n').next().unwrap_or_default();
+    json!({"line": line, "character": last_line.encode_utf16().count()})
+}
+
+/// Edit replacing only the lines that differ between `text` and `formatted`,
+/// so range formatting doesn't touch the lines outside the formatted nodes.
+fn changed_lines_edit(text: &str, formatted: &str) -> Value {
+    let lines = text.split_inclusive('\n').collect::<Vec<_>>();
+    let new_lines = formatted.split_inclusive('\n').collect::<Vec<_>>();
+    let prefix = lines
+        .iter()
+        .zip(&new_lines)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = lines[prefix..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let end = if suffix == 0 {
+        end_position(text)
+    } else {
+        json!({"line": lines.len() - suffix, "character": 0})
+    };
+    json!({
+        "range": {"start": {"line": prefix, "character": 0}, "end": end},
+        "newText": new_lines[prefix..new_lines.len() - suffix].concat(),
+    })
+}
+
+/// Convert a 0-based byte column of a line to UTF-16 code units,
+/// which are the default position encoding of LSP.
+fn utf16_column(line: &str, byte_column: usize) -> usize {
+    let mut end = byte_column.min(line.len());
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    line[..end].encode_utf16().count()
+}
@@ -1,5 +1,8 @@
 #[doc(hidden)]
 pub mod builder;
+mod config;
+#[cfg(feature = "lsp")]
+mod lsp;
 #[cfg(test)]
 mod tests;
 
@@ -21,6 +24,11 @@ pub fn run(cmd: clap::Command) -> i32 {
         }
     }
 
+    #[cfg(feature = "lsp")]
+    if let Some(("lsp", _)) = args.subcommand() {
+        return lsp::run();
+    }
+
     let files_arg: Vec<String> = if let Some(files) = args.get_many("files") {
         files.cloned().collect()
     } else {
@@ -32,6 +40,31 @@ pub fn run(cmd: clap::Command) -> i32 {
         .collect();
     let fix = args.get_flag("fix");
     let exit_zero_on_changes = args.get_flag("exit-zero-on-changes");
+    let verify = args.get_flag("verify");
+    let check_idempotent = args.get_flag("check-idempotent");
+    let lines = match args
+        .get_one::<String>("lines")
+        .map(|value| parse_lines_range(value))
+    {
+        Some(Ok(range)) => Some(range),
+        Some(Err(e)) => {
+            eprintln!("Invalid value for '--lines <START:END>': {e}");
+            return 1;
+        }
+        None => None,
+    };
+
+    let config = match args.get_one::<String>("config") {
+        Some(path) => match config::Config::from_file(std::path::Path::new(path)) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                eprintln!("{e}");
+                return 1;
+            }
+        },
+        None => None,
+    };
+    let mut configs = config::ConfigResolver::new(config);
 
     #[cfg(feature = "diff")]
     let no_diff = args.get_flag("no-diff");
@@ -74,23 +107,73 @@ pub fn run(cmd: clap::Command) -> i32 {
             }
         }
 
-        let parsed_or_err = crate::parser::parse_content(&content);
-        if let Err(e) = parsed_or_err {
-            if !something_printed {
-                something_printed = true;
-            } else {
-                eprintln!();
+        let (parsed, errors) = if is_timeclock_file(&file) {
+            let (nodes, errors) =
+                crate::parser::timeclock::parse_timeclock_content_recovering(&content);
+            (Parsed::Timeclock(nodes), errors)
+        } else {
+            let (nodes, errors) = crate::parser::parse_content_recovering(&content);
+            (Parsed::Journal(nodes), errors)
+        };
+        if !errors.is_empty() {
+            for e in &errors {
+                if !something_printed {
+                    something_printed = true;
+                } else {
+                    eprintln!();
+                }
+                eprintln!("{}", e.display_with_context(&content, &file));
             }
-            eprintln!(
-                "{}",
-                crate::parser::errors::build_error_context(&e, &content, &file)
-            );
             exitcode = 1;
             continue;
         }
-        let parsed = parsed_or_err.unwrap();
-        let format_opts = crate::FormatJournalOptions::new().with_estimated_length(content.len());
-        let buffer = crate::formatter::format_content_with_options(&parsed, &format_opts);
+        let config = match &file {
+            FilePathOrStdin::FilePath(path) => configs.resolve(Some(path)),
+            FilePathOrStdin::Stdin => configs.resolve(None),
+        };
+        let mut format_opts =
+            crate::FormatJournalOptions::new().with_estimated_length(content.len());
+        match config {
+            Ok(Some(config)) => format_opts = config.apply(format_opts),
+            Ok(None) => {}
+            Err(e) => {
+                if !something_printed {
+                    something_printed = true;
+                } else {
+                    eprintln!();
+                }
+                eprintln!("{e}");
+                exitcode = 1;
+                continue;
+            }
+        }
+        let buffer = match (&parsed, lines) {
+            (Parsed::Journal(nodes), Some((start, end))) => {
+                crate::formatter::format_content_range_with_options(
+                    nodes,
+                    &content,
+                    start..=end,
+                    &format_opts,
+                )
+            }
+            (Parsed::Journal(nodes), None) => {
+                crate::formatter::format_content_with_options(nodes, &format_opts)
+            }
+            (Parsed::Timeclock(nodes), Some((start, end))) => {
+                crate::formatter::timeclock::format_timeclock_content_range_with_options(
+                    nodes,
+                    &content,
+                    start..=end,
+                    &format_opts,
+                )
+            }
+            (Parsed::Timeclock(nodes), None) => {
+                crate::formatter::timeclock::format_timeclock_content_with_options(
+                    nodes,
+                    &format_opts,
+                )
+            }
+        };
         if buffer == content {
             #[cfg(feature = "diff")]
             {
@@ -103,6 +186,46 @@ pub fn run(cmd: clap::Command) -> i32 {
             continue;
         }
 
+        if verify && buffer != content {
+            let verified = match parsed {
+                Parsed::Journal(_) => crate::verify::verify_equivalent(&content, &buffer),
+                Parsed::Timeclock(_) => {
+                    crate::verify::verify_timeclock_equivalent(&content, &buffer)
+                }
+            };
+            if let Err(e) = verified {
+                if something_printed {
+                    eprintln!();
+                } else {
+                    something_printed = true;
+                }
+                eprintln!("Error verifying {file}: {e}");
+                exitcode = 1;
+                continue;
+            }
+        }
+
+        if check_idempotent {
+            let checked = match parsed {
+                Parsed::Journal(_) => {
+                    crate::verify::check_formatted_idempotent(&buffer, &format_opts)
+                }
+                Parsed::Timeclock(_) => {
+                    crate::verify::check_formatted_timeclock_idempotent(&buffer, &format_opts)
+                }
+            };
+            if let Err(e) = checked {
+                if something_printed {
+                    eprintln!();
+                } else {
+                    something_printed = true;
+                }
+                eprintln!("Error checking idempotency of {file}: {e}");
+                exitcode = 1;
+                continue;
+            }
+        }
+
         if exitcode == 0 && !exit_zero_on_changes {
             exitcode = 2;
         }
@@ -160,6 +283,22 @@ pub fn run(cmd: clap::Command) -> i32 {
     exitcode
 }
 
+/// Parsed content of a file, whose syntax is selected by its extension
+enum Parsed<'a> {
+    Journal(crate::parser::JournalFile<'a>),
+    Timeclock(crate::parser::timeclock::TimeclockFile<'a>),
+}
+
+/// If a file is a timeclock file, by its `.timeclock` extension
+fn is_timeclock_file(file: &FilePathOrStdin) -> bool {
+    match file {
+        FilePathOrStdin::FilePath(path) => {
+            path.extension() == Some(std::ffi::OsStr::new("timeclock"))
+        }
+        FilePathOrStdin::Stdin => false,
+    }
+}
+
 /// Print a colored diff between original and formatted content
 #[cfg(feature = "diff")]
 #[cold]
@@ -204,6 +343,26 @@ fn print_diff(original: &[u8], formatted: &str) {
     }
 }
 
+/// Parse a `START:END` range of lines.
+#[cold]
+fn parse_lines_range(value: &str) -> Result<(usize, usize), String> {
+    let (start, end) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected START:END, found '{value}'"))?;
+    let start = start
+        .parse::<usize>()
+        .map_err(|e| format!("invalid start line '{start}': {e}"))?;
+    let end = end
+        .parse::<usize>()
+        .map_err(|e| format!("invalid end line '{end}': {e}"))?;
+    if start == 0 {
+        return Err("line numbers start at 1".to_string());
+    } else if end < start {
+        return Err(format!("end line {end} is lower than start line {start}"));
+    }
+    Ok((start, end))
+}
+
 /// Gather input files from arguments or stdin
 /// Returns 0 on success, non-zero exit code on error
 #[cold]
@@ -229,7 +388,7 @@ fn gather_input_files(
             if files.is_empty() {
                 eprintln!(
                     "No hledger journal files found in the current directory nor its subdirectories.\n\
-                     Ensure that they have extensions '.hledger', '.journal' or '.j'."
+                     Ensure that they have extensions '.hledger', '.journal', '.j' or '.timeclock'."
                 );
                 return 1;
             }
@@ -269,7 +428,7 @@ fn gather_input_files(
 
                 eprintln!(
                     "No hledger journal files found looking for next files and/or directories: {files_arg_as_strings:#?}.\n\
-                     Ensure that they have extensions '.hledger', '.journal' or '.j'.",
+                     Ensure that they have extensions '.hledger', '.journal', '.j' or '.timeclock'.",
                 );
                 return 1;
             }
@@ -295,6 +454,7 @@ fn gather_files_from_directory_and_subdirectories<'a>(
     let journal = std::ffi::OsStr::new("journal");
     let hledger = std::ffi::OsStr::new("hledger");
     let j = std::ffi::OsStr::new("j");
+    let timeclock = std::ffi::OsStr::new("timeclock");
 
     match std::fs::read_dir(root) {
         Ok(read_dir_result) => {
@@ -314,7 +474,7 @@ fn gather_files_from_directory_and_subdirectories<'a>(
                         } else if path.is_file() {
                             let ext = path.extension();
                             if let Some(ext) = ext {
-                                if [journal, hledger, j].contains(&ext) {
+                                if [journal, hledger, j, timeclock].contains(&ext) {
                                     let file_path = FilePathOrStdin::from(path);
                                     let maybe_file_content = read_file(&file_path);
                                     if let Ok(content) = maybe_file_content {
@@ -121,6 +121,33 @@ fn walks_directory() {
     );
 }
 
+/// Files with the `.timeclock` extension are formatted as timeclock files.
+#[test]
+fn formats_timeclock_files() {
+    let dir = tempdir();
+    let file = dir.path().join("work.timeclock");
+    std::fs::write(
+        &file,
+        "i 2024-01-01 9:00 client:acme   meeting\no 2024-01-01 10:30\n",
+    )
+    .unwrap();
+    let mut cmd = init_cmd(&dir);
+    let cmd = cmd
+        .arg("--no-diff")
+        .arg("--verify")
+        .arg("--check-idempotent");
+
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        &stdout,
+        "i 2024-01-01 9:00  client:acme  meeting
+o 2024-01-01 10:30
+"
+    );
+}
+
 /// `--exit-zero-on-changes` exits with code 0 when there are changes.
 #[test]
 fn exit_zero_on_changes_with_changes() {
@@ -180,3 +207,453 @@ fn no_diff_and_exit_zero_on_changes() {
 "
     );
 }
+
+/// Syntax errors are printed with the context where they happened.
+#[test]
+fn syntax_error_prints_context() {
+    let dir = tempdir();
+    let file = dir.path().join("test.journal");
+    std::fs::write(&file, "2015-10-16 food\n  expenses:food  $10\n\n  foo\n").unwrap();
+    let mut cmd = init_cmd(&dir);
+
+    let output = cmd.arg("test.journal").output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        &stderr,
+        "hledger-fmt error: test.journal:4:3:
+  |\x20
+4 |   foo
+  |   ^
+Unexpected character 'f'
+Expected '#', ';' or newline
+"
+    );
+}
+
+/// All the syntax errors of a file are printed in one pass.
+#[test]
+fn syntax_errors_are_all_printed() {
+    let dir = tempdir();
+    let file = dir.path().join("test.journal");
+    std::fs::write(
+        &file,
+        "  foo\n2015-10-16 food\n  expenses:food     $10\n\n  bar\n",
+    )
+    .unwrap();
+    let mut cmd = init_cmd(&dir);
+
+    let output = cmd.arg("test.journal").output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        &stderr,
+        "hledger-fmt error: test.journal:1:3:
+1 |   foo
+  |   ^
+Unexpected character 'f'
+Expected '#', ';' or newline
+
+hledger-fmt error: test.journal:5:3:
+  |\x20
+5 |   bar
+  |   ^
+Unexpected character 'b'
+Expected '#', ';' or newline
+"
+    );
+}
+
+/// `--lines` only formats the nodes that overlap the range.
+#[test]
+fn lines_formats_only_range() {
+    let dir = tempdir();
+    let file = dir.path().join("test.journal");
+    let content =
+        "2015-10-16 food\n  expenses:food     $10\n\n2015-10-17 rent\n  expenses:rent     $500\n";
+    std::fs::write(&file, content).unwrap();
+    let mut cmd = init_cmd(&dir);
+    let cmd = cmd
+        .arg("--lines")
+        .arg("4:4")
+        .arg("--fix")
+        .arg("test.journal");
+
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "2015-10-16 food\n  expenses:food     $10\n\n2015-10-17 rent\n  expenses:rent  $500\n"
+    );
+}
+
+/// `--lines` with an invalid range errors.
+#[test]
+fn lines_invalid_range() {
+    let dir = tempdir();
+    let file = dir.path().join("test.journal");
+    std::fs::write(&file, "2015-10-16 food\n  expenses:food  $10\n").unwrap();
+
+    for (value, error) in [
+        ("4", "expected START:END, found '4'"),
+        ("0:2", "line numbers start at 1"),
+        ("3:2", "end line 2 is lower than start line 3"),
+        ("a:2", "invalid start line 'a'"),
+    ] {
+        let mut cmd = init_cmd(&dir);
+        let output = cmd.arg("--lines").arg(value).output().unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(error), "{stderr}");
+    }
+}
+
+/// `--verify` lets equivalent formatted content through.
+#[test]
+fn verify_equivalent_content() {
+    let dir = tempdir();
+    let file = dir.path().join("test.journal");
+    std::fs::write(&file, "2015-10-16 food  ; note\n  expenses:food     $10\n").unwrap();
+    let mut cmd = init_cmd(&dir);
+    let output = cmd
+        .arg("--verify")
+        .arg("--fix")
+        .arg("test.journal")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "2015-10-16 food  ; note\n  expenses:food  $10\n"
+    );
+}
+
+/// `--check-idempotent` lets content that is stable after formatting through.
+#[test]
+fn check_idempotent_content() {
+    let dir = tempdir();
+    let file = dir.path().join("test.journal");
+    std::fs::write(&file, "2015-10-16 food\n  expenses:food     $10\n").unwrap();
+    let mut cmd = init_cmd(&dir);
+    let output = cmd
+        .arg("--check-idempotent")
+        .arg("--fix")
+        .arg("test.journal")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "2015-10-16 food\n  expenses:food  $10\n"
+    );
+}
+
+/// `--check-idempotent` can't be used with `--lines`.
+#[test]
+fn check_idempotent_conflicts_with_lines() {
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    let output = cmd
+        .arg("--check-idempotent")
+        .arg("--lines")
+        .arg("1:2")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("cannot be used with"), "{stderr}");
+}
+
+#[cfg(feature = "lsp")]
+fn lsp_request(messages: &[serde_json::Value]) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let body = message.to_string();
+            format!("Content-Length: {}\r\n\r\n{body}", body.len())
+        })
+        .collect()
+}
+
+#[cfg(feature = "lsp")]
+fn lsp_responses(stdout: &[u8]) -> Vec<serde_json::Value> {
+    let stdout = String::from_utf8_lossy(stdout);
+    let mut responses = Vec::new();
+    let mut rest = stdout.as_ref();
+    while let Some((header, body)) = rest.split_once("\r\n\r\n") {
+        let length = header
+            .strip_prefix("Content-Length: ")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap();
+        responses.push(serde_json::from_str(&body[..length]).unwrap());
+        rest = &body[length..];
+    }
+    responses
+}
+
+/// `lsp` formats documents and publishes syntax errors as diagnostics.
+#[cfg(feature = "lsp")]
+#[test]
+fn lsp_formatting_and_diagnostics() {
+    use serde_json::json;
+
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    let uri = "file:///test.journal";
+    let content =
+        "2015-10-16 food\n  expenses:food     $10\n\n2015-10-17 rent\n  expenses:rent     $500\n";
+    let stdin = lsp_request(&[
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+        json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+        json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+            "textDocument": {"uri": uri, "languageId": "hledger", "version": 1, "text": content},
+        }}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "textDocument/formatting", "params": {
+            "textDocument": {"uri": uri},
+            "options": {"tabSize": 2, "insertSpaces": true},
+        }}),
+        json!({"jsonrpc": "2.0", "id": 3, "method": "textDocument/rangeFormatting", "params": {
+            "textDocument": {"uri": uri},
+            "range": {"start": {"line": 3, "character": 0}, "end": {"line": 5, "character": 0}},
+            "options": {"tabSize": 2, "insertSpaces": true},
+        }}),
+        json!({"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+            "textDocument": {"uri": uri, "version": 2},
+            "contentChanges": [{"text": "  foo\n"}],
+        }}),
+        json!({"jsonrpc": "2.0", "id": 4, "method": "textDocument/formatting", "params": {
+            "textDocument": {"uri": uri},
+            "options": {"tabSize": 2, "insertSpaces": true},
+        }}),
+        json!({"jsonrpc": "2.0", "id": 5, "method": "unknown/method", "params": {}}),
+        json!({"jsonrpc": "2.0", "id": 6, "method": "shutdown"}),
+        json!({"jsonrpc": "2.0", "method": "exit"}),
+    ]);
+
+    let output = cmd.arg("lsp").write_stdin(stdin).output().unwrap();
+    assert!(output.status.success());
+    let responses = lsp_responses(&output.stdout);
+    assert_eq!(responses.len(), 8);
+
+    assert_eq!(responses[0]["id"], 1);
+    assert_eq!(
+        responses[0]["result"]["capabilities"]["documentFormattingProvider"],
+        true
+    );
+    assert_eq!(
+        responses[1],
+        json!({"jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": {
+            "uri": uri,
+            "diagnostics": [],
+        }})
+    );
+    let end = json!({"line": 5, "character": 0});
+    assert_eq!(
+        responses[2]["result"],
+        json!([{
+            "range": {"start": {"line": 0, "character": 0}, "end": end},
+            "newText": "2015-10-16 food\n  expenses:food  $10\n\n2015-10-17 rent\n  expenses:rent  $500\n",
+        }])
+    );
+    // range formatting only edits the changed lines
+    assert_eq!(
+        responses[3]["result"],
+        json!([{
+            "range": {"start": {"line": 4, "character": 0}, "end": end},
+            "newText": "  expenses:rent  $500\n",
+        }])
+    );
+    assert_eq!(
+        responses[4]["params"]["diagnostics"],
+        json!([{
+            "range": {
+                "start": {"line": 0, "character": 2},
+                "end": {"line": 0, "character": 3},
+            },
+            "severity": 1,
+            "source": "hledger-fmt",
+            "message": "Unexpected character 'f'\nExpected '#', ';' or newline",
+        }])
+    );
+    // documents with syntax errors are not formatted
+    assert_eq!(
+        responses[5],
+        json!({"jsonrpc": "2.0", "id": 4, "result": null})
+    );
+    assert_eq!(responses[6]["error"]["code"], -32601);
+    assert_eq!(
+        responses[7],
+        json!({"jsonrpc": "2.0", "id": 6, "result": null})
+    );
+}
+
+/// `lsp` handles documents with the `.timeclock` extension as timeclock files.
+#[cfg(feature = "lsp")]
+#[test]
+fn lsp_timeclock_documents() {
+    use serde_json::json;
+
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    let uri = "file:///work.timeclock";
+    let content = "i 2024-01-01 9:00 client:acme   meeting\no 2024-01-01 10:30\n";
+    let stdin = lsp_request(&[
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+        json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+            "textDocument": {"uri": uri, "languageId": "timeclock", "version": 1, "text": content},
+        }}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "textDocument/formatting", "params": {
+            "textDocument": {"uri": uri},
+            "options": {"tabSize": 2, "insertSpaces": true},
+        }}),
+        json!({"jsonrpc": "2.0", "id": 3, "method": "shutdown"}),
+        json!({"jsonrpc": "2.0", "method": "exit"}),
+    ]);
+
+    let output = cmd.arg("lsp").write_stdin(stdin).output().unwrap();
+    assert!(output.status.success());
+    let responses = lsp_responses(&output.stdout);
+    assert_eq!(responses.len(), 4);
+    assert_eq!(responses[1]["params"]["diagnostics"], json!([]));
+    assert_eq!(
+        responses[2]["result"],
+        json!([{
+            "range": {"start": {"line": 0, "character": 0}, "end": {"line": 2, "character": 0}},
+            "newText": "i 2024-01-01 9:00  client:acme  meeting\no 2024-01-01 10:30\n",
+        }])
+    );
+}
+
+/// `lsp` replies to messages that are not valid JSON with parse errors and
+/// keeps serving, but stops at messages without a valid `Content-Length`
+/// as the rest of the input can't be read.
+#[cfg(feature = "lsp")]
+#[test]
+fn lsp_parse_errors() {
+    use serde_json::json;
+
+    let dir = tempdir();
+    let mut cmd = init_cmd(&dir);
+    let stdin = format!(
+        "Content-Length: 5\r\n\r\n{{foo}}{}Content-Length: foo\r\n\r\n{}",
+        lsp_request(&[json!({"jsonrpc": "2.0", "id": 1, "method": "shutdown"})]),
+        lsp_request(&[json!({"jsonrpc": "2.0", "method": "exit"})]),
+    );
+
+    let output = cmd.arg("lsp").write_stdin(stdin).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("missing or invalid Content-Length"),
+        "{stderr}"
+    );
+    let responses = lsp_responses(&output.stdout);
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["id"], serde_json::Value::Null);
+    assert_eq!(responses[0]["error"]["code"], -32700);
+    assert_eq!(
+        responses[1],
+        json!({"jsonrpc": "2.0", "id": 1, "result": null})
+    );
+}
+
+/// The `.hledger-fmt.toml` files of the directory of each file and its
+/// parents are used, the nearest ones overriding the settings of the others.
+#[test]
+fn config_file_discovery() {
+    let dir = tempdir();
+    std::fs::write(
+        dir.path().join(".hledger-fmt.toml"),
+        "entry_spacing = 4\ndate_format = 'YYYY-MM-DD'\n",
+    )
+    .unwrap();
+    let file = dir.path().join("test.journal");
+    std::fs::write(&file, "2015/10/16 food\n  expenses:food  $10\n").unwrap();
+    let subdir = dir.path().join("subdir");
+    std::fs::create_dir(&subdir).unwrap();
+    std::fs::write(
+        subdir.join(".hledger-fmt.toml"),
+        "[tool.hledger-fmt]\nentry_spacing = 3\n",
+    )
+    .unwrap();
+    let subfile = subdir.join("test.journal");
+    std::fs::write(&subfile, "2015/10/16 food\n  expenses:food  $10\n").unwrap();
+
+    let mut cmd = init_cmd(&dir);
+    let output = cmd.arg("--fix").output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "2015-10-16 food\n  expenses:food    $10\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(&subfile).unwrap(),
+        "2015-10-16 food\n  expenses:food   $10\n"
+    );
+
+    // STDIN uses the configuration of the current directory
+    let mut cmd = init_cmd(&dir);
+    let output = cmd
+        .arg("-")
+        .arg("--no-diff")
+        .write_stdin("2015-10-16 food\n  expenses:food  $10\n")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "2015-10-16 food\n  expenses:food    $10\n"
+    );
+}
+
+/// `--config` overrides the configuration files discovery.
+#[test]
+fn config_option() {
+    let dir = tempdir();
+    std::fs::write(dir.path().join(".hledger-fmt.toml"), "entry_spacing = 4\n").unwrap();
+    std::fs::write(dir.path().join("custom.toml"), "entry_spacing = 1\n").unwrap();
+    let file = dir.path().join("test.journal");
+    std::fs::write(&file, "2015-10-16 food\n  expenses:food  $10\n").unwrap();
+
+    let mut cmd = init_cmd(&dir);
+    let output = cmd
+        .arg("--config")
+        .arg("custom.toml")
+        .arg("--no-diff")
+        .arg("test.journal")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "2015-10-16 food\n  expenses:food $10\n"
+    );
+}
+
+/// Invalid configuration files are reported.
+#[test]
+fn config_file_invalid() {
+    let dir = tempdir();
+    std::fs::write(dir.path().join(".hledger-fmt.toml"), "entry_spacin = 4\n").unwrap();
+    let file = dir.path().join("test.journal");
+    std::fs::write(&file, "2015-10-16 food\n  expenses:food  $10\n").unwrap();
+
+    let mut cmd = init_cmd(&dir);
+    let output = cmd.arg("test.journal").output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("Error parsing config file ")
+            && stderr.ends_with(".hledger-fmt.toml:1: unknown setting 'entry_spacin'\n"),
+        "{stderr}"
+    );
+
+    let mut cmd = init_cmd(&dir);
+    let output = cmd.arg("--config").arg("missing.toml").output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("Error reading config file missing.toml: "),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,152 @@
+//! Dates of transactions and market prices.
+//!
+//! hledger accepts dates with `-`, `/` or `.` separators, with or without
+//! zero-padding and with an optional year, like `2024-01-05`, `2024/1/5`
+//! or `1.5`.
+
+use crate::Vec;
+
+/// Format in which dates are written
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DateFormat {
+    /// `YYYY-MM-DD`
+    #[default]
+    Iso,
+    /// `YYYY/MM/DD`
+    Slash,
+    /// `YYYY.MM.DD`
+    Dot,
+}
+
+impl DateFormat {
+    /// Separator between the year, month and day
+    #[inline]
+    fn separator(self) -> u8 {
+        match self {
+            DateFormat::Iso => b'-',
+            DateFormat::Slash => b'/',
+            DateFormat::Dot => b'.',
+        }
+    }
+}
+
+/// Split a date (digits separated by `-`, `/` or `.`) from the start of a slice
+pub(crate) fn split_date(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    if !bytes.first().is_some_and(u8::is_ascii_digit) {
+        return None;
+    }
+    let end = bytes
+        .iter()
+        .position(|b| !b.is_ascii_digit() && !is_date_separator(*b))
+        .unwrap_or(bytes.len());
+    Some(bytes.split_at(end))
+}
+
+#[inline]
+fn is_date_separator(byte: u8) -> bool {
+    byte == b'-' || byte == b'/' || byte == b'.'
+}
+
+/// Parts of a valid date
+struct DateParts<'a> {
+    year: Option<&'a [u8]>,
+    month: &'a [u8],
+    day: &'a [u8],
+}
+
+/// Split a date in its optional year, month and day
+///
+/// Returns `None` if the date is not valid, so it must be kept as is.
+fn date_parts(date: &[u8]) -> Option<DateParts<'_>> {
+    let separator = *date.iter().find(|b| is_date_separator(**b))?;
+    let mut parts = date.split(|b| *b == separator);
+    let first = parts.next()?;
+    let second = parts.next()?;
+    let (year, month, day) = match parts.next() {
+        Some(third) => (Some(first), second, third),
+        None => (None, first, second),
+    };
+    if parts.next().is_some()
+        || year.is_some_and(|year| year.is_empty())
+        || !(1..=2).contains(&month.len())
+        || !(1..=2).contains(&day.len())
+        || ![month, day]
+            .iter()
+            .chain(year.as_ref())
+            .all(|part| part.iter().all(u8::is_ascii_digit))
+    {
+        return None;
+    }
+    Some(DateParts { year, month, day })
+}
+
+/// Number of bytes of `date` once written with [`extend_date`]
+pub(crate) fn normalized_date_len(date: &[u8]) -> usize {
+    match date_parts(date) {
+        Some(DateParts {
+            year: Some(year), ..
+        }) => year.len() + 6,
+        Some(DateParts { year: None, .. }) => 5,
+        None => date.len(),
+    }
+}
+
+/// Write `date` in the format `format` with zero-padded month and day.
+///
+/// Dates that are not valid are written as is.
+pub(crate) fn extend_date(buffer: &mut Vec<u8>, date: &[u8], format: DateFormat) {
+    let Some(DateParts { year, month, day }) = date_parts(date) else {
+        buffer.extend_from_slice(date);
+        return;
+    };
+    let separator = format.separator();
+    if let Some(year) = year {
+        buffer.extend_from_slice(year);
+        buffer.push(separator);
+    }
+    for (i, part) in [month, day].into_iter().enumerate() {
+        if i > 0 {
+            buffer.push(separator);
+        }
+        if part.len() == 1 {
+            buffer.push(b'0');
+        }
+        buffer.extend_from_slice(part);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(date: &str, format: DateFormat) -> String {
+        let mut buffer = Vec::new();
+        extend_date(&mut buffer, date.as_bytes(), format);
+        assert_eq!(buffer.len(), normalized_date_len(date.as_bytes()));
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_extend_date() {
+        let test_cases = vec![
+            ("2024-01-05", DateFormat::Iso, "2024-01-05"),
+            ("2024/1/5", DateFormat::Iso, "2024-01-05"),
+            ("2024.1.15", DateFormat::Slash, "2024/01/15"),
+            ("2024-12-5", DateFormat::Dot, "2024.12.05"),
+            ("1/5", DateFormat::Iso, "01-05"),
+            // not valid, kept as is
+            ("2024-1/5", DateFormat::Iso, "2024-1/5"),
+            ("2024-123-5", DateFormat::Iso, "2024-123-5"),
+            ("2024", DateFormat::Iso, "2024"),
+            ("2024-01-05-01", DateFormat::Iso, "2024-01-05-01"),
+        ];
+
+        for (date, format, expected) in test_cases {
+            assert_eq!(
+                normalize(date, format),
+                expected,
+                "extend_date({date:?}, {format:?})"
+            );
+        }
+    }
+}
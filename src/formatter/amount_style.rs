@@ -0,0 +1,480 @@
+//! Normalization of amounts to the styles declared by `commodity` directives.
+//!
+//! A style is read from the sample amount of a `commodity` directive, like
+//! `commodity 1.000,00 EUR`, or from its `format` subdirective. Amounts of
+//! the commodity are rewritten with the symbol side and spacing, digit
+//! grouping and decimal precision of the sample. Amounts that can't be read
+//! unambiguously or that would change their value are left untouched.
+
+use crate::parser::amount::amount_commodity;
+use crate::parser::{Directive, DirectiveNode, JournalCstNode};
+use crate::Vec;
+
+/// Style of the amounts of a commodity
+struct AmountStyle<'a> {
+    /// Commodity symbol as written in the sample, with quotes if any
+    symbol: &'a [u8],
+    /// Commodity symbol without quotes
+    commodity: &'a [u8],
+    symbol_on_left: bool,
+    /// If the symbol is separated from the number by a space
+    spaced: bool,
+    decimal_mark: Option<u8>,
+    digit_group_mark: Option<u8>,
+    /// Number of digits of each group
+    digit_group_size: usize,
+    /// Number of decimal digits
+    precision: usize,
+}
+
+/// Styles declared by the `commodity` directives formatted so far
+#[derive(Default)]
+pub(crate) struct AmountStyles<'a> {
+    styles: Vec<AmountStyle<'a>>,
+}
+
+impl<'a> AmountStyles<'a> {
+    /// Record the styles declared by a node, if any
+    pub(crate) fn add_node(&mut self, node: &JournalCstNode<'a>) {
+        if let JournalCstNode::DirectivesGroup { nodes, .. } = node {
+            self.add_directives(nodes);
+        }
+    }
+
+    /// Record the styles declared by the directives of a group
+    fn add_directives(&mut self, nodes: &[DirectiveNode<'a>]) {
+        let mut inside_commodity = false;
+        for node in nodes {
+            match node {
+                DirectiveNode::Directive(Directive { name, content, .. }) => {
+                    inside_commodity = name.as_bytes() == b"commodity";
+                    if inside_commodity {
+                        self.add_sample(content.as_bytes());
+                    }
+                }
+                DirectiveNode::Subdirective { content, .. } if inside_commodity => {
+                    let content = content.as_bytes();
+                    if let Some(sample) = content.strip_prefix(b"format") {
+                        if sample.first().is_some_and(u8::is_ascii_whitespace) {
+                            let end = memchr::memchr2(b';', b'#', sample).unwrap_or(sample.len());
+                            self.add_sample(&sample[..end]);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Record the style of a sample amount, like `1.000,00 EUR`
+    fn add_sample(&mut self, sample: &'a [u8]) {
+        let Some(amount) = split_amount(trim(sample)) else {
+            return;
+        };
+        if amount.sign.is_some() {
+            return;
+        }
+
+        // the rightmost mark is the decimal mark
+        let decimal_mark_position = amount.number.iter().rposition(|c| *c == b'.' || *c == b',');
+        let (integer, decimal_mark, precision) = match decimal_mark_position {
+            Some(position) => (
+                &amount.number[..position],
+                Some(amount.number[position]),
+                amount.number.len() - position - 1,
+            ),
+            None => (amount.number, None, 0),
+        };
+        let digit_group_mark = integer.iter().find(|c| !c.is_ascii_digit()).copied();
+        let digit_group_size = match digit_group_mark {
+            Some(mark) => integer.rsplit(|c| *c == mark).next().map_or(3, <[u8]>::len),
+            None => 3,
+        };
+
+        self.styles.push(AmountStyle {
+            symbol: amount.symbol,
+            commodity: amount_commodity(sample),
+            symbol_on_left: amount.symbol_on_left,
+            spaced: amount.spaced,
+            decimal_mark,
+            digit_group_mark,
+            digit_group_size: digit_group_size.max(1),
+            precision,
+        });
+    }
+
+    /// If no `commodity` directive has declared a style
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.styles.is_empty()
+    }
+
+    /// Rewrite an amount with the style of its commodity.
+    ///
+    /// Returns the restyled amount and the position of its decimal mark, or
+    /// of the end of its number if it has no decimals, where it's aligned.
+    /// Returns `None` if the commodity has no style, the amount can't be read
+    /// unambiguously or rewriting it would change its value.
+    pub(crate) fn restyle(&self, amount: &[u8]) -> Option<(Vec<u8>, usize)> {
+        if self.styles.is_empty() {
+            return None;
+        }
+        let commodity = amount_commodity(amount);
+        let style = self
+            .styles
+            .iter()
+            .rev()
+            .find(|s| s.commodity == commodity)?;
+        let parts = split_amount(trim(amount))?;
+        let number = Number::parse(parts.number, style.decimal_mark)?;
+
+        let mut restyled = Vec::with_capacity(amount.len() + 8);
+        if let Some(sign) = parts.sign {
+            restyled.push(sign);
+        }
+        if style.symbol_on_left && !style.symbol.is_empty() {
+            restyled.extend_from_slice(style.symbol);
+            if style.spaced {
+                restyled.push(b' ');
+            }
+        }
+        let number_start = restyled.len();
+        let decimals_start = style.extend_number(&mut restyled, &number);
+        let number_end = restyled.len();
+        if !style.symbol_on_left && !style.symbol.is_empty() {
+            if style.spaced {
+                restyled.push(b' ');
+            }
+            restyled.extend_from_slice(style.symbol);
+        }
+
+        // refuse any change of the value the amount has on its own, when its
+        // syntax is unambiguous without the style
+        let value = Number::parse(parts.number, None).unwrap_or(number);
+        if Number::parse(&restyled[number_start..number_end], style.decimal_mark)? != value {
+            return None;
+        }
+        Some((restyled, decimals_start))
+    }
+
+    /// Value of an amount of a commodity with a style, read with its decimal
+    /// mark and written as its commodity and number with a `.` decimal mark
+    /// and without digit group marks, like `EUR -1234.5`.
+    ///
+    /// Unlike the restyled amount, it doesn't depend on the style written,
+    /// so it can tell if rewriting an amount changed its value. Returns `None`
+    /// if the commodity has no style or the amount can't be read unambiguously.
+    pub(crate) fn value(&self, amount: &[u8]) -> Option<Vec<u8>> {
+        let commodity = amount_commodity(amount);
+        let style = self
+            .styles
+            .iter()
+            .rev()
+            .find(|s| s.commodity == commodity)?;
+        let parts = split_amount(trim(amount))?;
+        let number = Number::parse(parts.number, style.decimal_mark)?;
+
+        let mut value = Vec::with_capacity(commodity.len() + number.integer.len() + 8);
+        value.extend_from_slice(commodity);
+        value.push(b' ');
+        if parts.sign == Some(b'-') {
+            value.push(b'-');
+        }
+        value.extend_from_slice(&number.integer);
+        if !number.fraction.is_empty() {
+            value.push(b'.');
+            value.extend_from_slice(&number.fraction);
+        }
+        Some(value)
+    }
+}
+
+impl AmountStyle<'_> {
+    /// Write a number, returning the position of its decimal mark in `buffer`
+    /// or the end of the number if it has no decimals.
+    fn extend_number(&self, buffer: &mut Vec<u8>, number: &Number) -> usize {
+        let integer = &number.integer;
+        for (i, digit) in integer.iter().enumerate() {
+            let remaining = integer.len() - i;
+            if i > 0 && remaining % self.digit_group_size == 0 {
+                if let Some(mark) = self.digit_group_mark {
+                    buffer.push(mark);
+                }
+            }
+            buffer.push(*digit);
+        }
+
+        let fraction = number.fraction.as_slice();
+        let padding = self.precision.saturating_sub(fraction.len());
+        let decimals_start = buffer.len();
+        if fraction.is_empty() && padding == 0 {
+            return decimals_start;
+        }
+        let decimal_mark = self.decimal_mark.unwrap_or(match self.digit_group_mark {
+            Some(b'.') => b',',
+            _ => b'.',
+        });
+        buffer.push(decimal_mark);
+        buffer.extend_from_slice(fraction);
+        buffer.extend(core::iter::repeat(b'0').take(padding));
+        decimals_start
+    }
+}
+
+/// Parts of an amount like `-$1,000.00`
+struct AmountParts<'a> {
+    sign: Option<u8>,
+    symbol: &'a [u8],
+    symbol_on_left: bool,
+    spaced: bool,
+    number: &'a [u8],
+}
+
+/// Split an amount in its sign, commodity symbol and number
+fn split_amount(amount: &[u8]) -> Option<AmountParts<'_>> {
+    let first_digit = amount.iter().position(u8::is_ascii_digit)?;
+    let mut last_digit = amount.iter().rposition(u8::is_ascii_digit)?;
+    if matches!(amount.get(last_digit + 1), Some(b'.' | b',')) {
+        // number with decimal mark and without decimals, like `1000.`
+        last_digit += 1;
+    }
+    let number = &amount[first_digit..=last_digit];
+    if number
+        .iter()
+        .any(|c| !(c.is_ascii_digit() || matches!(c, b'.' | b',' | b' ')))
+    {
+        return None;
+    }
+
+    let mut prefix = &amount[..first_digit];
+    let suffix = &amount[last_digit + 1..];
+    let mut sign = None;
+    if let Some((&c @ (b'-' | b'+'), rest)) = prefix.split_first() {
+        sign = Some(c);
+        prefix = rest;
+    }
+    let prefix_without_spaces = trim(prefix);
+    let prefix_without_spaces = match prefix_without_spaces.split_last() {
+        Some((&c @ (b'-' | b'+'), rest)) if sign.is_none() => {
+            sign = Some(c);
+            trim(rest)
+        }
+        _ => prefix_without_spaces,
+    };
+    if prefix_without_spaces.contains(&b'-') || prefix_without_spaces.contains(&b'+') {
+        return None;
+    }
+
+    let suffix_without_spaces = trim(suffix);
+    match (
+        prefix_without_spaces.is_empty(),
+        suffix_without_spaces.is_empty(),
+    ) {
+        (false, false) => None,
+        (false, true) => Some(AmountParts {
+            sign,
+            symbol: prefix_without_spaces,
+            symbol_on_left: true,
+            spaced: prefix.last().is_some_and(u8::is_ascii_whitespace),
+            number,
+        }),
+        (true, _) => Some(AmountParts {
+            sign,
+            symbol: suffix_without_spaces,
+            symbol_on_left: false,
+            spaced: suffix.first().is_some_and(u8::is_ascii_whitespace),
+            number,
+        }),
+    }
+}
+
+fn trim(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |i| i + 1);
+    &bytes[start..end]
+}
+
+/// Value of a number, without digit group marks
+#[derive(Debug, PartialEq)]
+struct Number {
+    /// Digits of the integer part, without leading zeros
+    integer: Vec<u8>,
+    /// Digits of the decimal part, without trailing zeros
+    fraction: Vec<u8>,
+}
+
+impl Number {
+    /// Read a number with the decimal mark `decimal_mark`, or with the mark
+    /// its own syntax implies if `None`.
+    ///
+    /// With a decimal mark, other marks must be digit group marks, so a
+    /// number like `1234.5` read with `,` is ambiguous. Returns `None` if the
+    /// number is ambiguous.
+    fn parse(number: &[u8], decimal_mark: Option<u8>) -> Option<Self> {
+        let marks = number
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.is_ascii_digit())
+            .map(|(i, c)| (i, *c))
+            .collect::<Vec<_>>();
+
+        let decimal_mark_position = match decimal_mark {
+            Some(mark) => {
+                let mut positions = marks.iter().filter(|(_, c)| *c == mark);
+                match (positions.next(), positions.next()) {
+                    (Some((position, _)), None) => Some(*position),
+                    (Some(_), Some(_)) => return None,
+                    (None, _) if is_grouped(number) => None,
+                    (None, _) => return None,
+                }
+            }
+            None => match marks.last() {
+                Some((position, c @ (b'.' | b',')))
+                    if !is_grouped(number) || marks.iter().any(|(_, m)| m != c) =>
+                {
+                    Some(*position)
+                }
+                Some((_, b'.' | b',')) if marks.len() == 1 => return None,
+                _ => None,
+            },
+        };
+
+        let (integer, fraction) = match decimal_mark_position {
+            Some(position) => (&number[..position], &number[position + 1..]),
+            None => (number, &[][..]),
+        };
+        if !fraction.iter().all(u8::is_ascii_digit) || !is_grouped(integer) {
+            return None;
+        }
+
+        let integer = integer
+            .iter()
+            .filter(|c| c.is_ascii_digit())
+            .skip_while(|c| **c == b'0')
+            .copied()
+            .collect::<Vec<_>>();
+        let fraction_end = fraction
+            .iter()
+            .rposition(|c| *c != b'0')
+            .map_or(0, |i| i + 1);
+        Some(Self {
+            integer: if integer.is_empty() {
+                Vec::from([b'0'])
+            } else {
+                integer
+            },
+            fraction: fraction[..fraction_end].to_vec(),
+        })
+    }
+}
+
+/// If the digits of a number are grouped by a single mark in groups of three
+/// after the first one, or are not grouped at all
+fn is_grouped(number: &[u8]) -> bool {
+    let Some(mark) = number.iter().find(|c| !c.is_ascii_digit()) else {
+        return true;
+    };
+    let mut groups = number.split(|c| c == mark);
+    let first = groups.next().unwrap_or_default();
+    !first.is_empty()
+        && first.len() <= 3
+        && first.iter().all(u8::is_ascii_digit)
+        && groups.all(|group| group.len() == 3 && group.iter().all(u8::is_ascii_digit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styles(samples: &[&'static str]) -> AmountStyles<'static> {
+        let mut styles = AmountStyles::default();
+        for sample in samples {
+            styles.add_sample(sample.as_bytes());
+        }
+        styles
+    }
+
+    fn restyle(styles: &AmountStyles, amount: &str) -> Option<String> {
+        styles
+            .restyle(amount.as_bytes())
+            .map(|(restyled, _)| String::from_utf8(restyled).unwrap())
+    }
+
+    #[test]
+    fn restyle_amounts() {
+        let styles = styles(&[
+            "1.000,00 EUR",
+            "$1,000.00",
+            "1000 JPY",
+            "\"Chocolate Frogs\" 1",
+        ]);
+        let test_cases = vec![
+            ("EUR 1234,5", Some("1.234,50 EUR")),
+            ("1234,5EUR", Some("1.234,50 EUR")),
+            ("-1.234 EUR", Some("-1.234,00 EUR")),
+            ("1234.50$", Some("$1,234.50")),
+            ("$-1234.5", Some("-$1,234.50")),
+            ("-$0.125", Some("-$0.125")),
+            ("$1,000,000", Some("$1,000,000.00")),
+            ("$1,234", Some("$1,234.00")),
+            ("12345 JPY", Some("12345 JPY")),
+            ("3 \"Chocolate Frogs\"", Some("\"Chocolate Frogs\" 3")),
+            // ambiguous
+            ("EUR 1234.5", None),
+            ("1234,50$", None),
+            ("1,234 JPY", None),
+            ("1.234.5 EUR", None),
+            // unknown commodity
+            ("10 GBP", None),
+        ];
+        for (amount, expected) in test_cases {
+            assert_eq!(
+                restyle(&styles, amount).as_deref(),
+                expected,
+                "restyle({amount:?})"
+            );
+        }
+    }
+
+    #[test]
+    fn amount_value() {
+        let styles = styles(&["1.000,00 EUR", "$1,000.00"]);
+        let value = |amount: &str| {
+            styles
+                .value(amount.as_bytes())
+                .map(|value| String::from_utf8(value).unwrap())
+        };
+        assert_eq!(value("-1.234 EUR").as_deref(), Some("EUR -1234"));
+        assert_eq!(value("EUR 1234,50"), value("1.234,5 EUR"));
+        assert_eq!(value("$1,234.50"), value("1234.5$"));
+        assert_eq!(value("EUR 1234.5"), None);
+        assert_eq!(value("10 GBP"), None);
+    }
+
+    #[test]
+    fn number_value() {
+        assert_eq!(
+            Number::parse(b"001.234,500", Some(b',')),
+            Some(Number {
+                integer: b"1234".to_vec(),
+                fraction: b"5".to_vec(),
+            })
+        );
+        assert_eq!(
+            Number::parse(b"1 000", Some(b',')),
+            Number::parse(b"1000", None)
+        );
+        assert_eq!(
+            Number::parse(b"1.5", None),
+            Number::parse(b"1,5", Some(b','))
+        );
+        assert_eq!(Number::parse(b"1,234", None), None);
+        assert_eq!(Number::parse(b"1234.5", Some(b',')), None);
+    }
+}
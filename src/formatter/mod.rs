@@ -1,14 +1,123 @@
+pub(crate) mod amount_style;
 #[cfg(test)]
 mod tests;
-use crate::Vec;
+pub(crate) mod timeclock;
+use crate::date::{extend_date, normalized_date_len, split_date, DateFormat};
+use crate::{Cow, Vec};
+use amount_style::AmountStyles;
+use core::ops::RangeInclusive;
 
 use crate::parser::{
-    Directive, DirectiveNode, IndentedComment, JournalCstNode, JournalFile, TransactionNode,
+    CommentPrefix, Directive, DirectiveNode, IndentedComment, JournalCstNode, JournalFile,
+    PostingKind, TransactionHeader, TransactionNode,
 };
 
+#[derive(Clone)]
 pub struct FormatJournalOptions {
     estimated_length: usize,
     entry_spacing: usize,
+    normalize_transaction_headers: bool,
+    date_format: Option<DateFormat>,
+    normalize_amount_styles: bool,
+    alignment_scope: AlignmentScope,
+    amount_column: Option<usize>,
+    posting_indent: Option<PostingIndent>,
+    comment_prefix: Option<CommentPrefix>,
+    comment_alignment: CommentAlignment,
+    max_blank_lines: Option<usize>,
+    /// Blank lines between nodes of each [`NodeKind`]
+    blank_lines_between: [Option<usize>; 3],
+    trim_blank_lines: bool,
+    align_virtual_account_names: bool,
+}
+
+/// Kinds of top level nodes separated by blank lines
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeKind {
+    /// Transactions
+    Transaction = 0,
+    /// Groups of directives
+    DirectivesGroup = 1,
+    /// Single line and multiline comments
+    Comment = 2,
+}
+
+impl NodeKind {
+    fn of(node: &JournalCstNode) -> Option<Self> {
+        match node {
+            JournalCstNode::Transaction { .. }
+            | JournalCstNode::PeriodicTransaction { .. }
+            | JournalCstNode::AutoPostingRule { .. } => Some(NodeKind::Transaction),
+            JournalCstNode::DirectivesGroup { .. } => Some(NodeKind::DirectivesGroup),
+            JournalCstNode::SingleLineComment(_) | JournalCstNode::MultilineComment { .. } => {
+                Some(NodeKind::Comment)
+            }
+            JournalCstNode::EmptyLine { .. } | JournalCstNode::Verbatim { .. } => None,
+        }
+    }
+}
+
+/// Placement of inline comments of transaction titles, entries and directives
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CommentAlignment {
+    /// Align comments with the other comments of their transaction or
+    /// directives group
+    #[default]
+    Group,
+    /// Start comments at the character `N` of their lines, or two spaces
+    /// after the content of lines that don't fit before it
+    Column(usize),
+    /// Start all the comments of the journal at the same column
+    File,
+    /// Separate comments from the content of their lines by a single space,
+    /// or by two spaces after entries without amount and directives, which
+    /// need them to end
+    SingleSpace,
+}
+
+/// Indentation of the entries and comments of transactions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PostingIndent {
+    /// Indent with a number of spaces
+    Spaces(usize),
+    /// Indent with a tab.
+    ///
+    /// The tab is counted as 4 columns wide when placing the amounts at
+    /// [`FormatJournalOptions::with_amount_column`], like the parser counts
+    /// the indentation of tab-indented entries.
+    Tab,
+}
+
+impl PostingIndent {
+    #[inline]
+    fn extend(self, buffer: &mut Vec<u8>) {
+        match self {
+            PostingIndent::Spaces(n) => spaces::extend(buffer, n),
+            PostingIndent::Tab => buffer.push(b'\t'),
+        }
+    }
+
+    /// Width of the indentation, counting a tab as 4 columns like the parser
+    /// does, whatever the tab width of the editor.
+    #[inline]
+    fn width(self) -> usize {
+        match self {
+            PostingIndent::Spaces(n) => n,
+            PostingIndent::Tab => 4,
+        }
+    }
+}
+
+/// Scope in which the columns of transaction entries are aligned
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AlignmentScope {
+    /// Align the entries of each transaction on their own
+    #[default]
+    Transaction,
+    /// Align the entries of adjacent transactions, only separated by empty lines
+    Block,
+    /// Align the entries of all the transactions of the journal
+    File,
 }
 
 impl Default for FormatJournalOptions {
@@ -34,6 +143,18 @@ impl Default for FormatJournalOptions {
                     compile_time_value
                 }
             },
+            normalize_transaction_headers: false,
+            date_format: None,
+            normalize_amount_styles: false,
+            alignment_scope: AlignmentScope::Transaction,
+            amount_column: None,
+            posting_indent: None,
+            comment_prefix: None,
+            comment_alignment: CommentAlignment::Group,
+            max_blank_lines: None,
+            blank_lines_between: [None; 3],
+            trim_blank_lines: false,
+            align_virtual_account_names: false,
         }
     }
 }
@@ -61,6 +182,189 @@ impl FormatJournalOptions {
     pub fn entry_spacing(&self) -> usize {
         self.entry_spacing
     }
+
+    /// Separate the date, status, code and description of transaction
+    /// titles by a single space.
+    #[inline]
+    pub fn with_normalize_transaction_headers(mut self, normalize: bool) -> Self {
+        self.normalize_transaction_headers = normalize;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn normalize_transaction_headers(&self) -> bool {
+        self.normalize_transaction_headers
+    }
+
+    /// Rewrite the dates of transactions and `P` directives in the format
+    /// `date_format`, or leave them as they are if `None`.
+    #[inline]
+    pub fn with_date_format(mut self, date_format: Option<DateFormat>) -> Self {
+        self.date_format = date_format;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn date_format(&self) -> Option<DateFormat> {
+        self.date_format
+    }
+
+    /// Rewrite the amounts of commodities declared by `commodity` directives
+    /// with the symbol placement, digit grouping and decimal precision of
+    /// their samples.
+    ///
+    /// Amounts that can't be read unambiguously are left as they are, like
+    /// `1234.5` for a commodity whose decimal mark is `,`.
+    #[inline]
+    pub fn with_normalize_amount_styles(mut self, normalize: bool) -> Self {
+        self.normalize_amount_styles = normalize;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn normalize_amount_styles(&self) -> bool {
+        self.normalize_amount_styles
+    }
+
+    /// Align the columns of transaction entries across `alignment_scope`,
+    /// so amounts are in the same column in all its transactions.
+    #[inline]
+    pub fn with_alignment_scope(mut self, alignment_scope: AlignmentScope) -> Self {
+        self.alignment_scope = alignment_scope;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn alignment_scope(&self) -> AlignmentScope {
+        self.alignment_scope
+    }
+
+    /// End the amounts of entries at the character `amount_column` of their
+    /// lines instead of just after the longest entry name.
+    ///
+    /// Entries whose name doesn't fit before the column are separated from
+    /// their amount by `entry_spacing` spaces. Indenting tabs are counted as
+    /// 4 columns wide.
+    #[inline]
+    pub fn with_amount_column(mut self, amount_column: Option<usize>) -> Self {
+        self.amount_column = amount_column;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn amount_column(&self) -> Option<usize> {
+        self.amount_column
+    }
+
+    /// Indent the entries and comments of all transactions with
+    /// `posting_indent`, or with the indentation of their first entry
+    /// if `None`.
+    #[inline]
+    pub fn with_posting_indent(mut self, posting_indent: Option<PostingIndent>) -> Self {
+        self.posting_indent = posting_indent;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn posting_indent(&self) -> Option<PostingIndent> {
+        self.posting_indent
+    }
+
+    /// Write top level comments with `comment_prefix`, or with the prefix
+    /// they were written with if `None`.
+    ///
+    /// As hledger only reads `#` as a comment at the start of top level
+    /// lines, the prefixes of other comments are kept as they are.
+    #[inline]
+    pub fn with_comment_prefix(mut self, comment_prefix: Option<CommentPrefix>) -> Self {
+        self.comment_prefix = comment_prefix;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn comment_prefix(&self) -> Option<CommentPrefix> {
+        self.comment_prefix
+    }
+
+    /// Place the inline comments of transaction titles, entries and
+    /// directives with `comment_alignment`.
+    #[inline]
+    pub fn with_comment_alignment(mut self, comment_alignment: CommentAlignment) -> Self {
+        self.comment_alignment = comment_alignment;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn comment_alignment(&self) -> CommentAlignment {
+        self.comment_alignment
+    }
+
+    /// Collapse runs of consecutive blank lines to `max_blank_lines` lines.
+    #[inline]
+    pub fn with_max_blank_lines(mut self, max_blank_lines: Option<usize>) -> Self {
+        self.max_blank_lines = max_blank_lines;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn max_blank_lines(&self) -> Option<usize> {
+        self.max_blank_lines
+    }
+
+    /// Separate consecutive nodes of kind `kind` by exactly `blank_lines`
+    /// lines, instead of by the blank lines between them.
+    ///
+    /// Comments are only separated if they were separated by blank lines,
+    /// as consecutive comment lines are a single comment.
+    #[inline]
+    pub fn with_blank_lines_between(mut self, kind: NodeKind, blank_lines: Option<usize>) -> Self {
+        self.blank_lines_between[kind as usize] = blank_lines;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn blank_lines_between(&self, kind: NodeKind) -> Option<usize> {
+        self.blank_lines_between[kind as usize]
+    }
+
+    /// Remove the blank lines at the start and the end of the journal.
+    #[inline]
+    pub fn with_trim_blank_lines(mut self, trim: bool) -> Self {
+        self.trim_blank_lines = trim;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn trim_blank_lines(&self) -> bool {
+        self.trim_blank_lines
+    }
+
+    /// Align the account names of real postings with the account names
+    /// inside the brackets of virtual postings, instead of with the brackets.
+    ///
+    /// Only applies where there are virtual postings in the aligned entries.
+    #[inline]
+    pub fn with_align_virtual_account_names(mut self, align: bool) -> Self {
+        self.align_virtual_account_names = align;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn align_virtual_account_names(&self) -> bool {
+        self.align_virtual_account_names
+    }
 }
 
 #[cfg(test)]
@@ -71,13 +375,101 @@ fn format_content(nodes: &JournalFile) -> Vec<u8> {
 pub(crate) fn format_content_with_options(
     nodes: &JournalFile,
     opts: &FormatJournalOptions,
+) -> Vec<u8> {
+    if opts.comment_alignment == CommentAlignment::File {
+        let column = file_comment_column(nodes, opts);
+        let opts = opts
+            .clone()
+            .with_comment_alignment(CommentAlignment::Column(column));
+        return format_content_with_options(nodes, &opts);
+    }
+
+    let mut buffer = Vec::with_capacity(opts.estimated_length);
+    let mut styles = opts.normalize_amount_styles.then(AmountStyles::default);
+    format_nodes(nodes, &mut buffer, opts, styles.as_mut());
+    buffer
+}
+
+/// Format the nodes that overlap the lines `lines` (1-based, inclusive),
+/// copying the rest of `content` unchanged.
+///
+/// `nodes` must have been parsed from `content`.
+pub(crate) fn format_content_range_with_options(
+    nodes: &JournalFile,
+    content: &[u8],
+    lines: RangeInclusive<usize>,
+    opts: &FormatJournalOptions,
 ) -> Vec<u8> {
     let mut buffer = Vec::with_capacity(opts.estimated_length);
-    format_nodes(nodes, &mut buffer, opts.entry_spacing);
+    if lines.is_empty() {
+        buffer.extend_from_slice(content);
+        return buffer;
+    }
+
+    if opts.comment_alignment == CommentAlignment::File {
+        let column = file_comment_column(nodes, opts);
+        let opts = opts
+            .clone()
+            .with_comment_alignment(CommentAlignment::Column(column));
+        return format_content_range_with_options(nodes, content, lines, &opts);
+    }
+
+    let mut styles = opts.normalize_amount_styles.then(AmountStyles::default);
+    format_nodes_range(nodes, content, &lines, &mut buffer, opts, styles.as_mut());
     buffer
 }
 
-fn format_nodes(nodes: &JournalFile, buffer: &mut Vec<u8>, entry_spacing: usize) {
+fn format_nodes_range<'a>(
+    nodes: &JournalFile<'a>,
+    content: &[u8],
+    lines: &RangeInclusive<usize>,
+    buffer: &mut Vec<u8>,
+    opts: &FormatJournalOptions,
+    mut styles: Option<&mut AmountStyles<'a>>,
+) {
+    let widths = column_widths(nodes, opts);
+    let blank_lines = blank_lines(nodes, opts);
+    let mut copied_until = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        if let Some(styles) = styles.as_deref_mut() {
+            styles.add_node(node);
+        }
+        let span = node.span();
+        if span.end.lineno < *lines.start() {
+            continue;
+        } else if span.start.lineno > *lines.end() {
+            break;
+        }
+
+        // replace the newline that ends the node too, as it's formatted with one
+        let mut end = span.end.byteno;
+        if content.get(end) == Some(&b'\r') && content.get(end + 1) == Some(&b'\n') {
+            end += 2;
+        } else if content.get(end) == Some(&b'\n') {
+            end += 1;
+        }
+
+        buffer.extend_from_slice(&content[copied_until..span.start.byteno]);
+        match blank_lines.get(i) {
+            Some(n_lines) if matches!(node, JournalCstNode::EmptyLine { .. }) => {
+                buffer.resize(buffer.len() + n_lines, b'\n');
+            }
+            _ => {
+                let node_widths = widths.get(i).and_then(Option::as_ref);
+                format_node(node, buffer, opts, styles.as_deref(), node_widths);
+            }
+        }
+        copied_until = end;
+    }
+    buffer.extend_from_slice(&content[copied_until..]);
+}
+
+fn format_nodes<'a>(
+    nodes: &JournalFile<'a>,
+    buffer: &mut Vec<u8>,
+    opts: &FormatJournalOptions,
+    mut styles: Option<&mut AmountStyles<'a>>,
+) {
     #[cfg(any(test, feature = "tracing"))]
     {
         let span = tracing::span!(tracing::Level::TRACE, "format_nodes");
@@ -85,266 +477,1025 @@ fn format_nodes(nodes: &JournalFile, buffer: &mut Vec<u8>, entry_spacing: usize)
         tracing::trace!("nodes={:#?}", nodes);
     }
 
-    for node in nodes {
-        match node {
-            JournalCstNode::SingleLineComment(IndentedComment {
-                content,
-                prefix,
-                indent,
-                ..
-            }) => {
-                spaces::extend(buffer, *indent as usize);
-                buffer.push(*prefix as u8);
-                buffer.extend_from_slice(content);
-                buffer.push(b'\n');
+    let widths = column_widths(nodes, opts);
+    let blank_lines = blank_lines(nodes, opts);
+    for (i, node) in nodes.iter().enumerate() {
+        if let Some(styles) = styles.as_deref_mut() {
+            styles.add_node(node);
+        }
+        if let Some(n_lines) = blank_lines.get(i) {
+            if matches!(node, JournalCstNode::EmptyLine { .. }) {
+                buffer.resize(buffer.len() + n_lines, b'\n');
+                continue;
             }
-            JournalCstNode::EmptyLine => {
+        }
+        let node_widths = widths.get(i).and_then(Option::as_ref);
+        format_node(node, buffer, opts, styles.as_deref(), node_widths);
+    }
+
+    #[cfg(any(test, feature = "tracing"))]
+    {
+        let span = tracing::span!(tracing::Level::TRACE, "format_nodes(formatted)");
+        let _enter = span.enter();
+
+        tracing::trace!("buffer=\"{}\"", String::from_utf8_lossy(buffer));
+    }
+}
+
+/// Format a journal node, ending it with a newline.
+///
+/// If `styles` is passed, the amounts of the postings are restyled. If
+/// `widths` is passed, the entries of transactions are aligned to them
+/// instead of to the widths of the transaction.
+#[inline]
+fn format_node(
+    node: &JournalCstNode,
+    buffer: &mut Vec<u8>,
+    opts: &FormatJournalOptions,
+    styles: Option<&AmountStyles>,
+    widths: Option<&ColumnWidths>,
+) {
+    match node {
+        JournalCstNode::SingleLineComment(IndentedComment {
+            content,
+            prefix,
+            indent,
+            ..
+        }) => {
+            spaces::extend(buffer, *indent as usize);
+            buffer.push(comment_prefix(*prefix, *indent == 0, opts));
+            buffer.extend_from_slice(content);
+            buffer.push(b'\n');
+        }
+        JournalCstNode::EmptyLine { .. } => {
+            buffer.push(b'\n');
+        }
+        JournalCstNode::Verbatim { content, .. } => {
+            buffer.extend_from_slice(content);
+            if !content.ends_with(b"\n") {
                 buffer.push(b'\n');
             }
-            JournalCstNode::MultilineComment { content, .. } => {
-                buffer.extend_from_slice(b"comment\n");
-                buffer.extend_from_slice(content);
-                buffer.extend_from_slice(b"end comment\n");
-            }
-            JournalCstNode::DirectivesGroup {
-                nodes,
-                max_name_content_len,
-                ..
-            } => {
-                for node in nodes {
-                    match node {
-                        DirectiveNode::Directive(Directive {
+        }
+        JournalCstNode::MultilineComment { content, .. } => {
+            buffer.extend_from_slice(b"comment\n");
+            buffer.extend_from_slice(content);
+            buffer.extend_from_slice(b"end comment\n");
+        }
+        JournalCstNode::DirectivesGroup {
+            nodes,
+            max_name_content_len,
+            ..
+        } => {
+            let max_name_content_len = match opts.date_format {
+                Some(_) => nodes
+                    .iter()
+                    .filter_map(|node| match node {
+                        DirectiveNode::Directive(directive) => Some(
+                            directive.name_chars_count as usize
+                                + directive_content_chars_count(directive, opts),
+                        ),
+                        _ => None,
+                    })
+                    .max()
+                    .unwrap_or(0),
+                None => *max_name_content_len as usize,
+            };
+
+            for node in nodes {
+                match node {
+                    DirectiveNode::Directive(
+                        directive @ Directive {
                             name,
                             content,
                             comment,
                             name_chars_count,
-                            content_chars_count,
                             ..
-                        }) => {
-                            buffer.extend_from_slice(name);
-                            buffer.push(b' ');
-                            buffer.extend_from_slice(content);
-
-                            if let Some(comment) = comment {
-                                spaces::extend(
-                                    buffer,
-                                    2 + *max_name_content_len as usize
-                                        - *name_chars_count as usize
-                                        - *content_chars_count as usize,
-                                );
-                                buffer.push(comment.prefix as u8);
-                                buffer.extend_from_slice(&comment.content);
+                        },
+                    ) => {
+                        buffer.extend_from_slice(name);
+                        buffer.push(b' ');
+                        match (opts.date_format, price_directive_date(directive)) {
+                            (Some(date_format), Some((date, rest))) => {
+                                extend_date(buffer, date, date_format);
+                                buffer.extend_from_slice(rest);
                             }
-                            buffer.push(b'\n');
-                        }
-                        DirectiveNode::Subdirective(content) => {
-                            spaces::extend(buffer, 2);
-                            buffer.extend_from_slice(content);
-                            buffer.push(b'\n');
+                            _ => buffer.extend_from_slice(content),
                         }
-                        DirectiveNode::SingleLineComment(IndentedComment {
-                            content,
-                            prefix,
-                            ..
-                        }) => {
-                            spaces::extend(buffer, *max_name_content_len as usize + 3);
-                            buffer.push(*prefix as u8);
-                            buffer.extend_from_slice(content);
-                            buffer.push(b'\n');
+
+                        if let Some(comment) = comment {
+                            let line_chars_count = *name_chars_count as usize
+                                + 1
+                                + directive_content_chars_count(directive, opts);
+                            spaces::extend(
+                                buffer,
+                                comment_spaces(
+                                    opts.comment_alignment,
+                                    line_chars_count,
+                                    max_name_content_len + 3 - line_chars_count,
+                                    2,
+                                ),
+                            );
+                            buffer.push(comment_prefix(comment.prefix, false, opts));
+                            buffer.extend_from_slice(&comment.content);
                         }
+                        buffer.push(b'\n');
+                    }
+                    DirectiveNode::Subdirective { content, .. } => {
+                        spaces::extend(buffer, 2);
+                        buffer.extend_from_slice(content);
+                        buffer.push(b'\n');
+                    }
+                    DirectiveNode::SingleLineComment(IndentedComment {
+                        content, prefix, ..
+                    }) => {
+                        let aligned_spaces = max_name_content_len + 3;
+                        spaces::extend(
+                            buffer,
+                            comment_spaces(
+                                opts.comment_alignment,
+                                0,
+                                aligned_spaces,
+                                aligned_spaces,
+                            ),
+                        );
+                        buffer.push(comment_prefix(*prefix, false, opts));
+                        buffer.extend_from_slice(content);
+                        buffer.push(b'\n');
                     }
                 }
             }
+        }
+        JournalCstNode::Transaction { .. }
+        | JournalCstNode::PeriodicTransaction { .. }
+        | JournalCstNode::AutoPostingRule { .. } => {
+            format_transaction(node, buffer, opts, styles, widths);
+        }
+    }
+}
+
+/// Format a transaction, periodic transaction or auto posting rule node,
+/// ending it with a newline.
+fn format_transaction(
+    node: &JournalCstNode,
+    buffer: &mut Vec<u8>,
+    opts: &FormatJournalOptions,
+    styles: Option<&AmountStyles>,
+    widths: Option<&ColumnWidths>,
+) {
+    let JournalCstNode::Transaction {
+        title_comment,
+        entries,
+        first_entry_indent,
+        ..
+    } = node.inner()
+    else {
+        return;
+    };
+    let widths = match widths {
+        Some(widths) => *widths,
+        None => ColumnWidths::of(node, styles).unwrap_or_default(),
+    };
+    let entry_spacing = opts.entry_spacing;
+    let (indent, align_accounts) = entries_indent(entries, *first_entry_indent, opts);
+    let title_start = buffer.len();
+    extend_transaction_title(buffer, node, opts);
+    // Cache title_chars_count outside the loop since title doesn't change
+    let title_chars_count = crate::byte_str::utf8_chars_count(&buffer[title_start..]);
+
+    if let Some(comment) = title_comment {
+        spaces::extend(
+            buffer,
+            comment_spaces(opts.comment_alignment, title_chars_count, 2, 1),
+        );
+        buffer.push(comment_prefix(comment.prefix, false, opts));
+        buffer.extend_from_slice(&comment.content);
+    }
+    buffer.push(b'\n');
+
+    for entry in entries {
+        match entry {
+            TransactionNode::TransactionEntry(inner) => {
+                let e = inner.as_ref();
+                let parts = entry_parts(e, styles);
+                let account_offset = usize::from(align_accounts && e.kind == PostingKind::Real);
+
+                if let Some(ref comment) = e.comment {
+                    let mut entry_line_buffer = Vec::with_capacity(e.name.len() + 32);
+                    extend_entry(
+                        &mut entry_line_buffer,
+                        e,
+                        &parts,
+                        indent,
+                        account_offset,
+                        &widths,
+                        opts,
+                    );
+
+                    // the comment is aligned after the last part of the value
+                    let last_part = parts.iter().rposition(|part| !part.is_empty());
+                    let last = last_part.unwrap_or(0);
+                    let comment_separation = entry_spacing
+                        + widths.parts[last].after_decimals as usize
+                        - parts[last].after_decimals_chars_count;
+
+                    let entry_line_chars_count = entry_line_width(&entry_line_buffer, indent);
+
+                    buffer.append(&mut entry_line_buffer);
+
+                    let n_spaces = if title_chars_count + 2 > entry_line_chars_count + 2 {
+                        title_chars_count + 2 - entry_line_chars_count
+                    } else {
+                        comment_separation
+                    };
+                    // entry names end at two spaces
+                    spaces::extend(
+                        buffer,
+                        comment_spaces(
+                            opts.comment_alignment,
+                            entry_line_chars_count,
+                            n_spaces,
+                            if last_part.is_some() { 1 } else { 2 },
+                        ),
+                    );
+                    buffer.push(comment_prefix(comment.prefix, false, opts));
+                    buffer.extend_from_slice(&comment.content);
+                } else {
+                    extend_entry(buffer, e, &parts, indent, account_offset, &widths, opts);
+                }
+                buffer.push(b'\n');
+            }
+            TransactionNode::SingleLineComment(IndentedComment {
+                content, prefix, ..
+            }) => {
+                indent.extend(buffer);
+                buffer.push(comment_prefix(*prefix, false, opts));
+                buffer.extend_from_slice(content);
+                buffer.push(b'\n');
+            }
+        }
+    }
+}
+
+/// Indentation of the entries of a transaction, and if the account names of
+/// its real postings are offset to align them with the virtual ones
+fn entries_indent(
+    entries: &[TransactionNode],
+    first_entry_indent: u16,
+    opts: &FormatJournalOptions,
+) -> (PostingIndent, bool) {
+    let first_virtual_indent = entries.iter().find_map(|entry| match entry {
+        TransactionNode::TransactionEntry(entry) if entry.kind != PostingKind::Real => {
+            Some(entry.indent)
+        }
+        _ => None,
+    });
+    let align_accounts = opts.align_virtual_account_names && first_virtual_indent.is_some();
+    // the first entry may be a real posting already offset by a previous
+    // format, virtual postings are never offset
+    let indent = match first_virtual_indent {
+        Some(virtual_indent) if align_accounts => first_entry_indent.min(virtual_indent),
+        _ => first_entry_indent,
+    };
+    let indent = opts
+        .posting_indent
+        .unwrap_or(PostingIndent::Spaces(indent as usize));
+    (indent, align_accounts)
+}
+
+/// Write the title of a transaction, periodic transaction or auto posting
+/// rule, without its comment.
+fn extend_transaction_title(
+    buffer: &mut Vec<u8>,
+    node: &JournalCstNode,
+    opts: &FormatJournalOptions,
+) {
+    match node {
+        JournalCstNode::Transaction {
+            header: Some(header),
+            ..
+        } if opts.normalize_transaction_headers => {
+            extend_transaction_header(buffer, header, opts.date_format);
+        }
+        JournalCstNode::Transaction {
+            title,
+            header: Some(header),
+            ..
+        } if opts.date_format.is_some() => {
+            let dates_len = header.date.len()
+                + header
+                    .secondary_date
+                    .as_ref()
+                    .map_or(0, |date| date.len() + 1);
+            extend_transaction_dates(buffer, header, opts.date_format);
+            buffer.extend_from_slice(&title[dates_len..]);
+        }
+        JournalCstNode::Transaction { title, .. } => buffer.extend_from_slice(title),
+        JournalCstNode::PeriodicTransaction {
+            period,
+            description,
+            ..
+        } => extend_periodic_title(buffer, period, description.as_deref()),
+        JournalCstNode::AutoPostingRule { query, .. } => {
+            extend_auto_posting_rule_title(buffer, query);
+        }
+        _ => {}
+    }
+}
+
+/// Widths of the columns of transaction entries
+#[derive(Clone, Copy, Default)]
+struct ColumnWidths {
+    name: u16,
+    /// Width of the names of real postings
+    real_name: u16,
+    /// If there are virtual or balanced virtual postings
+    has_virtual: bool,
+    /// If a posting has both a lot annotation or cost and a balance
+    /// assertion, so the assertions are aligned after the costs. Otherwise
+    /// they're aligned right after the amounts.
+    assertions_after_costs: bool,
+    /// Widths of the amount, its lot price and date, its cost, the balance
+    /// assertion and the cost of the asserted amount
+    parts: [PartWidths; 6],
+}
+
+/// Widths of the columns of a part of the entry values
+#[derive(Clone, Copy, Default)]
+struct PartWidths {
+    separator: u16,
+    before_decimals: u16,
+    after_decimals: u16,
+}
+
+impl PartWidths {
+    /// If no entry has this part
+    #[inline]
+    fn is_empty(self) -> bool {
+        self.separator == 0 && self.before_decimals == 0 && self.after_decimals == 0
+    }
+
+    #[inline]
+    fn max(self, other: Self) -> Self {
+        Self {
+            separator: self.separator.max(other.separator),
+            before_decimals: self.before_decimals.max(other.before_decimals),
+            after_decimals: self.after_decimals.max(other.after_decimals),
+        }
+    }
+}
+
+impl ColumnWidths {
+    /// Widths of the columns of a transaction, with its amounts restyled if
+    /// `styles` is passed
+    fn of(node: &JournalCstNode, styles: Option<&AmountStyles>) -> Option<Self> {
+        match node.inner() {
             JournalCstNode::Transaction {
-                title,
-                title_comment,
                 entries,
-                first_entry_indent,
                 max_entry_name_len,
                 max_entry_value_first_part_before_decimals_len,
                 max_entry_value_first_part_after_decimals_len,
+                max_entry_lot_price_len,
+                max_entry_lot_date_len,
                 max_entry_value_first_separator_len,
                 max_entry_value_second_part_before_decimals_len,
                 max_entry_value_second_part_after_decimals_len,
-                max_entry_value_second_separator_len,
-                max_entry_value_third_part_before_decimals_len,
-                max_entry_value_third_part_after_decimals_len,
+                max_entry_assertion_operator_len,
+                max_entry_assertion_before_decimals_len,
+                max_entry_assertion_after_decimals_len,
+                max_entry_assertion_cost_separator_len,
+                max_entry_assertion_cost_before_decimals_len,
+                max_entry_assertion_cost_after_decimals_len,
+                ..
+            } => Some(Self {
+                name: *max_entry_name_len,
+                real_name: entries
+                    .iter()
+                    .filter_map(|entry| match entry {
+                        TransactionNode::TransactionEntry(entry)
+                            if entry.kind == PostingKind::Real =>
+                        {
+                            Some(entry.name_chars_count)
+                        }
+                        _ => None,
+                    })
+                    .max()
+                    .unwrap_or(0),
+                has_virtual: entries.iter().any(|entry| {
+                    matches!(entry, TransactionNode::TransactionEntry(entry)
+                        if entry.kind != PostingKind::Real)
+                }),
+                assertions_after_costs: entries.iter().any(|entry| {
+                    matches!(entry, TransactionNode::TransactionEntry(entry)
+                        if !entry.assertion_operator.is_empty()
+                            && (!entry.lot_price.is_empty()
+                                || !entry.lot_date.is_empty()
+                                || !entry.value_first_separator.is_empty()))
+                }),
+                parts: match styles {
+                    // the restyled amounts may change their lengths
+                    Some(styles) if !styles.is_empty() => restyled_part_widths(entries, styles),
+                    _ => [
+                        PartWidths {
+                            separator: 0,
+                            before_decimals: *max_entry_value_first_part_before_decimals_len,
+                            after_decimals: *max_entry_value_first_part_after_decimals_len,
+                        },
+                        PartWidths {
+                            separator: 0,
+                            before_decimals: 0,
+                            after_decimals: *max_entry_lot_price_len,
+                        },
+                        PartWidths {
+                            separator: 0,
+                            before_decimals: 0,
+                            after_decimals: *max_entry_lot_date_len,
+                        },
+                        PartWidths {
+                            separator: *max_entry_value_first_separator_len,
+                            before_decimals: *max_entry_value_second_part_before_decimals_len,
+                            after_decimals: *max_entry_value_second_part_after_decimals_len,
+                        },
+                        PartWidths {
+                            separator: *max_entry_assertion_operator_len,
+                            before_decimals: *max_entry_assertion_before_decimals_len,
+                            after_decimals: *max_entry_assertion_after_decimals_len,
+                        },
+                        PartWidths {
+                            separator: *max_entry_assertion_cost_separator_len,
+                            before_decimals: *max_entry_assertion_cost_before_decimals_len,
+                            after_decimals: *max_entry_assertion_cost_after_decimals_len,
+                        },
+                    ],
+                },
+            }),
+            _ => None,
+        }
+    }
+
+    /// Width of the entry names, including the offsets of the account names.
+    #[inline]
+    fn name_width(&self, align: bool) -> usize {
+        if align && self.has_virtual {
+            (self.name as usize).max(self.real_name as usize + 1)
+        } else {
+            self.name as usize
+        }
+    }
+
+    /// Widths wide enough for the columns of both `self` and `other`
+    fn max(self, other: Self) -> Self {
+        let mut parts = self.parts;
+        for (part, other_part) in parts.iter_mut().zip(other.parts) {
+            *part = part.max(other_part);
+        }
+        Self {
+            name: self.name.max(other.name),
+            real_name: self.real_name.max(other.real_name),
+            has_virtual: self.has_virtual || other.has_virtual,
+            assertions_after_costs: self.assertions_after_costs || other.assertions_after_costs,
+            parts,
+        }
+    }
+}
+
+/// A part of the value of an entry, with the separator before it
+struct EntryPart<'e> {
+    separator: &'e [u8],
+    before_decimals: Cow<'e, [u8]>,
+    before_decimals_chars_count: usize,
+    after_decimals: Cow<'e, [u8]>,
+    after_decimals_chars_count: usize,
+}
+
+impl EntryPart<'_> {
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.separator.is_empty()
+            && self.before_decimals.is_empty()
+            && self.after_decimals.is_empty()
+    }
+
+    #[inline]
+    fn widths(&self) -> PartWidths {
+        PartWidths {
+            separator: self.separator.len() as u16,
+            before_decimals: self.before_decimals_chars_count as u16,
+            after_decimals: self.after_decimals_chars_count as u16,
+        }
+    }
+
+    /// Rewrite the amount with the style of its commodity, if any
+    fn restyle(&mut self, styles: &AmountStyles) {
+        if self.before_decimals.is_empty() && self.after_decimals.is_empty() {
+            return;
+        }
+        let mut amount = Vec::with_capacity(self.before_decimals.len() + self.after_decimals.len());
+        amount.extend_from_slice(&self.before_decimals);
+        amount.extend_from_slice(&self.after_decimals);
+        let Some((mut before_decimals, decimals_start)) = styles.restyle(&amount) else {
+            return;
+        };
+        let after_decimals = before_decimals.split_off(decimals_start);
+        self.before_decimals_chars_count = crate::byte_str::utf8_chars_count(&before_decimals);
+        self.after_decimals_chars_count = crate::byte_str::utf8_chars_count(&after_decimals);
+        self.before_decimals = Cow::Owned(before_decimals);
+        self.after_decimals = Cow::Owned(after_decimals);
+    }
+}
+
+/// Amount, lot price, lot date, cost, balance assertion and cost of the
+/// asserted amount of an entry, with the amounts restyled if `styles` is
+/// passed.
+///
+/// Lot annotations are written as values without decimals, so they are
+/// aligned to the left.
+#[inline]
+fn entry_parts<'e>(
+    entry: &'e crate::parser::TransactionEntry,
+    styles: Option<&AmountStyles>,
+) -> [EntryPart<'e>; 6] {
+    let mut parts = [
+        EntryPart {
+            separator: b"",
+            before_decimals: Cow::Borrowed(&entry.value_first_part_before_decimals),
+            before_decimals_chars_count: entry.value_first_part_before_decimals_chars_count(),
+            after_decimals: Cow::Borrowed(&entry.value_first_part_after_decimals),
+            after_decimals_chars_count: entry.value_first_part_after_decimals_chars_count(),
+        },
+        EntryPart {
+            separator: b"",
+            before_decimals: Cow::Borrowed(b""),
+            before_decimals_chars_count: 0,
+            after_decimals: Cow::Borrowed(&entry.lot_price),
+            after_decimals_chars_count: entry.lot_price_chars_count(),
+        },
+        EntryPart {
+            separator: b"",
+            before_decimals: Cow::Borrowed(b""),
+            before_decimals_chars_count: 0,
+            after_decimals: Cow::Borrowed(&entry.lot_date),
+            after_decimals_chars_count: entry.lot_date_chars_count(),
+        },
+        EntryPart {
+            separator: &entry.value_first_separator,
+            before_decimals: Cow::Borrowed(&entry.value_second_part_before_decimals),
+            before_decimals_chars_count: entry.value_second_part_before_decimals_chars_count(),
+            after_decimals: Cow::Borrowed(&entry.value_second_part_after_decimals),
+            after_decimals_chars_count: entry.value_second_part_after_decimals_chars_count(),
+        },
+        EntryPart {
+            separator: &entry.assertion_operator,
+            before_decimals: Cow::Borrowed(&entry.assertion_before_decimals),
+            before_decimals_chars_count: entry.assertion_before_decimals_chars_count(),
+            after_decimals: Cow::Borrowed(&entry.assertion_after_decimals),
+            after_decimals_chars_count: entry.assertion_after_decimals_chars_count(),
+        },
+        EntryPart {
+            separator: &entry.assertion_cost_separator,
+            before_decimals: Cow::Borrowed(&entry.assertion_cost_before_decimals),
+            before_decimals_chars_count: entry.assertion_cost_before_decimals_chars_count(),
+            after_decimals: Cow::Borrowed(&entry.assertion_cost_after_decimals),
+            after_decimals_chars_count: entry.assertion_cost_after_decimals_chars_count(),
+        },
+    ];
+    if let Some(styles) = styles {
+        // lot annotations are not amounts
+        for i in [0, 3, 4, 5] {
+            parts[i].restyle(styles);
+        }
+    }
+    parts
+}
+
+/// Widths of the parts of the entry values of a transaction with their
+/// amounts restyled
+fn restyled_part_widths(entries: &[TransactionNode], styles: &AmountStyles) -> [PartWidths; 6] {
+    let mut widths = [PartWidths::default(); 6];
+    for entry in entries {
+        if let TransactionNode::TransactionEntry(entry) = entry {
+            for (part_widths, part) in widths.iter_mut().zip(entry_parts(entry, Some(styles))) {
+                *part_widths = part_widths.max(part.widths());
+            }
+        }
+    }
+    widths
+}
+
+/// Widths of the columns of each node in the alignment scope, or an empty
+/// vector if each transaction is aligned on its own.
+fn column_widths(nodes: &JournalFile, opts: &FormatJournalOptions) -> Vec<Option<ColumnWidths>> {
+    let mut widths = Vec::new();
+    let scope = opts.alignment_scope;
+    if scope == AlignmentScope::Transaction {
+        return widths;
+    }
+
+    widths.reserve(nodes.len());
+    let mut scope_start = 0;
+    let mut scope_widths: Option<ColumnWidths> = None;
+    let mut styles = opts.normalize_amount_styles.then(AmountStyles::default);
+    for (i, node) in nodes.iter().enumerate() {
+        if let Some(styles) = styles.as_mut() {
+            styles.add_node(node);
+        }
+        if let Some(node_widths) = ColumnWidths::of(node, styles.as_ref()) {
+            scope_widths = Some(match scope_widths {
+                Some(scope_widths) => scope_widths.max(node_widths),
+                None => node_widths,
+            });
+            widths.push(scope_widths);
+        } else {
+            widths.push(None);
+            let ends_block = !matches!(node, JournalCstNode::EmptyLine { .. });
+            if scope == AlignmentScope::Block && ends_block {
+                fill_scope(&mut widths[scope_start..i], scope_widths.take());
+                scope_start = i + 1;
+            }
+        }
+    }
+    fill_scope(&mut widths[scope_start..], scope_widths);
+    widths
+}
+
+/// Set the widths of the transactions of a scope to the widths of the scope
+fn fill_scope(widths: &mut [Option<ColumnWidths>], scope_widths: Option<ColumnWidths>) {
+    for node_widths in widths.iter_mut().filter(|w| w.is_some()) {
+        *node_widths = scope_widths;
+    }
+}
+
+/// Number of spaces before an inline comment placed with `alignment`.
+///
+/// `line_chars_count` is the number of characters written in the line,
+/// `aligned_spaces` the spaces that align the comment with its group and
+/// `single_spaces` the minimum spaces that separate it from the content.
+#[inline]
+fn comment_spaces(
+    alignment: CommentAlignment,
+    line_chars_count: usize,
+    aligned_spaces: usize,
+    single_spaces: usize,
+) -> usize {
+    match alignment {
+        CommentAlignment::Group | CommentAlignment::File => aligned_spaces,
+        CommentAlignment::Column(column) => column
+            .checked_sub(line_chars_count + 1)
+            .filter(|n_spaces| *n_spaces >= 2)
+            .unwrap_or(2),
+        CommentAlignment::SingleSpace => single_spaces,
+    }
+}
+
+/// Width of an entry line written with [`extend_entry`], counting its
+/// indentation with [`PostingIndent::width`] like the amount column does.
+#[inline]
+fn entry_line_width(line: &[u8], indent: PostingIndent) -> usize {
+    let indent_len = match indent {
+        PostingIndent::Spaces(n) => n,
+        PostingIndent::Tab => 1,
+    };
+    indent.width() + crate::byte_str::utf8_chars_count(&line[indent_len..])
+}
+
+/// Column at which all the comments of the journal start with
+/// [`CommentAlignment::File`].
+///
+/// It's the first column after the longest line with an inline comment,
+/// separated from it by two spaces.
+fn file_comment_column(nodes: &JournalFile, opts: &FormatJournalOptions) -> usize {
+    let widths = column_widths(nodes, opts);
+    let mut styles = opts.normalize_amount_styles.then(AmountStyles::default);
+    // titles and entries with comments are written here to count their characters
+    let mut line = Vec::new();
+    let mut max_line_chars_count = None;
+    for (i, node) in nodes.iter().enumerate() {
+        if let Some(styles) = styles.as_mut() {
+            styles.add_node(node);
+        }
+        match node.inner() {
+            JournalCstNode::Transaction {
+                title_comment,
+                entries,
+                first_entry_indent,
+                ..
             } => {
-                buffer.extend_from_slice(title);
-                if let Some(comment) = title_comment {
-                    spaces::extend(buffer, 2);
-                    buffer.push(comment.prefix as u8);
-                    buffer.extend_from_slice(&comment.content);
+                if title_comment.is_some() {
+                    line.clear();
+                    extend_transaction_title(&mut line, node, opts);
+                    let title_chars_count = crate::byte_str::utf8_chars_count(&line);
+                    max_line_chars_count = max_line_chars_count.max(Some(title_chars_count));
                 }
-                buffer.push(b'\n');
-
-                // Cache title_chars_count outside the loop since title doesn't change
-                let title_chars_count = title.chars_count();
 
+                let node_widths = match widths.get(i).and_then(Option::as_ref) {
+                    Some(widths) => *widths,
+                    None => ColumnWidths::of(node, styles.as_ref()).unwrap_or_default(),
+                };
+                let (indent, align_accounts) = entries_indent(entries, *first_entry_indent, opts);
                 for entry in entries {
-                    match entry {
-                        TransactionNode::TransactionEntry(inner) => {
-                            let e = inner.as_ref();
-
-                            if let Some(ref comment) = e.comment {
-                                // Use cached chars_count values
-                                let after_decimals_chars_count =
-                                    if !e.value_second_separator.is_empty() {
-                                        e.value_third_part_after_decimals_chars_count as usize
-                                    } else if !e.value_first_separator.is_empty() {
-                                        e.value_second_part_after_decimals_chars_count as usize
-                                    } else {
-                                        e.value_first_part_after_decimals_chars_count as usize
-                                    };
-
-                                let mut entry_line_buffer = Vec::with_capacity(e.name.len() + 32);
-                                extend_entry(
-                                    &mut entry_line_buffer,
-                                    e,
-                                    *first_entry_indent,
-                                    *max_entry_name_len,
-                                    *max_entry_value_first_part_before_decimals_len,
-                                    *max_entry_value_first_part_after_decimals_len,
-                                    *max_entry_value_first_separator_len,
-                                    *max_entry_value_second_part_before_decimals_len,
-                                    *max_entry_value_second_part_after_decimals_len,
-                                    *max_entry_value_second_separator_len,
-                                    *max_entry_value_third_part_before_decimals_len,
-                                    entry_spacing,
-                                );
-
-                                let comment_separation = if !e.value_second_separator.is_empty() {
-                                    entry_spacing
-                                        + *max_entry_value_third_part_after_decimals_len as usize
-                                        - after_decimals_chars_count
-                                } else if !e.value_first_separator.is_empty() {
-                                    entry_spacing
-                                        + *max_entry_value_second_part_after_decimals_len as usize
-                                        - after_decimals_chars_count
-                                } else {
-                                    entry_spacing
-                                        + *max_entry_value_first_part_after_decimals_len as usize
-                                        - after_decimals_chars_count
-                                };
-
-                                let entry_line_chars_count =
-                                    crate::byte_str::utf8_chars_count(&entry_line_buffer);
-
-                                buffer.append(&mut entry_line_buffer);
-
-                                let n_spaces = if title_chars_count + 2 > entry_line_chars_count + 2
-                                {
-                                    title_chars_count + 2 - entry_line_chars_count
-                                } else {
-                                    comment_separation
-                                };
-                                spaces::extend(buffer, n_spaces);
-                                buffer.push(comment.prefix as u8);
-                                buffer.extend_from_slice(&comment.content);
-                            } else {
-                                extend_entry(
-                                    buffer,
-                                    e,
-                                    *first_entry_indent,
-                                    *max_entry_name_len,
-                                    *max_entry_value_first_part_before_decimals_len,
-                                    *max_entry_value_first_part_after_decimals_len,
-                                    *max_entry_value_first_separator_len,
-                                    *max_entry_value_second_part_before_decimals_len,
-                                    *max_entry_value_second_part_after_decimals_len,
-                                    *max_entry_value_second_separator_len,
-                                    *max_entry_value_third_part_before_decimals_len,
-                                    entry_spacing,
-                                );
-                            }
-                            buffer.push(b'\n');
-                        }
-                        TransactionNode::SingleLineComment(IndentedComment {
-                            content,
-                            prefix,
+                    let TransactionNode::TransactionEntry(entry) = entry else {
+                        continue;
+                    };
+                    if entry.comment.is_none() {
+                        continue;
+                    }
+                    let parts = entry_parts(entry, styles.as_ref());
+                    let account_offset =
+                        usize::from(align_accounts && entry.kind == PostingKind::Real);
+                    line.clear();
+                    extend_entry(
+                        &mut line,
+                        entry,
+                        &parts,
+                        indent,
+                        account_offset,
+                        &node_widths,
+                        opts,
+                    );
+                    let entry_chars_count = entry_line_width(&line, indent);
+                    max_line_chars_count = max_line_chars_count.max(Some(entry_chars_count));
+                }
+            }
+            JournalCstNode::DirectivesGroup { nodes, .. } => {
+                for node in nodes {
+                    if let DirectiveNode::Directive(
+                        directive @ Directive {
+                            comment: Some(_),
+                            name_chars_count,
                             ..
-                        }) => {
-                            spaces::extend(buffer, *first_entry_indent as usize);
-                            buffer.push(*prefix as u8);
-                            buffer.extend_from_slice(content);
-                            buffer.push(b'\n');
-                        }
+                        },
+                    ) = node
+                    {
+                        let directive_chars_count = *name_chars_count as usize
+                            + 1
+                            + directive_content_chars_count(directive, opts);
+                        max_line_chars_count =
+                            max_line_chars_count.max(Some(directive_chars_count));
                     }
                 }
             }
+            _ => {}
         }
     }
+    max_line_chars_count.map_or(0, |chars_count| chars_count + 3)
+}
 
-    #[cfg(any(test, feature = "tracing"))]
+/// Prefix of a comment written with the `comment_prefix` option.
+///
+/// hledger only reads `#` as a comment at the start of lines outside
+/// transactions and directives (`top_level`), elsewhere it's part of the
+/// text, like of a transaction description. So only the prefixes of top
+/// level comments are rewritten.
+#[inline]
+fn comment_prefix(prefix: CommentPrefix, top_level: bool, opts: &FormatJournalOptions) -> u8 {
+    let prefix = match opts.comment_prefix {
+        Some(comment_prefix) if top_level => comment_prefix,
+        _ => prefix,
+    };
+    prefix as u8
+}
+
+/// Number of newlines written for each empty line node, or an empty vector
+/// if blank lines are written as they are.
+///
+/// The newlines of a run of blank lines are written by its first nodes, or
+/// by its last one if there must be more blank lines than nodes.
+fn blank_lines(nodes: &JournalFile, opts: &FormatJournalOptions) -> Vec<usize> {
+    let mut blank_lines = Vec::new();
+    if opts.max_blank_lines.is_none()
+        && opts.blank_lines_between.iter().all(Option::is_none)
+        && !opts.trim_blank_lines
     {
-        let span = tracing::span!(tracing::Level::TRACE, "format_nodes(formatted)");
-        let _enter = span.enter();
+        return blank_lines;
+    }
 
-        tracing::trace!("buffer=\"{}\"", String::from_utf8_lossy(buffer));
+    blank_lines.resize(nodes.len(), 0);
+    let mut i = 0;
+    while i < nodes.len() {
+        if !matches!(nodes[i], JournalCstNode::EmptyLine { .. }) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < nodes.len() && matches!(nodes[i], JournalCstNode::EmptyLine { .. }) {
+            i += 1;
+        }
+
+        let run_length = i - start;
+        let previous = start.checked_sub(1).map(|j| &nodes[j]);
+        let next = nodes.get(i);
+        let n_lines = match (previous.map(NodeKind::of), next.map(NodeKind::of)) {
+            (None, _) | (_, None) if opts.trim_blank_lines => 0,
+            (Some(Some(previous)), Some(Some(next))) if previous == next => opts
+                .blank_lines_between(next)
+                .or(opts.max_blank_lines.map(|max| run_length.min(max)))
+                .unwrap_or(run_length),
+            _ => opts
+                .max_blank_lines
+                .map_or(run_length, |max| run_length.min(max)),
+        };
+
+        for (j, node_lines) in blank_lines[start..i].iter_mut().enumerate() {
+            *node_lines = usize::from(j < n_lines);
+        }
+        blank_lines[i - 1] += n_lines.saturating_sub(run_length);
     }
+    blank_lines
 }
 
+/// Write the dates of a transaction title.
+fn extend_transaction_dates(
+    buffer: &mut Vec<u8>,
+    header: &TransactionHeader,
+    date_format: Option<DateFormat>,
+) {
+    extend_optional_date(buffer, &header.date, date_format);
+    if let Some(secondary_date) = &header.secondary_date {
+        buffer.push(b'=');
+        extend_optional_date(buffer, secondary_date, date_format);
+    }
+}
+
+#[inline]
+fn extend_optional_date(buffer: &mut Vec<u8>, date: &[u8], date_format: Option<DateFormat>) {
+    match date_format {
+        Some(date_format) => extend_date(buffer, date, date_format),
+        None => buffer.extend_from_slice(date),
+    }
+}
+
+/// Write the fields of a transaction title separated by a single space.
+fn extend_transaction_header(
+    buffer: &mut Vec<u8>,
+    header: &TransactionHeader,
+    date_format: Option<DateFormat>,
+) {
+    extend_transaction_dates(buffer, header, date_format);
+    if let Some(status) = header.status {
+        buffer.push(b' ');
+        buffer.push(status as u8);
+    }
+    if let Some(code) = &header.code {
+        buffer.extend_from_slice(b" (");
+        buffer.extend_from_slice(code);
+        buffer.push(b')');
+    }
+    if !header.description.is_empty() {
+        buffer.push(b' ');
+        buffer.extend_from_slice(&header.description);
+    }
+}
+
+/// Write the title of a periodic transaction, separating the period
+/// expression from `~` by a space and from the description by two spaces.
+fn extend_periodic_title(buffer: &mut Vec<u8>, period: &[u8], description: Option<&[u8]>) {
+    buffer.push(b'~');
+    if !period.is_empty() {
+        buffer.push(b' ');
+        buffer.extend_from_slice(period);
+    }
+    if let Some(description) = description {
+        buffer.extend_from_slice(b"  ");
+        buffer.extend_from_slice(description);
+    }
+}
+
+/// Write the title of an auto posting rule, separating the terms of its
+/// query by a single space.
+///
+/// Whitespace inside quoted terms is preserved.
+fn extend_auto_posting_rule_title(buffer: &mut Vec<u8>, query: &[u8]) {
+    buffer.push(b'=');
+    let mut quote = None;
+    let mut pending_space = true;
+    for &byte in query {
+        match quote {
+            Some(q) if byte == q => quote = None,
+            Some(_) => {}
+            None if byte == b' ' || byte == b'\t' => {
+                pending_space = true;
+                continue;
+            }
+            None if byte == b'"' || byte == b'\'' => quote = Some(byte),
+            None => {}
+        }
+        if pending_space {
+            buffer.push(b' ');
+            pending_space = false;
+        }
+        buffer.push(byte);
+    }
+}
+
+/// Date of a `P` directive and the rest of its content.
+fn price_directive_date<'a>(directive: &Directive<'a>) -> Option<(&'a [u8], &'a [u8])> {
+    if directive.name.as_bytes() != b"P" {
+        return None;
+    }
+    let (date, rest) = split_date(directive.content.as_bytes())?;
+    if rest.first().is_some_and(|b| !b.is_ascii_whitespace()) {
+        return None;
+    }
+    Some((date, rest))
+}
+
+/// Number of characters of the content of a directive once formatted.
+fn directive_content_chars_count(directive: &Directive, opts: &FormatJournalOptions) -> usize {
+    match (opts.date_format, price_directive_date(directive)) {
+        (Some(_), Some((date, _))) => {
+            directive.content_chars_count as usize + normalized_date_len(date) - date.len()
+        }
+        _ => directive.content_chars_count as usize,
+    }
+}
+
+/// Write an entry aligning the parts of its value to `widths`.
+///
+/// The spaces that align a part are only written before some content, so
+/// entries without the last parts don't end with spaces.
+///
+/// `account_offset` are the spaces written before the account name, after
+/// its status mark, to align it with the account names inside brackets.
 #[allow(clippy::too_many_arguments)]
 #[inline]
 fn extend_entry(
     buffer: &mut Vec<u8>,
     entry: &crate::parser::TransactionEntry,
-    first_entry_indent: u16,
-    max_entry_name_len: u16,
-    max_entry_value_first_part_before_decimals_len: u16,
-    max_entry_value_first_part_after_decimals_len: u16,
-    max_entry_value_first_separator_len: u16,
-    max_entry_value_second_part_before_decimals_len: u16,
-    max_entry_value_second_part_after_decimals_len: u16,
-    max_entry_value_second_separator_len: u16,
-    max_entry_value_third_part_before_decimals_len: u16,
-    entry_spacing: usize,
+    parts: &[EntryPart; 6],
+    indent: PostingIndent,
+    account_offset: usize,
+    widths: &ColumnWidths,
+    opts: &FormatJournalOptions,
 ) {
-    spaces::extend(buffer, first_entry_indent as usize);
-    buffer.extend_from_slice(&entry.name);
-    if !entry.value_first_part_before_decimals.is_empty() {
-        let n_spaces = entry_spacing + max_entry_name_len as usize
-            - entry.name_chars_count as usize
-            + max_entry_value_first_part_before_decimals_len as usize
-            - entry.value_first_part_before_decimals_chars_count as usize;
-        spaces::extend(buffer, n_spaces);
-    }
-    buffer.extend_from_slice(&entry.value_first_part_before_decimals);
-    buffer.extend_from_slice(&entry.value_first_part_after_decimals);
-
-    if !entry.value_first_separator.is_empty() {
-        let n_spaces = entry_spacing + max_entry_value_first_part_after_decimals_len as usize
-            - entry.value_first_part_after_decimals_chars_count as usize;
-        spaces::extend(buffer, n_spaces);
-    }
-    buffer.extend_from_slice(&entry.value_first_separator);
-    if !entry.value_second_part_before_decimals.is_empty() {
-        let value_first_separator_len = entry.value_first_separator.len();
-        let n_spaces = entry_spacing + max_entry_value_first_separator_len as usize
-            - value_first_separator_len
-            + max_entry_value_second_part_before_decimals_len as usize
-            - entry.value_second_part_before_decimals_chars_count as usize;
-        spaces::extend(buffer, n_spaces);
-    }
-    buffer.extend_from_slice(&entry.value_second_part_before_decimals);
-    buffer.extend_from_slice(&entry.value_second_part_after_decimals);
-
-    if !entry.value_second_separator.is_empty() {
-        let n_spaces = entry_spacing + max_entry_value_second_part_after_decimals_len as usize
-            - entry.value_second_part_after_decimals_chars_count as usize;
-        spaces::extend(buffer, n_spaces);
-    }
-    buffer.extend_from_slice(&entry.value_second_separator);
-    if !entry.value_third_part_before_decimals.is_empty() {
-        let value_second_separator_len = entry.value_second_separator.len();
-        let n_spaces = entry_spacing + max_entry_value_second_separator_len as usize
-            - value_second_separator_len
-            + max_entry_value_third_part_before_decimals_len as usize
-            - entry.value_third_part_before_decimals_chars_count as usize;
-        spaces::extend(buffer, n_spaces);
-    }
-    buffer.extend_from_slice(&entry.value_third_part_before_decimals);
-    buffer.extend_from_slice(&entry.value_third_part_after_decimals);
+    let entry_spacing = opts.entry_spacing;
+    indent.extend(buffer);
+    if account_offset > 0 {
+        // the account name is offset after the status mark, if any
+        let mark_end = match entry.name.first() {
+            Some(b'*' | b'!') => {
+                1 + entry.name[1..]
+                    .iter()
+                    .take_while(|c| **c == b' ' || **c == b'\t')
+                    .count()
+            }
+            _ => 0,
+        };
+        buffer.extend_from_slice(&entry.name[..mark_end]);
+        spaces::extend(buffer, account_offset);
+        buffer.extend_from_slice(&entry.name[mark_end..]);
+    } else {
+        buffer.extend_from_slice(&entry.name);
+    }
+
+    let name_chars_count = entry.name_chars_count as usize + account_offset;
+    let name_width = widths.name_width(opts.align_virtual_account_names);
+    let amount = &parts[0];
+    let mut n_spaces = match opts.amount_column {
+        // no entry has an amount, like in balance assignments
+        _ if widths.parts[0].is_empty() => name_width - name_chars_count,
+        // the amounts, aligned at their decimal marks, end at the column
+        // if the entry name fits before it
+        Some(amount_column) => amount_column
+            .checked_sub(
+                indent.width()
+                    + name_chars_count
+                    + amount.before_decimals_chars_count
+                    + widths.parts[0].after_decimals as usize,
+            )
+            .filter(|n_spaces| *n_spaces >= entry_spacing)
+            .unwrap_or(entry_spacing),
+        None => {
+            entry_spacing + name_width - name_chars_count + widths.parts[0].before_decimals as usize
+                - amount.before_decimals_chars_count
+        }
+    };
+
+    // the columns of the lot annotations and costs are skipped by postings
+    // without them when no posting has both them and an assertion
+    let skip_costs = !widths.assertions_after_costs && parts[1..4].iter().all(EntryPart::is_empty);
+    for (i, (part, part_widths)) in parts.iter().zip(widths.parts).enumerate() {
+        if i > 0 {
+            if part_widths.is_empty() || (skip_costs && i < 4) {
+                continue;
+            }
+            n_spaces += entry_spacing;
+            if part_widths.separator > 0 {
+                if !part.separator.is_empty() {
+                    spaces::extend(buffer, n_spaces);
+                    buffer.extend_from_slice(part.separator);
+                    n_spaces = 0;
+                }
+                n_spaces += entry_spacing + part_widths.separator as usize - part.separator.len();
+            }
+            n_spaces += part_widths.before_decimals as usize - part.before_decimals_chars_count;
+        }
+        if !part.before_decimals.is_empty() || !part.after_decimals.is_empty() {
+            spaces::extend(buffer, n_spaces);
+            buffer.extend_from_slice(&part.before_decimals);
+            buffer.extend_from_slice(&part.after_decimals);
+            n_spaces = 0;
+        }
+        n_spaces += part_widths.after_decimals as usize - part.after_decimals_chars_count;
+    }
 }
 
 mod spaces {
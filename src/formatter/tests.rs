@@ -1,4 +1,7 @@
-use crate::{formatter::format_content, parser::parse_content};
+use crate::{
+    formatter::{format_content, format_content_range_with_options, FormatJournalOptions},
+    parser::parse_content,
+};
 use similar::{ChangeTag, TextDiff};
 
 /// Add a newline to the end of the string if it doesn't have one.
@@ -42,6 +45,12 @@ fn assert_noop_format(content: &str) {
     assert_format(content, &with_ending_newline(content));
 }
 
+fn format_with_options(content: &str, opts: &FormatJournalOptions) -> String {
+    let parsed = parse_content(content.as_bytes()).unwrap();
+    let buffer = crate::formatter::format_content_with_options(&parsed, opts);
+    String::from_utf8(buffer).unwrap()
+}
+
 /// Read a corpus file from the fuzz/corpus directory
 fn read_corpus_file(filename: &str) -> String {
     let path = format!("fuzz/corpus/{}", filename);
@@ -91,6 +100,11 @@ fn multiline_comment() {
     assert_noop_format("comment\nfoo\nbar\nend comment");
 }
 
+#[test]
+fn multiline_comment_with_empty_lines() {
+    assert_noop_format("comment\nfoo\n\nbar\nend comment\n");
+}
+
 #[test]
 fn empty_newlines_are_preserved() {
     assert_noop_format("\n\n\n\n");
@@ -288,6 +302,42 @@ fn auto_posting_rule() {
     );
 }
 
+#[test]
+fn periodic_transaction_title_spacing() {
+    // the period expression ends at the first double space or tab
+    assert_format(
+        "~  monthly from 2024/1   rent  ; budget
+    (expenses:rent)  $1000
+
+~\tweekly
+    (expenses:food)  $50
+",
+        "~ monthly from 2024/1  rent  ; budget
+    (expenses:rent)  $1000
+
+~ weekly
+    (expenses:food)  $50
+",
+    );
+}
+
+#[test]
+fn auto_posting_rule_query_spacing() {
+    // whitespace inside quoted terms is preserved
+    assert_format(
+        r#"=   expenses:food    desc:"whole  foods"	not:tag:x
+    (budget:food)  *-1
+    (budget:total)  *0.25
+    assets:cash  *1.5 USD
+"#,
+        r#"= expenses:food desc:"whole  foods" not:tag:x
+    (budget:food)   *-1
+    (budget:total)   *0.25
+    assets:cash      *1.5 USD
+"#,
+    );
+}
+
 #[test]
 fn full_transaction_1() {
     assert_format(
@@ -512,6 +562,38 @@ fn issue_13() {
     );
 }
 
+#[test]
+fn balance_assertions_column() {
+    // assertions are aligned in their own column, after the costs
+    assert_format(
+        r#"2024-01-01 x
+    assets:checking  = $1500  ; assigned
+    assets:cash   $10 = $20
+    equity    $-10 @ EUR1 == $5
+    assets:stock  1 AAPL =* 3 AAPL @ $150
+"#,
+        r#"2024-01-01 x
+    assets:checking                      =   $1500       ; assigned
+    assets:cash       $10                =     $20
+    equity           $-10       @  EUR1  ==     $5
+    assets:stock        1 AAPL           =*      3 AAPL  @  $150
+"#,
+    );
+    // without postings with both a cost and an assertion, the assertions
+    // are aligned right after the amounts
+    assert_format(
+        r#"2024-01-01 x
+    assets:checking  $10 = $1,500
+    assets:stocks  1 AAPL {$150} @ $170
+    assets:cash  = $20
+"#,
+        r#"2024-01-01 x
+    assets:checking  $10       =  $1,500
+    assets:stocks      1 AAPL  {$150}  @  $170
+    assets:cash                =     $20
+"#,
+    );
+}
 #[test]
 fn lots() {
     assert_format(
@@ -526,6 +608,73 @@ fn lots() {
     );
 }
 
+#[test]
+fn align_virtual_account_names() {
+    let content = r#"2024-01-01 budget
+    expenses:food  $10
+    (budget:food)  $-10
+    * [assets:envelope]  $5
+    * assets:checking
+
+2024-01-02 no virtual postings
+    expenses:food  $10
+    assets:checking
+
+2024-01-03 indented by the first entry
+  expenses:food  $10
+    (budget:food)  $-10
+"#;
+    let opts = FormatJournalOptions::new().with_align_virtual_account_names(true);
+    assert_eq!(
+        format_with_options(content, &opts),
+        r#"2024-01-01 budget
+     expenses:food        $10
+    (budget:food)        $-10
+    * [assets:envelope]    $5
+    *  assets:checking
+
+2024-01-02 no virtual postings
+    expenses:food    $10
+    assets:checking
+
+2024-01-03 indented by the first entry
+   expenses:food   $10
+  (budget:food)   $-10
+"#
+    );
+}
+
+/// Offset real postings don't increase the indent when formatted again.
+#[test]
+fn align_virtual_account_names_is_idempotent() {
+    let content = "2024-01-01 x\n    expenses:food  $10\n    (budget:food)  $-10\n    assets\n";
+    let opts = FormatJournalOptions::new().with_align_virtual_account_names(true);
+    let formatted = format_with_options(content, &opts);
+    assert_eq!(
+        formatted,
+        "2024-01-01 x\n     expenses:food   $10\n    (budget:food)   $-10\n     assets\n"
+    );
+    assert_eq!(format_with_options(&formatted, &opts), formatted);
+}
+
+#[test]
+fn lot_annotations() {
+    assert_format(
+        r#"2024-01-01 buy
+    assets:stocks  10 AAPL {$150.00} [2024-01-15] @ $170
+    assets:stocks  5 MSFT {{$1500}} @ $310.5
+    assets:stocks  -2 AAPL {=$150} [2024/01/15]  ; sold
+    assets:cash
+"#,
+        r#"2024-01-01 buy
+    assets:stocks  10 AAPL  {$150.00}  [2024-01-15]  @  $170
+    assets:stocks   5 MSFT  {{$1500}}                @  $310.5
+    assets:stocks  -2 AAPL  {=$150}    [2024/01/15]  ; sold
+    assets:cash
+"#,
+    );
+}
+
 // https://github.com/mondeja/hledger-fmt/issues/25
 #[test]
 fn issue_25() {
@@ -633,6 +782,507 @@ fn transaction_with_multi_spaced_description() {
     );
 }
 
+#[test]
+fn normalize_transaction_headers() {
+    let content = "2025-10-10=2025-10-12   *  (42)   Payee  |  note   ; comment\n    assets:A   10 EUR\n    assets:B\n";
+    let opts = FormatJournalOptions::new().with_normalize_transaction_headers(true);
+    assert_eq!(
+        format_with_options(content, &opts),
+        "2025-10-10=2025-10-12 * (42) Payee  |  note  ; comment\n    assets:A  10 EUR\n    assets:B\n"
+    );
+}
+
+#[test]
+fn date_format() {
+    let content = r#"P 2024/1/5 EUR $1.10  ; price
+P 2024-01-06 EUR $1.12  ; price
+
+2024.1.5=2024/1/7  *  food  ; comment
+    expenses:food  $10
+    assets
+
+01/06 rent
+    expenses:rent  $500
+    assets
+"#;
+    assert_eq!(
+        format_with_options(
+            content,
+            &FormatJournalOptions::new().with_date_format(Some(crate::DateFormat::Iso))
+        ),
+        r#"P 2024-01-05 EUR $1.10  ; price
+P 2024-01-06 EUR $1.12  ; price
+
+2024-01-05=2024-01-07  *  food  ; comment
+    expenses:food  $10
+    assets
+
+01-06 rent
+    expenses:rent  $500
+    assets
+"#
+    );
+    assert_eq!(
+        format_with_options(
+            content,
+            &FormatJournalOptions::new()
+                .with_date_format(Some(crate::DateFormat::Slash))
+                .with_normalize_transaction_headers(true)
+        ),
+        r#"P 2024/01/05 EUR $1.10  ; price
+P 2024/01/06 EUR $1.12  ; price
+
+2024/01/05=2024/01/07 * food  ; comment
+    expenses:food  $10
+    assets
+
+01/06 rent
+    expenses:rent  $500
+    assets
+"#
+    );
+}
+
+#[test]
+fn date_format_disabled_by_default() {
+    assert_noop_format(
+        "P 2024/1/5 EUR $1.10\n\n2024.1.5 food\n    expenses:food  $10\n    assets\n",
+    );
+}
+
+#[test]
+fn decimal_mark_directive() {
+    assert_format(
+        r#"decimal-mark ,
+
+2024-01-01 test
+    expenses:a  1.234 EUR
+    expenses:b  12,5 EUR
+    assets
+"#,
+        r#"decimal-mark ,
+
+2024-01-01 test
+    expenses:a  1.234 EUR
+    expenses:b     12,5 EUR
+    assets
+"#,
+    );
+}
+
+#[test]
+fn commodity_directive_decimal_marks() {
+    assert_format(
+        r#"commodity 1.000,00 EUR
+commodity USD
+  format USD 1,000.00
+
+2024-01-01 test
+    expenses:a  1.234 EUR
+    expenses:b  12,5 EUR
+    expenses:c  USD 1.5
+    expenses:d  USD 1,000
+    assets
+"#,
+        r#"commodity 1.000,00 EUR
+commodity USD
+  format USD 1,000.00
+
+2024-01-01 test
+    expenses:a      1.234 EUR
+    expenses:b         12,5 EUR
+    expenses:c      USD 1.5
+    expenses:d  USD 1,000
+    assets
+"#,
+    );
+}
+
+#[test]
+fn normalize_amount_styles() {
+    let content = r#"commodity 1.000,00 EUR
+commodity $1,000.00
+
+2024-01-01 test
+    expenses:a  EUR 1234,5  ; comment
+    expenses:b  1234.50$ @ 0,9 EUR
+    expenses:c  $1,234
+    expenses:d  10 GBP
+    assets
+"#;
+    assert_eq!(
+        format_with_options(
+            content,
+            &FormatJournalOptions::new().with_normalize_amount_styles(true)
+        ),
+        r#"commodity 1.000,00 EUR
+commodity $1,000.00
+
+2024-01-01 test
+    expenses:a   1.234,50 EUR  ; comment
+    expenses:b  $1,234.50      @  0,90 EUR
+    expenses:c  $1,234.00
+    expenses:d      10 GBP
+    assets
+"#
+    );
+}
+
+#[test]
+fn normalize_amount_styles_range() {
+    let content = "commodity $1,000.00\n\n2024-01-01 a\n  expenses:a  1000$\n  assets\n\n2024-01-02 b\n  expenses:b  1000$\n  assets\n";
+    let formatted = crate::format_journal_range(
+        content,
+        7..=7,
+        FormatJournalOptions::new().with_normalize_amount_styles(true),
+    )
+    .unwrap();
+    assert_eq!(
+        formatted,
+        "commodity $1,000.00\n\n2024-01-01 a\n  expenses:a  1000$\n  assets\n\n2024-01-02 b\n  expenses:b  $1,000.00\n  assets\n"
+    );
+}
+
+#[test]
+fn alignment_scope() {
+    let content = r#"2024-01-01 rent
+    expenses:rent  $500.00
+    assets
+
+2024-01-02 food
+    expenses:food:groceries  $10
+    assets
+
+account assets
+
+2024-01-03 gift
+    income  $-1000
+    assets
+"#;
+    let format = |scope| {
+        format_with_options(
+            content,
+            &FormatJournalOptions::new().with_alignment_scope(scope),
+        )
+    };
+    assert_eq!(
+        format(crate::AlignmentScope::Transaction),
+        with_ending_newline(content)
+    );
+    assert_eq!(
+        format(crate::AlignmentScope::Block),
+        r#"2024-01-01 rent
+    expenses:rent            $500.00
+    assets
+
+2024-01-02 food
+    expenses:food:groceries   $10
+    assets
+
+account assets
+
+2024-01-03 gift
+    income  $-1000
+    assets
+"#
+    );
+    assert_eq!(
+        format(crate::AlignmentScope::File),
+        r#"2024-01-01 rent
+    expenses:rent              $500.00
+    assets
+
+2024-01-02 food
+    expenses:food:groceries     $10
+    assets
+
+account assets
+
+2024-01-03 gift
+    income                   $-1000
+    assets
+"#
+    );
+}
+
+#[test]
+fn amount_column() {
+    let content = r#"2024-01-01 rent  ; monthly
+    expenses:rent  $500.00  ; comment
+    expenses:a:very:long:account:name  $1.5
+    assets
+"#;
+    assert_eq!(
+        format_with_options(
+            content,
+            &FormatJournalOptions::new().with_amount_column(Some(30))
+        ),
+        r#"2024-01-01 rent  ; monthly
+    expenses:rent      $500.00  ; comment
+    expenses:a:very:long:account:name  $1.5
+    assets
+"#
+    );
+}
+
+#[test]
+fn posting_indent() {
+    let content = "2024-01-01 rent\n  ; comment\n  expenses:rent  $500\n\tassets\n\n2024-01-02 food\n\texpenses:food  $10\n    assets\n";
+    assert_eq!(
+        format_with_options(
+            content,
+            &FormatJournalOptions::new().with_posting_indent(Some(crate::PostingIndent::Spaces(4)))
+        ),
+        "2024-01-01 rent\n    ; comment\n    expenses:rent  $500\n    assets\n\n2024-01-02 food\n    expenses:food  $10\n    assets\n"
+    );
+    assert_eq!(
+        format_with_options(
+            content,
+            &FormatJournalOptions::new().with_posting_indent(Some(crate::PostingIndent::Tab))
+        ),
+        "2024-01-01 rent\n\t; comment\n\texpenses:rent  $500\n\tassets\n\n2024-01-02 food\n\texpenses:food  $10\n\tassets\n"
+    );
+}
+
+#[test]
+fn comment_prefix() {
+    let content = r#"# top level
+; top level
+  # indented
+account assets  # directive
+# after directive
+
+2024-01-01 rent  ; title
+    # entry comment
+    expenses:rent  $500  # entry
+    assets
+"#;
+    let format = |prefix| {
+        format_with_options(
+            content,
+            &FormatJournalOptions::new().with_comment_prefix(prefix),
+        )
+    };
+    assert_eq!(
+        format(None),
+        content.replace("\n# after", "\n                # after")
+    );
+    assert_eq!(
+        format(Some(crate::CommentPrefix::Semicolon)),
+        r#"; top level
+; top level
+  # indented
+account assets  # directive
+                # after directive
+
+2024-01-01 rent  ; title
+    # entry comment
+    expenses:rent  $500  # entry
+    assets
+"#
+    );
+    assert_eq!(
+        format(Some(crate::CommentPrefix::Hash)),
+        r#"# top level
+# top level
+  # indented
+account assets  # directive
+                # after directive
+
+2024-01-01 rent  ; title
+    # entry comment
+    expenses:rent  $500  # entry
+    assets
+"#
+    );
+}
+
+#[test]
+fn comment_prefix_keeps_hash_in_descriptions() {
+    let content =
+        "; comment\n2024-01-01 invoice #42  ; paid\n    expenses:rent  $500\n    assets\n";
+    assert_eq!(
+        format_with_options(
+            content,
+            &FormatJournalOptions::new().with_comment_prefix(Some(crate::CommentPrefix::Hash)),
+        ),
+        "# comment\n2024-01-01 invoice #42  ; paid\n    expenses:rent  $500\n    assets\n"
+    );
+    let content = "# comment\n2024-01-01 invoice #42\n    expenses:rent  $500\n    assets\n";
+    assert_eq!(
+        format_with_options(
+            content,
+            &FormatJournalOptions::new().with_comment_prefix(Some(crate::CommentPrefix::Semicolon)),
+        ),
+        "; comment\n2024-01-01 invoice #42\n    expenses:rent  $500\n    assets\n"
+    );
+}
+
+#[test]
+fn comment_alignment() {
+    let content = r#"account assets  ; directive
+account expenses:rent  ; directive
+
+2024-01-01 rent  ; title
+    expenses:rent  $500  ; entry
+    assets  ; entry without amount
+"#;
+    let format = |alignment| {
+        format_with_options(
+            content,
+            &FormatJournalOptions::new().with_comment_alignment(alignment),
+        )
+    };
+    assert_eq!(
+        format(crate::CommentAlignment::Group),
+        r#"account assets         ; directive
+account expenses:rent  ; directive
+
+2024-01-01 rent  ; title
+    expenses:rent  $500  ; entry
+    assets       ; entry without amount
+"#
+    );
+    assert_eq!(
+        format(crate::CommentAlignment::Column(25)),
+        r#"account assets          ; directive
+account expenses:rent   ; directive
+
+2024-01-01 rent         ; title
+    expenses:rent  $500  ; entry
+    assets              ; entry without amount
+"#
+    );
+    assert_eq!(
+        format(crate::CommentAlignment::File),
+        r#"account assets           ; directive
+account expenses:rent    ; directive
+
+2024-01-01 rent          ; title
+    expenses:rent  $500  ; entry
+    assets               ; entry without amount
+"#
+    );
+    assert_eq!(
+        format(crate::CommentAlignment::SingleSpace),
+        r#"account assets  ; directive
+account expenses:rent  ; directive
+
+2024-01-01 rent ; title
+    expenses:rent  $500 ; entry
+    assets  ; entry without amount
+"#
+    );
+}
+
+#[test]
+fn comment_alignment_file_with_restyled_amounts() {
+    let content = "~ monthly  ; budget\n    expenses:rent  $500\n    assets\n\ncommodity $1,000.00\n\n2024-01-01 rent\n    expenses:rent  1000$  ; restyled\n    assets\n";
+    assert_eq!(
+        format_with_options(
+            content,
+            &FormatJournalOptions::new()
+                .with_comment_alignment(crate::CommentAlignment::File)
+                .with_normalize_amount_styles(true),
+        ),
+        "~ monthly                     ; budget\n    expenses:rent  $500\n    assets\n\ncommodity $1,000.00\n\n2024-01-01 rent\n    expenses:rent  $1,000.00  ; restyled\n    assets\n"
+    );
+}
+
+/// Tab indents are counted as 4 columns, like with `amount_column`.
+#[test]
+fn comment_alignment_file_with_tab_indent() {
+    let content = "2024-01-01 rent  ; title\n    expenses:rent  $500  ; entry\n    assets\n";
+    assert_eq!(
+        format_with_options(
+            content,
+            &FormatJournalOptions::new()
+                .with_comment_alignment(crate::CommentAlignment::File)
+                .with_posting_indent(Some(crate::PostingIndent::Tab)),
+        ),
+        "2024-01-01 rent          ; title\n\texpenses:rent  $500  ; entry\n\tassets\n"
+    );
+}
+
+#[test]
+fn blank_lines() {
+    let content = r#"
+
+; comment
+
+
+
+; comment
+account assets
+2024-01-01 rent
+    expenses:rent  $500
+    assets
+2024-01-02 food
+    expenses:food  $10
+    assets
+
+
+
+
+2024-01-03 gift
+    income  $-1
+    assets
+
+
+"#;
+    let format = |opts| format_with_options(content, &opts);
+    assert_eq!(
+        format(
+            FormatJournalOptions::new()
+                .with_max_blank_lines(Some(1))
+                .with_trim_blank_lines(true)
+        ),
+        r#"; comment
+
+; comment
+account assets
+2024-01-01 rent
+    expenses:rent  $500
+    assets
+
+2024-01-02 food
+    expenses:food  $10
+    assets
+
+2024-01-03 gift
+    income  $-1
+    assets
+"#
+    );
+    assert_eq!(
+        format(
+            FormatJournalOptions::new()
+                .with_max_blank_lines(Some(1))
+                .with_blank_lines_between(crate::NodeKind::Transaction, Some(2))
+                .with_blank_lines_between(crate::NodeKind::Comment, Some(0))
+        ),
+        r#"
+; comment
+; comment
+account assets
+2024-01-01 rent
+    expenses:rent  $500
+    assets
+
+
+2024-01-02 food
+    expenses:food  $10
+    assets
+
+
+2024-01-03 gift
+    income  $-1
+    assets
+
+"#
+    );
+}
+
 #[test]
 fn transaction_with_multi_spaced_description_and_valid_comment() {
     assert_format(
@@ -694,7 +1344,14 @@ fn single_letter_account_names() {
     A  $-1
     B   $1
 ",
-    )
+    );
+    // without amount at the end of the line
+    assert_noop_format(
+        r"2026-01-01 Test
+    A  $-1
+    B
+",
+    );
 }
 
 // https://github.com/mondeja/hledger-fmt/issues/83
@@ -789,8 +1446,8 @@ fn corpus_transaction_codes() {
     income:employer
 
 2016-04-02 (FOREIGN CCY) HLEDGER
-    assets:Lloyds:current  £-6     =   £1208.72
-    expenses:donations      $7.68  @@     £6
+    assets:Lloyds:current  £-6     =  £1208.72
+    expenses:donations      $7.68  @@  £6
 "#;
 
     assert_format(test_input, expected);
@@ -900,7 +1557,7 @@ fn corpus_virtual_postings() {
 "#;
 
     let expected2 = r#"2016-12-31 pension valuation
-    assets:pension:aviva    = £308.27
+    assets:pension:aviva    =  £308.27
     virtual:unrealized pnl
 "#;
 
@@ -945,3 +1602,171 @@ fn corpus_custom_directives() {
     let parsed = parse_content(content.as_bytes());
     assert!(parsed.is_ok(), "Failed to parse stock-trading.journal");
 }
+
+fn assert_range_format(content: &str, lines: core::ops::RangeInclusive<usize>, expected: &str) {
+    let parsed = parse_content(content.as_bytes()).unwrap();
+    let buffer = format_content_range_with_options(
+        &parsed,
+        content.as_bytes(),
+        lines,
+        &FormatJournalOptions::default(),
+    );
+    assert_eq!(String::from_utf8_lossy(&buffer), expected);
+}
+
+#[test]
+fn range_formats_overlapping_transaction() {
+    let content =
+        "2015-10-16 food\n  expenses:food     $10\n\n2015-10-17 rent\n  expenses:rent     $500\n";
+    assert_range_format(
+        content,
+        5..=5,
+        "2015-10-16 food\n  expenses:food     $10\n\n2015-10-17 rent\n  expenses:rent  $500\n",
+    );
+    assert_range_format(
+        content,
+        1..=1,
+        "2015-10-16 food\n  expenses:food  $10\n\n2015-10-17 rent\n  expenses:rent     $500\n",
+    );
+}
+
+#[test]
+fn range_formats_multiple_nodes() {
+    let content = "account  foo\n\n2015-10-16 food\n  expenses:food     $10\n\n2015-10-17 rent\n  expenses:rent     $500\n";
+    assert_range_format(
+        content,
+        1..=3,
+        "account foo\n\n2015-10-16 food\n  expenses:food  $10\n\n2015-10-17 rent\n  expenses:rent     $500\n",
+    );
+}
+
+#[test]
+fn range_outside_content() {
+    let content = "2015-10-16 food\n  expenses:food     $10\n";
+    assert_range_format(content, 10..=20, content);
+    #[allow(clippy::reversed_empty_ranges)]
+    assert_range_format(content, 2..=1, content);
+}
+
+#[test]
+fn range_separates_adjacent_transactions() {
+    let content =
+        "2015-10-16 food\n  expenses:food  $10\n2015-10-17 rent\n  expenses:rent     $500\n";
+    assert_range_format(
+        content,
+        3..=4,
+        "2015-10-16 food\n  expenses:food  $10\n\n2015-10-17 rent\n  expenses:rent  $500\n",
+    );
+    assert_range_format(
+        content,
+        1..=1,
+        "2015-10-16 food\n  expenses:food  $10\n2015-10-17 rent\n  expenses:rent     $500\n",
+    );
+}
+
+#[test]
+fn range_keeps_bytes_outside_nodes() {
+    let content = "; foo   \r\n\r\n2015-10-16 food\r\n  expenses:food     $10\r\n  \r\n2015-10-17 rent\r\n  expenses:rent     $500";
+    assert_range_format(
+        content,
+        3..=3,
+        "; foo   \r\n\r\n2015-10-16 food\n  expenses:food  $10\n  \r\n2015-10-17 rent\r\n  expenses:rent     $500",
+    );
+    assert_range_format(
+        content,
+        7..=7,
+        "; foo   \r\n\r\n2015-10-16 food\r\n  expenses:food     $10\r\n  \r\n2015-10-17 rent\n  expenses:rent  $500\n",
+    );
+}
+
+#[test]
+fn formatter_off_on_regions_are_verbatim() {
+    assert_format(
+        r#"account  foo
+; hledger-fmt: off
+;   | food | rent |
+;   |  10  |  500 |
+2015-10-16 budget
+    expenses:food      $10
+    expenses:rent     $500
+; hledger-fmt: on
+
+2015-10-17 food
+    expenses:food      $10
+"#,
+        r#"account foo
+; hledger-fmt: off
+;   | food | rent |
+;   |  10  |  500 |
+2015-10-16 budget
+    expenses:food      $10
+    expenses:rent     $500
+; hledger-fmt: on
+
+2015-10-17 food
+    expenses:food  $10
+"#,
+    );
+}
+
+#[test]
+fn formatter_off_until_end_of_file() {
+    assert_noop_format(
+        "# hledger-fmt: off\naccount  foo\r\n  \n\n2015-10-16 food\n  expenses:food    $10",
+    );
+}
+
+#[test]
+fn formatter_skip_next_transaction_after_directive() {
+    assert_format(
+        r#"; hledger-fmt: skip
+account   assets
+
+2015-10-16 food
+    expenses:food      $10  ;  comment
+    assets:cash
+"#,
+        r#"; hledger-fmt: skip
+account assets
+
+2015-10-16 food
+    expenses:food      $10  ;  comment
+    assets:cash
+"#,
+    );
+}
+
+#[test]
+fn formatter_skip_next_transaction() {
+    assert_format(
+        r#"; hledger-fmt: skip
+2015-10-16 food
+    expenses:food      $10  ;  comment
+    assets:cash
+2015-10-17 food
+    expenses:food      $10
+"#,
+        r#"; hledger-fmt: skip
+2015-10-16 food
+    expenses:food      $10  ;  comment
+    assets:cash
+2015-10-17 food
+    expenses:food  $10
+"#,
+    );
+}
+
+#[test]
+fn normalize_amount_styles_range_separating_transactions() {
+    let content = "commodity $1,000.00\n\n2024-01-01 a\n  expenses:a  5$\n  assets\n2024-01-02 b\n  expenses:b  1000$\n  expenses:c  5$\n  assets\n";
+    let formatted = crate::format_journal_range(
+        content,
+        3..=6,
+        FormatJournalOptions::new().with_normalize_amount_styles(true),
+    )
+    .unwrap();
+    assert_eq!(
+        formatted,
+        "commodity $1,000.00\n\n2024-01-01 a\n  expenses:a  $5.00\n  assets\n\n2024-01-02 b\n  expenses:b  $1,000.00\n  expenses:c      $5.00\n  assets\n"
+    );
+}
@@ -0,0 +1,273 @@
+//! Formatter of timeclock files
+//!
+//! The dates, times, accounts and descriptions of all the entries of a file
+//! are aligned in columns, and their comments after the longest entry.
+
+use super::{comment_prefix, comment_spaces, spaces, FormatJournalOptions};
+use crate::date::{extend_date, normalized_date_len};
+use crate::parser::timeclock::{TimeclockEntry, TimeclockFile, TimeclockNode};
+use crate::parser::IndentedComment;
+use crate::Vec;
+use core::ops::RangeInclusive;
+
+/// Widths of the columns of timeclock entries
+#[derive(Default)]
+struct Widths {
+    date: usize,
+    /// Width of the times, including their time zones
+    time: usize,
+    /// Width of the accounts of entries with a description
+    account: usize,
+    /// Width of the longest entry, without its comment
+    line: usize,
+}
+
+impl Widths {
+    fn of(nodes: &TimeclockFile, opts: &FormatJournalOptions) -> Self {
+        let entries = nodes.iter().filter_map(|node| match node {
+            TimeclockNode::Entry(entry) => Some(entry),
+            _ => None,
+        });
+        let mut widths = Self::default();
+        for entry in entries.clone() {
+            widths.date = widths.date.max(date_chars_count(entry, opts));
+            widths.time = widths.time.max(time_chars_count(entry));
+            if entry.description.is_some() {
+                widths.account = widths.account.max(entry.account.chars_count());
+            }
+        }
+        widths.line = entries
+            .map(|entry| entry_chars_count(entry, &widths, opts))
+            .max()
+            .unwrap_or(0);
+        widths
+    }
+}
+
+/// Format a timeclock file.
+pub(crate) fn format_timeclock_content_with_options(
+    nodes: &TimeclockFile,
+    opts: &FormatJournalOptions,
+) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(opts.estimated_length);
+    let widths = Widths::of(nodes, opts);
+    for node in nodes {
+        format_node(node, &mut buffer, &widths, opts);
+    }
+    buffer
+}
+
+/// Format the lines `lines` (1-based, inclusive) of a timeclock file,
+/// copying the rest of `content` unchanged.
+///
+/// `nodes` must have been parsed from `content`.
+pub(crate) fn format_timeclock_content_range_with_options(
+    nodes: &TimeclockFile,
+    content: &[u8],
+    lines: RangeInclusive<usize>,
+    opts: &FormatJournalOptions,
+) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(opts.estimated_length);
+    let widths = Widths::of(nodes, opts);
+    let mut copied_until = 0;
+    for node in nodes {
+        let span = node.span();
+        if !lines.contains(&span.start.lineno) {
+            continue;
+        }
+
+        // replace the newline that ends the line too, as it's formatted with one
+        let mut end = span.end.byteno;
+        if content.get(end) == Some(&b'\r') && content.get(end + 1) == Some(&b'\n') {
+            end += 2;
+        } else if content.get(end) == Some(&b'\n') {
+            end += 1;
+        }
+
+        buffer.extend_from_slice(&content[copied_until..span.start.byteno]);
+        format_node(node, &mut buffer, &widths, opts);
+        copied_until = end;
+    }
+    buffer.extend_from_slice(&content[copied_until..]);
+    buffer
+}
+
+/// Format a line of a timeclock file, ending it with a newline.
+fn format_node(
+    node: &TimeclockNode,
+    buffer: &mut Vec<u8>,
+    widths: &Widths,
+    opts: &FormatJournalOptions,
+) {
+    match node {
+        TimeclockNode::EmptyLine { .. } => {}
+        TimeclockNode::Comment(IndentedComment {
+            content,
+            prefix,
+            indent,
+            ..
+        }) => {
+            spaces::extend(buffer, *indent as usize);
+            buffer.push(comment_prefix(*prefix, *indent == 0, opts));
+            buffer.extend_from_slice(content);
+        }
+        TimeclockNode::Entry(entry) => {
+            let line_start = buffer.len();
+            extend_entry(buffer, entry, widths, opts);
+            if let Some(comment) = &entry.comment {
+                let line_chars_count = crate::byte_str::utf8_chars_count(&buffer[line_start..]);
+                // accounts end at two spaces
+                let single_spaces = if entry.description.is_none() && !entry.account.is_empty() {
+                    2
+                } else {
+                    1
+                };
+                spaces::extend(
+                    buffer,
+                    comment_spaces(
+                        opts.comment_alignment,
+                        line_chars_count,
+                        widths.line + 2 - line_chars_count,
+                        single_spaces,
+                    ),
+                );
+                buffer.push(comment_prefix(comment.prefix, false, opts));
+                buffer.extend_from_slice(&comment.content);
+            }
+        }
+    }
+    buffer.push(b'\n');
+}
+
+/// Write an entry without its comment, padding each field to the width
+/// of its column if another field follows it.
+fn extend_entry(
+    buffer: &mut Vec<u8>,
+    entry: &TimeclockEntry,
+    widths: &Widths,
+    opts: &FormatJournalOptions,
+) {
+    buffer.push(entry.code as u8);
+    buffer.push(b' ');
+    match opts.date_format {
+        Some(date_format) => extend_date(buffer, &entry.date, date_format),
+        None => buffer.extend_from_slice(&entry.date),
+    }
+    spaces::extend(buffer, widths.date - date_chars_count(entry, opts) + 1);
+    buffer.extend_from_slice(&entry.time);
+    if let Some(time_zone) = &entry.time_zone {
+        buffer.push(b' ');
+        buffer.extend_from_slice(time_zone);
+    }
+    if entry.account.is_empty() && entry.description.is_none() {
+        return;
+    }
+
+    spaces::extend(buffer, widths.time - time_chars_count(entry) + 1);
+    buffer.extend_from_slice(&entry.account);
+    if let Some(description) = &entry.description {
+        spaces::extend(
+            buffer,
+            widths.account - entry.account.chars_count() + opts.entry_spacing,
+        );
+        buffer.extend_from_slice(description);
+    }
+}
+
+/// Number of characters of an entry once formatted, without its comment
+fn entry_chars_count(
+    entry: &TimeclockEntry,
+    widths: &Widths,
+    opts: &FormatJournalOptions,
+) -> usize {
+    if entry.account.is_empty() && entry.description.is_none() {
+        return 2 + date_chars_count(entry, opts) + 1 + time_chars_count(entry);
+    }
+    let account_start = 2 + widths.date + 1 + widths.time + 1;
+    match &entry.description {
+        None => account_start + entry.account.chars_count(),
+        Some(description) => {
+            account_start + widths.account + opts.entry_spacing + description.chars_count()
+        }
+    }
+}
+
+#[inline]
+fn date_chars_count(entry: &TimeclockEntry, opts: &FormatJournalOptions) -> usize {
+    match opts.date_format {
+        Some(_) => normalized_date_len(&entry.date),
+        None => entry.date.len(),
+    }
+}
+
+#[inline]
+fn time_chars_count(entry: &TimeclockEntry) -> usize {
+    entry.time.len() + entry.time_zone.as_ref().map_or(0, |zone| zone.len() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::timeclock::parse_timeclock_content;
+    use crate::{CommentAlignment, DateFormat, String};
+
+    fn format(content: &str, opts: &FormatJournalOptions) -> String {
+        let nodes = parse_timeclock_content(content.as_bytes()).unwrap();
+        String::from_utf8(format_timeclock_content_with_options(&nodes, opts)).unwrap()
+    }
+
+    #[test]
+    fn aligns_columns() {
+        let content = "; work
+i 2024-01-01 09:00:00 client:acme   meeting with team   ; billable
+o 2024-01-01 10:30:00
+i 2024/1/2 14:00 client:b:longer\tfix bug #12
+o 2024/1/2 16:15 ; done
+
+i 2024-01-03 8:00 +0100 internal
+";
+        let expected = "; work
+i 2024-01-01 09:00:00   client:acme      meeting with team  ; billable
+o 2024-01-01 10:30:00
+i 2024/1/2   14:00      client:b:longer  fix bug #12
+o 2024/1/2   16:15                                          ; done
+
+i 2024-01-03 8:00 +0100 internal
+";
+        let opts = FormatJournalOptions::new();
+        assert_eq!(format(content, &opts), expected);
+        assert_eq!(format(expected, &opts), expected);
+    }
+
+    #[test]
+    fn options() {
+        let content = "i 2024/1/2 14:00 client:acme  ; billable
+o 2024-01-02 16:15  # done
+";
+        let opts = FormatJournalOptions::new()
+            .with_date_format(Some(DateFormat::Iso))
+            .with_comment_alignment(CommentAlignment::SingleSpace);
+        assert_eq!(
+            format(content, &opts),
+            "i 2024-01-02 14:00 client:acme  ; billable
+o 2024-01-02 16:15 # done
+"
+        );
+    }
+
+    #[test]
+    fn range() {
+        let content = "i 2024-01-01 9:00    a  x\no 2024-01-01 10:00:00   b\n";
+        let nodes = parse_timeclock_content(content.as_bytes()).unwrap();
+        let formatted = format_timeclock_content_range_with_options(
+            &nodes,
+            content.as_bytes(),
+            2..=2,
+            &FormatJournalOptions::new(),
+        );
+        assert_eq!(
+            String::from_utf8(formatted).unwrap(),
+            "i 2024-01-01 9:00    a  x\no 2024-01-01 10:00:00 b\n"
+        );
+    }
+}
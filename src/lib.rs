@@ -10,24 +10,122 @@
 extern crate alloc;
 
 #[cfg(not(feature = "std"))]
-pub(crate) use alloc::{boxed::Box, format, string::String, vec::Vec};
+pub(crate) use alloc::{borrow::Cow, boxed::Box, format, string::String, vec::Vec};
 
 #[cfg(feature = "std")]
-pub(crate) use std::{boxed::Box, format, string::String, vec::Vec};
+pub(crate) use std::{borrow::Cow, boxed::Box, format, string::String, vec::Vec};
+
+use core::ops::RangeInclusive;
 
 mod byte_str;
 #[doc(hidden)]
 #[cfg(feature = "cli")]
 pub mod cli;
+mod date;
 #[cfg(feature = "cli")]
 pub mod file_path;
 mod formatter;
 mod parser;
 #[cfg(any(test, feature = "tracing"))]
 mod tracing;
+mod verify;
+
+pub use byte_str::ByteStr;
+pub use date::DateFormat;
+pub use formatter::{
+    AlignmentScope, CommentAlignment, FormatJournalOptions, NodeKind, PostingIndent,
+};
+pub use parser::errors::{SyntaxError, SyntaxErrorContext};
+pub use parser::span::{Position, Span};
+pub use parser::timeclock::{
+    TimeclockCode, TimeclockEntry, TimeclockFile as Timeclock, TimeclockNode,
+};
+pub use parser::{
+    CommentPrefix, Directive, DirectiveNode, IndentedComment, InlineComment, JournalCstNode,
+    JournalFile as Journal, PostingKind, TransactionEntry, TransactionHeader, TransactionNode,
+    TransactionStatus,
+};
+pub use verify::{
+    check_idempotent, check_timeclock_idempotent, verify_equivalent, verify_timeclock_equivalent,
+    IdempotencyError, VerifyError,
+};
+
+/// Parse an hledger journal string file content into its concrete syntax tree.
+///
+/// The returned [`Journal`] borrows from `content`, so it can be inspected
+/// and later formatted with [`format_parsed`] without parsing twice.
+///
+/// ```
+/// use hledger_fmt::{parse_journal, JournalCstNode};
+///
+/// let journal = parse_journal("2024-01-01 rent\n  expenses:rent  $500\n  assets:bank\n").unwrap();
+/// let JournalCstNode::Transaction { title, entries, .. } = &journal[0] else {
+///     unreachable!();
+/// };
+/// assert_eq!(title.as_bytes(), b"2024-01-01 rent");
+/// assert_eq!(entries.len(), 2);
+/// ```
+#[inline]
+pub fn parse_journal(content: &str) -> Result<Journal<'_>, SyntaxError> {
+    parser::parse_content(content.as_bytes())
+}
+
+/// Parse an hledger journal file content as bytes into its concrete syntax tree.
+#[inline]
+pub fn parse_journal_bytes(content: &[u8]) -> Result<Journal<'_>, SyntaxError> {
+    parser::parse_content(content)
+}
+
+/// Parse an hledger journal string file content reporting all its syntax errors.
+///
+/// Lines with syntax errors are skipped, so the returned [`Journal`] is partial
+/// when there are errors and formatting it would drop those lines.
+///
+/// ```
+/// use hledger_fmt::parse_journal_recovering;
+///
+/// let (journal, errors) = parse_journal_recovering("  foo\naccount assets\n  bar\n\n  baz\n");
+/// assert_eq!(journal.len(), 2);
+/// assert_eq!(errors.len(), 2);
+/// assert_eq!(errors[0].lineno, 1);
+/// assert_eq!(errors[1].lineno, 5);
+/// ```
+#[inline]
+pub fn parse_journal_recovering(content: &str) -> (Journal<'_>, Vec<SyntaxError>) {
+    parser::parse_content_recovering(content.as_bytes())
+}
+
+/// Parse an hledger journal file content as bytes reporting all its syntax errors.
+///
+/// See [`parse_journal_recovering`].
+#[inline]
+pub fn parse_journal_bytes_recovering(content: &[u8]) -> (Journal<'_>, Vec<SyntaxError>) {
+    parser::parse_content_recovering(content)
+}
+
+/// Format an already parsed hledger journal with specified options.
+///
+/// The output is valid UTF-8 if the journal was parsed from valid UTF-8.
+#[inline]
+pub fn format_parsed(journal: &Journal, options: FormatJournalOptions) -> Vec<u8> {
+    formatter::format_content_with_options(journal, &options)
+}
+
+/// Format an already parsed hledger journal with the default options.
+#[cfg(feature = "bench")]
+#[deprecated(note = "use `format_parsed` instead, the `bench` feature will be removed")]
+#[inline]
+pub fn format_parsed_journal(parsed: &Journal) -> Result<Vec<u8>, SyntaxError> {
+    Ok(format_parsed(parsed, FormatJournalOptions::new()))
+}
 
-pub use formatter::FormatJournalOptions;
-pub use parser::errors::SyntaxError;
+/// Parse an hledger journal file content as bytes into its concrete syntax tree.
+#[cfg(feature = "bench")]
+#[deprecated(note = "use `parse_journal_bytes` instead, the `bench` feature will be removed")]
+#[inline]
+pub fn parse_content(content: &[u8]) -> Result<Journal<'_>, SyntaxError> {
+    parser::parse_content(content)
+}
 
 /// Format an hledger journal string file content as a String.
 #[inline]
@@ -77,13 +175,140 @@ pub fn format_journal_bytes_with_options(
     Ok(formatted)
 }
 
-#[cfg(feature = "bench")]
+/// Format only the lines `lines` of an hledger journal string file content.
+///
+/// Line numbers start at 1 and the range is inclusive. The nodes that overlap
+/// the range (whole transactions, directive groups, comments...) are formatted
+/// and every byte outside them is kept unchanged.
+///
+/// ```
+/// use hledger_fmt::{format_journal_range, FormatJournalOptions};
+///
+/// let content = "2024-01-01 rent\n  expenses:rent     $500\n\n2024-01-02 food\n  expenses:food     $10\n";
+/// let formatted = format_journal_range(content, 4..=4, FormatJournalOptions::new()).unwrap();
+/// assert_eq!(
+///     formatted,
+///     "2024-01-01 rent\n  expenses:rent     $500\n\n2024-01-02 food\n  expenses:food  $10\n",
+/// );
+/// ```
 #[inline]
-pub fn format_parsed_journal(parsed: &parser::JournalFile) -> Result<Vec<u8>, SyntaxError> {
-    let format_opts = formatter::FormatJournalOptions::new();
-    let formatted = formatter::format_content_with_options(parsed, &format_opts);
+pub fn format_journal_range(
+    content: &str,
+    lines: RangeInclusive<usize>,
+    options: formatter::FormatJournalOptions,
+) -> Result<String, SyntaxError> {
+    let formatted_bytes = format_journal_bytes_range(content.as_bytes(), lines, options)?;
+    // SAFETY: The formatter only outputs valid UTF-8 since it only writes:
+    // 1. Slices from the valid UTF-8 input
+    // 2. ASCII characters (spaces, newlines, comment prefixes)
+    let formatted =
+        String::from_utf8(formatted_bytes).expect("formatter should only produce valid UTF-8");
     Ok(formatted)
 }
 
-#[cfg(feature = "bench")]
-pub use parser::parse_content;
+/// Format only the lines `lines` of an hledger journal file content as bytes.
+///
+/// See [`format_journal_range`].
+#[inline]
+pub fn format_journal_bytes_range(
+    content: &[u8],
+    lines: RangeInclusive<usize>,
+    options: formatter::FormatJournalOptions,
+) -> Result<Vec<u8>, SyntaxError> {
+    let parsed = parser::parse_content(content)?;
+    let merged_options = options.with_estimated_length(content.len());
+    let formatted =
+        formatter::format_content_range_with_options(&parsed, content, lines, &merged_options);
+    Ok(formatted)
+}
+
+/// Parse a timeclock file content into its lines.
+///
+/// ```
+/// use hledger_fmt::{parse_timeclock, TimeclockCode, TimeclockNode};
+///
+/// let timeclock = parse_timeclock("i 2024-01-01 09:00 client:acme  meeting\n").unwrap();
+/// let TimeclockNode::Entry(entry) = &timeclock[0] else {
+///     unreachable!();
+/// };
+/// assert_eq!(entry.code, TimeclockCode::In);
+/// assert_eq!(entry.account.as_bytes(), b"client:acme");
+/// ```
+#[inline]
+pub fn parse_timeclock(content: &str) -> Result<Timeclock<'_>, SyntaxError> {
+    parser::timeclock::parse_timeclock_content(content.as_bytes())
+}
+
+/// Parse a timeclock file content reporting all its syntax errors.
+///
+/// See [`parse_journal_recovering`].
+#[inline]
+pub fn parse_timeclock_recovering(content: &str) -> (Timeclock<'_>, Vec<SyntaxError>) {
+    parser::timeclock::parse_timeclock_content_recovering(content.as_bytes())
+}
+
+/// Format a timeclock file content as a String.
+///
+/// The dates, times, accounts and descriptions of the entries are aligned
+/// in columns.
+///
+/// ```
+/// use hledger_fmt::format_timeclock;
+///
+/// let content = "i 2024-01-01 9:00 client:acme  meeting\no 2024-01-01 10:30\n";
+/// assert_eq!(
+///     format_timeclock(content).unwrap(),
+///     "i 2024-01-01 9:00  client:acme  meeting\no 2024-01-01 10:30\n",
+/// );
+/// ```
+#[inline]
+pub fn format_timeclock(content: &str) -> Result<String, SyntaxError> {
+    format_timeclock_with_options(content, FormatJournalOptions::new())
+}
+
+/// Format a timeclock file content as a String with specified options.
+///
+/// The entry spacing separates accounts from descriptions, and the date
+/// format and the comment options apply as in journals.
+#[inline]
+pub fn format_timeclock_with_options(
+    content: &str,
+    options: formatter::FormatJournalOptions,
+) -> Result<String, SyntaxError> {
+    let parsed = parser::timeclock::parse_timeclock_content(content.as_bytes())?;
+    let merged_options = options.with_estimated_length(content.len());
+    let formatted_bytes =
+        formatter::timeclock::format_timeclock_content_with_options(&parsed, &merged_options);
+    // SAFETY: The formatter only outputs valid UTF-8 since it only writes:
+    // 1. Slices from the valid UTF-8 input
+    // 2. ASCII characters (spaces, newlines, comment prefixes, dates)
+    let formatted =
+        String::from_utf8(formatted_bytes).expect("formatter should only produce valid UTF-8");
+    Ok(formatted)
+}
+
+/// Format only the lines `lines` of a timeclock file content.
+///
+/// Line numbers start at 1 and the range is inclusive. The lines in the range
+/// are aligned with the rest of the file, which is kept unchanged.
+#[inline]
+pub fn format_timeclock_range(
+    content: &str,
+    lines: RangeInclusive<usize>,
+    options: formatter::FormatJournalOptions,
+) -> Result<String, SyntaxError> {
+    let parsed = parser::timeclock::parse_timeclock_content(content.as_bytes())?;
+    let merged_options = options.with_estimated_length(content.len());
+    let formatted_bytes = formatter::timeclock::format_timeclock_content_range_with_options(
+        &parsed,
+        content.as_bytes(),
+        lines,
+        &merged_options,
+    );
+    // SAFETY: The formatter only outputs valid UTF-8 since it only writes:
+    // 1. Slices from the valid UTF-8 input
+    // 2. ASCII characters (spaces, newlines, comment prefixes, dates)
+    let formatted =
+        String::from_utf8(formatted_bytes).expect("formatter should only produce valid UTF-8");
+    Ok(formatted)
+}
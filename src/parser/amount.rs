@@ -0,0 +1,180 @@
+//! Decimal marks of amounts.
+//!
+//! hledger amounts can use `.` or `,` as decimal mark. The parser tracks the
+//! marks declared by `decimal-mark` and `commodity` directives as it walks
+//! the file, so the amounts that follow them are split at the right mark.
+
+use crate::Vec;
+
+/// Decimal marks declared by directives
+#[derive(Default)]
+pub(crate) struct DecimalMarks<'a> {
+    /// Mark declared by the last `decimal-mark` directive
+    default: Option<u8>,
+    /// Commodity symbols and marks declared by `commodity` directives
+    commodities: Vec<(&'a [u8], u8)>,
+}
+
+impl<'a> DecimalMarks<'a> {
+    /// Record the mark declared by a directive, if any
+    pub(crate) fn add_directive(&mut self, name: &[u8], content: &'a [u8]) {
+        match name {
+            b"decimal-mark" => {
+                if let Some(mark @ (b'.' | b',')) = content.first() {
+                    self.default = Some(*mark);
+                }
+            }
+            b"commodity" => self.add_commodity_sample(content),
+            _ => {}
+        }
+    }
+
+    /// Record the mark declared by a `format` subdirective of a `commodity` directive
+    pub(crate) fn add_format_subdirective(&mut self, content: &'a [u8]) {
+        let Some(sample) = content.strip_prefix(b"format") else {
+            return;
+        };
+        if sample.first().is_some_and(u8::is_ascii_whitespace) {
+            let end = memchr::memchr2(b';', b'#', sample).unwrap_or(sample.len());
+            self.add_commodity_sample(&sample[..end]);
+        }
+    }
+
+    /// Record the mark of a sample amount like `1.000,00 EUR`
+    ///
+    /// The rightmost `.` or `,` of the number is the decimal mark.
+    fn add_commodity_sample(&mut self, sample: &'a [u8]) {
+        let Some((first_digit, last_digit)) = digits_range(sample) else {
+            return;
+        };
+        let number_end = (last_digit + 2).min(sample.len());
+        let mark = sample[first_digit..number_end]
+            .iter()
+            .rev()
+            .find(|c| **c == b'.' || **c == b',');
+        if let Some(mark) = mark {
+            self.commodities.push((amount_commodity(sample), *mark));
+        }
+    }
+
+    /// Decimal mark of an amount, `None` if it has not been declared
+    pub(crate) fn get(&self, amount: &[u8]) -> Option<u8> {
+        if !self.commodities.is_empty() {
+            let commodity = amount_commodity(amount);
+            if let Some((_, mark)) = self.commodities.iter().rev().find(|(c, _)| *c == commodity) {
+                return Some(*mark);
+            }
+        }
+        self.default
+    }
+}
+
+/// Indexes of the first and the last digits of an amount
+fn digits_range(amount: &[u8]) -> Option<(usize, usize)> {
+    let first_digit = amount.iter().position(u8::is_ascii_digit)?;
+    let last_digit = amount.iter().rposition(u8::is_ascii_digit)?;
+    Some((first_digit, last_digit))
+}
+
+/// Commodity symbol of an amount, without quotes
+pub(crate) fn amount_commodity(amount: &[u8]) -> &[u8] {
+    let Some((first_digit, last_digit)) = digits_range(amount) else {
+        return trim_commodity(amount);
+    };
+    let before = trim_commodity(&amount[..first_digit]);
+    if !before.is_empty() {
+        return before;
+    }
+    let after = &amount[last_digit + 1..];
+    let after = after
+        .strip_prefix(b".")
+        .or_else(|| after.strip_prefix(b","))
+        .unwrap_or(after);
+    trim_commodity(after)
+}
+
+/// Remove whitespace, signs and quotes around a commodity symbol
+fn trim_commodity(commodity: &[u8]) -> &[u8] {
+    let is_trimmed = |c: &u8| c.is_ascii_whitespace() || matches!(c, b'-' | b'+' | b'"');
+    let start = commodity
+        .iter()
+        .position(|c| !is_trimmed(c))
+        .unwrap_or(commodity.len());
+    let end = commodity
+        .iter()
+        .rposition(|c| !is_trimmed(c))
+        .map_or(start, |i| i + 1);
+    &commodity[start..end]
+}
+
+/// Split an amount at its decimal mark `mark`.
+///
+/// If the amount has no decimal mark, it's split after its last digit.
+/// Returns `None` if the amount has no digits.
+pub(crate) fn split_at_decimal_mark(amount: &[u8], mark: u8) -> Option<(&[u8], &[u8])> {
+    let (_, last_digit) = digits_range(amount)?;
+    if amount.get(last_digit + 1) == Some(&mark) {
+        return Some(amount.split_at(last_digit + 1));
+    }
+    let mark_position = amount[..=last_digit]
+        .iter()
+        .rev()
+        .take_while(|c| c.is_ascii_digit() || matches!(c, b'.' | b',' | b' '))
+        .position(|c| *c == mark)
+        .map(|i| last_digit - i);
+    Some(amount.split_at(mark_position.unwrap_or(last_digit + 1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amount_commodity() {
+        let test_cases: Vec<(&[u8], &[u8])> = vec![
+            (b"$-1", b"$"),
+            (b"-$1,000.00", b"$"),
+            (b"1.000,00 EUR", b"EUR"),
+            (b"EUR 1.000,00", b"EUR"),
+            (b"1000. AAAA", b"AAAA"),
+            (b"3 \"Chocolate Frogs\"", b"Chocolate Frogs"),
+            (b"10", b""),
+        ];
+        for (amount, expected) in test_cases {
+            assert_eq!(amount_commodity(amount), expected, "{amount:?}");
+        }
+    }
+
+    fn assert_split(amount: &str, mark: u8, before: &str, after: &str) {
+        assert_eq!(
+            split_at_decimal_mark(amount.as_bytes(), mark),
+            Some((before.as_bytes(), after.as_bytes())),
+            "{amount:?}"
+        );
+    }
+
+    #[test]
+    fn test_split_at_decimal_mark() {
+        assert_split("1.234,56 EUR", b',', "1.234", ",56 EUR");
+        assert_split("1.234 EUR", b',', "1.234", " EUR");
+        assert_split("1.234 EUR", b'.', "1", ".234 EUR");
+        assert_split("EUR 1 000,5", b',', "EUR 1 000", ",5");
+        assert_split("$1,000", b'.', "$1,000", "");
+        assert_split("1000. AAAA", b'.', "1000", ". AAAA");
+        assert_eq!(split_at_decimal_mark(b"EUR", b','), None);
+    }
+
+    #[test]
+    fn declared_marks() {
+        let mut marks = DecimalMarks::default();
+        assert_eq!(marks.get(b"1,5 EUR"), None);
+
+        marks.add_directive(b"decimal-mark", b",");
+        marks.add_directive(b"commodity", b"$1,000.00");
+        marks.add_directive(b"commodity", b"GBP");
+        marks.add_format_subdirective(b"format 1.000,00 GBP  ; comment");
+        assert_eq!(marks.get(b"1,5 EUR"), Some(b','));
+        assert_eq!(marks.get(b"$-1,000"), Some(b'.'));
+        assert_eq!(marks.get(b"1.000,5 GBP"), Some(b','));
+    }
+}
@@ -1,49 +1,115 @@
 use crate::String;
+use core::fmt;
 
 /// Syntax error occurring while parsing a journal file content.
 #[derive(Debug, PartialEq)]
 pub struct SyntaxError {
+    /// Line number of the error, starting at 1
     pub lineno: usize,
+    /// Column where the error starts, starting at 1
     pub colno_start: usize,
+    /// Column where the error ends (exclusive)
     pub colno_end: usize,
+    /// Description of the error
     pub message: String,
+    /// What the parser expected to find instead
     pub expected: &'static str,
 }
 
-/// Generate an error context similar to what hledger does.
-#[cfg(feature = "cli")]
-pub fn build_error_context(
-    error: &SyntaxError,
-    content: &[u8],
-    file_path_or_stdin: &crate::file_path::FilePathOrStdin,
-) -> String {
-    use std::io::{self, BufRead, Cursor};
-    let cursor = Cursor::new(content);
-    let reader = io::BufReader::new(cursor);
-    let lines = reader
-        .lines()
-        .map(|line| line.unwrap_or_default())
-        .collect::<Vec<String>>();
-    let mut context = format!(
-        "hledger-fmt error: {}:{}:{}:\n",
-        file_path_or_stdin, error.lineno, error.colno_start
-    );
-
-    let lineno_len = format!("{}", error.lineno).len();
-    if error.lineno > 1 {
-        context.push_str(&format!(
-            "{} | {}\n",
-            " ".repeat(lineno_len),
-            lines[error.lineno - 2]
-        ));
+impl SyntaxError {
+    /// Render the error with the lines of `content` where it happened,
+    /// similar to what hledger does.
+    ///
+    /// `source` is displayed before the location of the error, usually
+    /// the path of the file that was being parsed.
+    ///
+    /// ```
+    /// let content = "2024-01-01 rent\n  expenses:rent  $500\n\n  foo\n";
+    /// let error = hledger_fmt::parse_journal(content).unwrap_err();
+    /// assert_eq!(
+    ///     error.display_with_context(content.as_bytes(), "main.journal").to_string(),
+    ///     "hledger-fmt error: main.journal:4:3:
+    ///   |\x20
+    /// 4 |   foo
+    ///   |   ^
+    /// Unexpected character 'f'
+    /// Expected '#', ';' or newline",
+    /// );
+    /// ```
+    #[inline]
+    pub fn display_with_context<'a, S: fmt::Display>(
+        &'a self,
+        content: &'a [u8],
+        source: S,
+    ) -> SyntaxErrorContext<'a, S> {
+        SyntaxErrorContext {
+            error: self,
+            content,
+            source,
+        }
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}, expected {}",
+            self.lineno, self.colno_start, self.message, self.expected
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SyntaxError {}
+
+/// A [`SyntaxError`] displayed along with the content where it happened.
+///
+/// Created by [`SyntaxError::display_with_context`].
+#[derive(Debug)]
+pub struct SyntaxErrorContext<'a, S> {
+    error: &'a SyntaxError,
+    content: &'a [u8],
+    source: S,
+}
+
+impl<S: fmt::Display> fmt::Display for SyntaxErrorContext<'_, S> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let error = self.error;
+        writeln!(
+            f,
+            "hledger-fmt error: {}:{}:{}:",
+            self.source, error.lineno, error.colno_start
+        )?;
+
+        let lineno_len = error.lineno.checked_ilog10().unwrap_or(0) as usize + 1;
+        let line = |lineno: usize| {
+            let line = self
+                .content
+                .split(|&c| c == b'\n')
+                .nth(lineno - 1)
+                .unwrap_or_default();
+            String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line))
+        };
+
+        if error.lineno > 1 {
+            writeln!(f, "{:lineno_len$} | {}", "", line(error.lineno - 1))?;
+        }
+        if error.lineno > 0 {
+            writeln!(f, "{} | {}", error.lineno, line(error.lineno))?;
+        }
+        write!(
+            f,
+            "{:lineno_len$} | {:indent$}",
+            "",
+            "",
+            indent = error.colno_start.saturating_sub(1)
+        )?;
+        for _ in error.colno_start..error.colno_end {
+            f.write_str("^")?;
+        }
+        write!(f, "\n{}\nExpected {}", error.message, error.expected)
     }
-    context.push_str(&format!("{} | {}\n", error.lineno, lines[error.lineno - 1]));
-    context.push_str(&format!(
-        "{} | {}{}\n",
-        " ".repeat(lineno_len),
-        " ".repeat(error.colno_start - 1),
-        "^".repeat(error.colno_end - error.colno_start)
-    ));
-    context.push_str(&format!("{}\nExpected {}", error.message, error.expected));
-    context
 }
@@ -1,41 +1,64 @@
 use crate::{Box, Vec};
 
+pub(crate) mod amount;
 pub mod errors;
+pub mod span;
 #[cfg(test)]
 mod tests;
+pub mod timeclock;
 use crate::format;
 
 use crate::byte_str::ByteStr;
+use crate::date::split_date;
+use amount::{split_at_decimal_mark, DecimalMarks};
 use errors::SyntaxError;
+use span::{Position, Span};
 
 /// A journal file
 pub type JournalFile<'a> = Vec<JournalCstNode<'a>>;
 
 /// Each node in a journal file
-#[cfg_attr(any(test, feature = "tracing"), derive(Debug, PartialEq))]
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub enum JournalCstNode<'a> {
     /// An empty line
-    EmptyLine,
+    ///
+    /// The parser inserts empty lines between transactions that are not
+    /// separated by one, in that case the span is empty.
+    #[non_exhaustive]
+    EmptyLine {
+        /// Location of the line
+        span: Span,
+    },
 
     /// Multiline comment
+    #[non_exhaustive]
     MultilineComment {
         /// The comment content
         content: ByteStr<'a>,
+        /// Location of the comment, from `comment` to `end comment`
+        span: Span,
     },
 
+    /// Single line comment outside of directives groups and transactions
     SingleLineComment(IndentedComment<'a>),
 
     /// Directives group
+    #[non_exhaustive]
     DirectivesGroup {
         /// Directives in the group
         nodes: Vec<DirectiveNode<'a>>,
         /// Maximum length of the directive name + content (u16 max: 65,535 - more than sufficient)
         max_name_content_len: u16,
+        /// Location of the group
+        span: Span,
     },
 
     /// A transaction.
     ///
-    /// Can be a transaction, a auto posting rule, a balance assertion, etc.
+    /// Periodic transactions and auto posting rules are parsed as
+    /// [`JournalCstNode::PeriodicTransaction`] and
+    /// [`JournalCstNode::AutoPostingRule`] nodes wrapping a transaction.
     ///
     /// The syntax is:
     ///
@@ -43,9 +66,17 @@ pub enum JournalCstNode<'a> {
     /// <title>  ; comment
     ///     <entry-name>  <entry-value>  ; comment
     /// ```
+    ///
+    /// The `max_entry_value_*` and `max_entry_assertion_*` fields hold the
+    /// maximum character counts of each part of the entry values (see
+    /// [`TransactionEntry`]), used to align them.
+    #[non_exhaustive]
     Transaction {
         /// Transaction title
         title: ByteStr<'a>,
+        /// Fields of the title, `None` if it doesn't start with a date
+        /// (periodic transactions, auto posting rules...)
+        header: Option<Box<TransactionHeader<'a>>>,
         /// Transaction title comment
         title_comment: Option<InlineComment<'a>>,
         /// Transaction entries
@@ -57,19 +88,103 @@ pub enum JournalCstNode<'a> {
 
         max_entry_value_first_part_before_decimals_len: u16,
         max_entry_value_first_part_after_decimals_len: u16,
+        max_entry_lot_price_len: u16,
+        max_entry_lot_date_len: u16,
         max_entry_value_first_separator_len: u16,
         max_entry_value_second_part_before_decimals_len: u16,
         max_entry_value_second_part_after_decimals_len: u16,
-        max_entry_value_second_separator_len: u16,
-        max_entry_value_third_part_before_decimals_len: u16,
-        max_entry_value_third_part_after_decimals_len: u16,
+        max_entry_assertion_operator_len: u16,
+        max_entry_assertion_before_decimals_len: u16,
+        max_entry_assertion_after_decimals_len: u16,
+        max_entry_assertion_cost_separator_len: u16,
+        max_entry_assertion_cost_before_decimals_len: u16,
+        max_entry_assertion_cost_after_decimals_len: u16,
+        /// Location of the transaction, from the title to the last entry
+        span: Span,
+    },
+
+    /// A periodic transaction, whose title starts with `~`
+    ///
+    /// ```text
+    /// ~ <period expression>  <description>  ; comment
+    ///     <entry-name>  <entry-value>  ; comment
+    /// ```
+    #[non_exhaustive]
+    PeriodicTransaction {
+        /// Period expression, like `monthly from 2024/1`
+        period: ByteStr<'a>,
+        /// Description, separated from the period expression by two or
+        /// more spaces or a tab
+        description: Option<ByteStr<'a>>,
+        /// The transaction, a [`JournalCstNode::Transaction`] whose title is
+        /// the whole title of the periodic transaction
+        transaction: Box<JournalCstNode<'a>>,
     },
+
+    /// An auto posting rule, whose title starts with `=`
+    ///
+    /// ```text
+    /// = <query>  ; comment
+    ///     <entry-name>  <entry-value>  ; comment
+    /// ```
+    ///
+    /// The amounts of its entries can be multipliers of the matched
+    /// amounts, like `*0.25`.
+    #[non_exhaustive]
+    AutoPostingRule {
+        /// Query matching the postings the rule applies to
+        query: ByteStr<'a>,
+        /// The transaction, a [`JournalCstNode::Transaction`] whose title is
+        /// the whole title of the rule
+        transaction: Box<JournalCstNode<'a>>,
+    },
+
+    /// Region of the content that must not be formatted
+    ///
+    /// Delimited by `; hledger-fmt: off` and `; hledger-fmt: on` comments,
+    /// or the next transaction after a `; hledger-fmt: skip` comment.
+    #[non_exhaustive]
+    Verbatim {
+        /// The content of the region, including the delimiting comments
+        /// and the newline of its last line
+        content: ByteStr<'a>,
+        /// Location of the region
+        span: Span,
+    },
+}
+
+impl<'a> JournalCstNode<'a> {
+    /// Location of the node in the journal content
+    #[inline]
+    pub fn span(&self) -> Span {
+        match self {
+            JournalCstNode::EmptyLine { span }
+            | JournalCstNode::MultilineComment { span, .. }
+            | JournalCstNode::DirectivesGroup { span, .. }
+            | JournalCstNode::Transaction { span, .. }
+            | JournalCstNode::Verbatim { span, .. } => *span,
+            JournalCstNode::SingleLineComment(comment) => comment.span,
+            JournalCstNode::PeriodicTransaction { transaction, .. }
+            | JournalCstNode::AutoPostingRule { transaction, .. } => transaction.span(),
+        }
+    }
+
+    /// The transaction of a periodic transaction or auto posting rule,
+    /// or the node itself
+    #[inline]
+    pub(crate) fn inner(&self) -> &Self {
+        match self {
+            JournalCstNode::PeriodicTransaction { transaction, .. }
+            | JournalCstNode::AutoPostingRule { transaction, .. } => transaction,
+            node => node,
+        }
+    }
 }
 
 /// Prefix of a single line comment
 #[repr(u8)]
-#[derive(Clone, Copy)]
-#[cfg_attr(any(test, feature = "tracing"), derive(Debug, PartialEq))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
 pub enum CommentPrefix {
     /// '#'
     Hash = b'#',
@@ -89,16 +204,20 @@ impl CommentPrefix {
 }
 
 /// A single line comment without indentation (inline comment)
-#[cfg_attr(any(test, feature = "tracing"), derive(Debug, PartialEq))]
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub struct InlineComment<'a> {
     /// The comment content
     pub content: ByteStr<'a>,
     /// The comment prefix ('#' or ';')
     pub prefix: CommentPrefix,
+    /// Location of the comment, from the prefix to the end of the line
+    pub span: Span,
 }
 
 /// A single line comment that starts at the beginning of a line and tracks indentation
-#[cfg_attr(any(test, feature = "tracing"), derive(Debug, PartialEq))]
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub struct IndentedComment<'a> {
     /// The comment content
     pub content: ByteStr<'a>,
@@ -106,10 +225,13 @@ pub struct IndentedComment<'a> {
     pub indent: u16,
     /// The comment prefix ('#' or ';')
     pub prefix: CommentPrefix,
+    /// Location of the comment, from the start to the end of the line
+    pub span: Span,
 }
 
 /// A directive
-#[cfg_attr(any(test, feature = "tracing"), derive(Debug, PartialEq))]
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub struct Directive<'a> {
     /// The directive name
     pub name: ByteStr<'a>,
@@ -117,58 +239,395 @@ pub struct Directive<'a> {
     pub content: ByteStr<'a>,
     /// Comment associated with the directive
     pub comment: Option<InlineComment<'a>>,
+    /// Location of the directive line
+    pub span: Span,
     /// Cached character count for name
     pub(crate) name_chars_count: u16,
     /// Cached character count for content
     pub(crate) content_chars_count: u16,
 }
 
+impl<'a> Directive<'a> {
+    /// Number of characters of the directive name
+    #[inline]
+    pub fn name_chars_count(&self) -> usize {
+        self.name_chars_count as usize
+    }
+
+    /// Number of characters of the directive content
+    #[inline]
+    pub fn content_chars_count(&self) -> usize {
+        self.content_chars_count as usize
+    }
+}
+
 /// A directive or a single line comment
-#[cfg_attr(any(test, feature = "tracing"), derive(Debug, PartialEq))]
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub enum DirectiveNode<'a> {
+    /// A directive
     Directive(Directive<'a>),
-    Subdirective(ByteStr<'a>), // includes comments after the subdirective content
+    /// An indented line after a directive
+    #[non_exhaustive]
+    Subdirective {
+        /// Content of the line without indentation, including comments after it
+        content: ByteStr<'a>,
+        /// Location of the line
+        span: Span,
+    },
+    /// A single line comment inside the group
     SingleLineComment(IndentedComment<'a>),
 }
 
+impl<'a> DirectiveNode<'a> {
+    /// Location of the node in the journal content
+    #[inline]
+    pub fn span(&self) -> Span {
+        match self {
+            DirectiveNode::Directive(directive) => directive.span,
+            DirectiveNode::Subdirective { span, .. } => *span,
+            DirectiveNode::SingleLineComment(comment) => comment.span,
+        }
+    }
+}
+
+/// Status mark of a transaction
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransactionStatus {
+    /// '!'
+    Pending = b'!',
+    /// '*'
+    Cleared = b'*',
+}
+
+/// Kind of a posting, set by the brackets around its account name
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PostingKind {
+    /// `account`
+    #[default]
+    Real,
+    /// `(account)`, not required to balance
+    Virtual,
+    /// `[account]`, required to balance with the other balanced virtual postings
+    BalancedVirtual,
+}
+
+impl PostingKind {
+    /// Kind of the posting of an entry name, which can start with a status mark
+    fn of(name: &[u8]) -> Self {
+        let account_start = match name.first() {
+            Some(b'*' | b'!') => {
+                1 + name[1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_whitespace())
+                    .count()
+            }
+            _ => 0,
+        };
+        match (name.get(account_start), name.last()) {
+            (Some(b'('), Some(b')')) => PostingKind::Virtual,
+            (Some(b'['), Some(b']')) => PostingKind::BalancedVirtual,
+            _ => PostingKind::Real,
+        }
+    }
+}
+
+/// Fields of a transaction title
+///
+/// The syntax is:
+///
+/// ```text
+/// <date>[=<secondary date>] [<status>] [(<code>)] [<payee> | <note>]
+/// ```
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub struct TransactionHeader<'a> {
+    /// Transaction date
+    pub date: ByteStr<'a>,
+    /// Secondary date, without the `=`
+    pub secondary_date: Option<ByteStr<'a>>,
+    /// Status mark
+    pub status: Option<TransactionStatus>,
+    /// Transaction code, without parentheses
+    pub code: Option<ByteStr<'a>>,
+    /// Description, the payee and the note with the `|` separator if any
+    pub description: ByteStr<'a>,
+    /// Payee, the description before `|`, or the whole description if it has no `|`
+    pub payee: ByteStr<'a>,
+    /// Note, the description after `|`
+    pub note: Option<ByteStr<'a>>,
+}
+
+impl<'a> TransactionHeader<'a> {
+    /// Parse the fields of a transaction title without comment
+    ///
+    /// Returns `None` if the title doesn't start with a date.
+    fn parse(title: &'a [u8]) -> Option<Self> {
+        let (date, rest) = split_date(title)?;
+        let (secondary_date, rest) = match rest.strip_prefix(b"=") {
+            Some(rest) => {
+                let (secondary_date, rest) = split_date(rest)?;
+                (Some(ByteStr::from(secondary_date)), rest)
+            }
+            None => (None, rest),
+        };
+        if rest
+            .first()
+            .is_some_and(|b| !b.is_ascii_whitespace() && *b != b'*' && *b != b'!')
+        {
+            return None;
+        }
+
+        let mut rest = trim_ascii_whitespace(rest);
+        let status = match rest.first() {
+            Some(b'*') => Some(TransactionStatus::Cleared),
+            Some(b'!') => Some(TransactionStatus::Pending),
+            _ => None,
+        };
+        if status.is_some() {
+            rest = trim_ascii_whitespace(&rest[1..]);
+        }
+
+        let mut code = None;
+        if rest.first() == Some(&b'(') {
+            if let Some(end) = memchr::memchr(b')', rest) {
+                code = Some(ByteStr::from(&rest[1..end]));
+                rest = trim_ascii_whitespace(&rest[end + 1..]);
+            }
+        }
+
+        let (payee, note) = match memchr::memchr(b'|', rest) {
+            Some(pipe) => (
+                trim_ascii_whitespace(&rest[..pipe]),
+                Some(ByteStr::from(trim_ascii_whitespace(&rest[pipe + 1..]))),
+            ),
+            None => (rest, None),
+        };
+
+        Some(Self {
+            date: ByteStr::from(date),
+            secondary_date,
+            status,
+            code,
+            description: ByteStr::from(rest),
+            payee: ByteStr::from(payee),
+            note,
+        })
+    }
+}
+
 /// A transaction entry
-#[cfg_attr(any(test, feature = "tracing"), derive(Debug, PartialEq))]
+///
+/// The value of the entry is split in the amount, its lot annotations, its
+/// cost, the balance assertion or assignment and the cost of the asserted
+/// amount, like `N {N} [date] @ N = N` or `N = N @ N`. Each of them is split again at its decimal
+/// mark so the formatter can align the values by the decimal point.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub struct TransactionEntry<'a> {
-    /// Entry name
+    /// Entry name, with the status mark and the brackets of the account
     pub name: ByteStr<'a>,
+    /// Kind of posting
+    pub kind: PostingKind,
+    /// Value of the first part (the amount) before the decimal mark
     pub value_first_part_before_decimals: ByteStr<'a>,
+    /// Value of the first part (the amount) from the decimal mark
     pub value_first_part_after_decimals: ByteStr<'a>,
+    /// Lot price of the amount, like `{$10}`, `{{$100}}` or `{=$10}`
+    pub lot_price: ByteStr<'a>,
+    /// Lot date of the amount, like `[2024-01-15]`
+    pub lot_date: ByteStr<'a>,
+    /// Separator between the amount and its cost (`@` or `@@`)
     pub value_first_separator: ByteStr<'a>,
+    /// Value of the second part (the cost) before the decimal mark
     pub value_second_part_before_decimals: ByteStr<'a>,
+    /// Value of the second part (the cost) from the decimal mark
     pub value_second_part_after_decimals: ByteStr<'a>,
-    pub value_second_separator: ByteStr<'a>,
-    pub value_third_part_before_decimals: ByteStr<'a>,
-    pub value_third_part_after_decimals: ByteStr<'a>,
+    /// Operator of the balance assertion or assignment (`=`, `==`, `=*` or `==*`)
+    pub assertion_operator: ByteStr<'a>,
+    /// Asserted or assigned amount before the decimal mark
+    pub assertion_before_decimals: ByteStr<'a>,
+    /// Asserted or assigned amount from the decimal mark
+    pub assertion_after_decimals: ByteStr<'a>,
+    /// Separator between the asserted amount and its cost (`@` or `@@`)
+    pub assertion_cost_separator: ByteStr<'a>,
+    /// Cost of the asserted amount before the decimal mark
+    pub assertion_cost_before_decimals: ByteStr<'a>,
+    /// Cost of the asserted amount from the decimal mark
+    pub assertion_cost_after_decimals: ByteStr<'a>,
     /// Comment associated with the entry
     pub comment: Option<InlineComment<'a>>,
+    /// Location of the entry line
+    pub span: Span,
+    /// Indent of the entry (u16 max: 65,535 - more than sufficient)
+    pub(crate) indent: u16,
     /// Cached character counts
     pub(crate) name_chars_count: u16,
     pub(crate) value_first_part_before_decimals_chars_count: u16,
     pub(crate) value_first_part_after_decimals_chars_count: u16,
+    pub(crate) lot_price_chars_count: u16,
+    pub(crate) lot_date_chars_count: u16,
     pub(crate) value_second_part_before_decimals_chars_count: u16,
     pub(crate) value_second_part_after_decimals_chars_count: u16,
-    pub(crate) value_third_part_before_decimals_chars_count: u16,
-    pub(crate) value_third_part_after_decimals_chars_count: u16,
+    pub(crate) assertion_before_decimals_chars_count: u16,
+    pub(crate) assertion_after_decimals_chars_count: u16,
+    pub(crate) assertion_cost_before_decimals_chars_count: u16,
+    pub(crate) assertion_cost_after_decimals_chars_count: u16,
+}
+
+impl<'a> TransactionEntry<'a> {
+    /// Number of characters of the entry name
+    #[inline]
+    pub fn name_chars_count(&self) -> usize {
+        self.name_chars_count as usize
+    }
+
+    /// Number of characters of the first part of the value before the decimal mark
+    #[inline]
+    pub fn value_first_part_before_decimals_chars_count(&self) -> usize {
+        self.value_first_part_before_decimals_chars_count as usize
+    }
+
+    /// Number of characters of the first part of the value after the decimal mark
+    #[inline]
+    pub fn value_first_part_after_decimals_chars_count(&self) -> usize {
+        self.value_first_part_after_decimals_chars_count as usize
+    }
+
+    /// Number of characters of the lot price
+    #[inline]
+    pub fn lot_price_chars_count(&self) -> usize {
+        self.lot_price_chars_count as usize
+    }
+
+    /// Number of characters of the lot date
+    #[inline]
+    pub fn lot_date_chars_count(&self) -> usize {
+        self.lot_date_chars_count as usize
+    }
+
+    /// Number of characters of the second part of the value before the decimal mark
+    #[inline]
+    pub fn value_second_part_before_decimals_chars_count(&self) -> usize {
+        self.value_second_part_before_decimals_chars_count as usize
+    }
+
+    /// Number of characters of the second part of the value after the decimal mark
+    #[inline]
+    pub fn value_second_part_after_decimals_chars_count(&self) -> usize {
+        self.value_second_part_after_decimals_chars_count as usize
+    }
+
+    /// Number of characters of the asserted amount before the decimal mark
+    #[inline]
+    pub fn assertion_before_decimals_chars_count(&self) -> usize {
+        self.assertion_before_decimals_chars_count as usize
+    }
+
+    /// Number of characters of the asserted amount after the decimal mark
+    #[inline]
+    pub fn assertion_after_decimals_chars_count(&self) -> usize {
+        self.assertion_after_decimals_chars_count as usize
+    }
+
+    /// Number of characters of the cost of the asserted amount before the decimal mark
+    #[inline]
+    pub fn assertion_cost_before_decimals_chars_count(&self) -> usize {
+        self.assertion_cost_before_decimals_chars_count as usize
+    }
+
+    /// Number of characters of the cost of the asserted amount after the decimal mark
+    #[inline]
+    pub fn assertion_cost_after_decimals_chars_count(&self) -> usize {
+        self.assertion_cost_after_decimals_chars_count as usize
+    }
 }
 
 /// A transaction entry or a single line comment
-#[cfg_attr(any(test, feature = "tracing"), derive(Debug, PartialEq))]
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub enum TransactionNode<'a> {
+    /// A posting
     TransactionEntry(Box<TransactionEntry<'a>>),
+    /// A single line comment inside the transaction
     SingleLineComment(IndentedComment<'a>),
 }
 
+impl<'a> TransactionNode<'a> {
+    /// Location of the node in the journal content
+    #[inline]
+    pub fn span(&self) -> Span {
+        match self {
+            TransactionNode::TransactionEntry(entry) => entry.span,
+            TransactionNode::SingleLineComment(comment) => comment.span,
+        }
+    }
+}
+
+/// Regions of the content that must not be formatted
+#[derive(Clone, Copy)]
+enum VerbatimRegion {
+    /// Started by `hledger-fmt: off`, until `hledger-fmt: on`
+    Off,
+    /// The transaction after a `hledger-fmt: skip` comment, until its end
+    Skip,
+}
+
+/// Comments that control the formatting of regions of the content
+#[derive(PartialEq)]
+enum FormatterComment {
+    Off,
+    On,
+    Skip,
+}
+
+impl FormatterComment {
+    /// Parse the content of a comment after its prefix
+    fn from_comment_content(content: &[u8]) -> Option<Self> {
+        let content = trim_ascii_whitespace(content);
+        let value = trim_ascii_whitespace(content.strip_prefix(b"hledger-fmt:")?);
+        match value {
+            b"off" => Some(Self::Off),
+            b"on" => Some(Self::On),
+            b"skip" => Some(Self::Skip),
+            _ => None,
+        }
+    }
+}
+
+/// Remove the leading and trailing ASCII whitespace of a slice
+fn trim_ascii_whitespace(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |i| i + 1);
+    &bytes[start..end]
+}
+
 #[derive(Default)]
 /// Temporary data used by the parser
 struct ParserTempData<'a> {
+    /// Position of the start of the current line
+    line_start: Position,
+    /// Position of the end of the previous line
+    prev_line_end: Position,
     /// Location of the content of the current multiline comment
     multiline_comment_byte_start: usize,
     multiline_comment_byte_end: usize,
+    /// Position of the `comment` line of the current multiline comment
+    multiline_comment_start: Position,
+    /// Current region that must not be formatted
+    verbatim: Option<VerbatimRegion>,
+    /// Whether a `hledger-fmt: skip` comment was found before the next transaction
+    skip_next_transaction: bool,
+    /// Location of the start of the current region that must not be formatted
+    verbatim_byte_start: usize,
+    verbatim_start: Position,
     /// Directives group nodes
     directives_group_nodes: Vec<DirectiveNode<'a>>,
     /// Maximum length of the directive names + contents (u16 max: 65,535 - more than sufficient)
@@ -176,8 +635,14 @@ struct ParserTempData<'a> {
     /// Transaction title
     transaction_title_byte_start: usize,
     transaction_title_byte_end: usize,
+    /// Fields of the transaction title
+    transaction_header: Option<Box<TransactionHeader<'a>>>,
     /// Transaction title comment
     transaction_title_comment: Option<InlineComment<'a>>,
+    /// Position of the start of the transaction title
+    transaction_start: Position,
+    /// Position of the end of the last line of the transaction
+    transaction_end: Position,
     /// Transaction entries
     transaction_entries: Vec<TransactionNode<'a>>,
     /// If the current transaction has entries (ignoring comments)
@@ -188,14 +653,21 @@ struct ParserTempData<'a> {
     max_entry_name_len: u16,
     max_entry_value_first_part_before_decimals_len: u16,
     max_entry_value_first_part_after_decimals_len: u16,
+    max_entry_lot_price_len: u16,
+    max_entry_lot_date_len: u16,
     max_entry_value_first_separator_len: u16,
     max_entry_value_second_part_before_decimals_len: u16,
     max_entry_value_second_part_after_decimals_len: u16,
-    max_entry_value_second_separator_len: u16,
-    max_entry_value_third_part_before_decimals_len: u16,
-    max_entry_value_third_part_after_decimals_len: u16,
+    max_entry_assertion_operator_len: u16,
+    max_entry_assertion_before_decimals_len: u16,
+    max_entry_assertion_after_decimals_len: u16,
+    max_entry_assertion_cost_separator_len: u16,
+    max_entry_assertion_cost_before_decimals_len: u16,
+    max_entry_assertion_cost_after_decimals_len: u16,
     /// Reusable entry value parser to avoid allocations
     entry_value_parser: EntryValueParser,
+    /// Decimal marks declared by the directives parsed so far
+    decimal_marks: DecimalMarks<'a>,
 }
 
 impl<'a> ParserTempData<'a> {
@@ -204,7 +676,29 @@ impl<'a> ParserTempData<'a> {
     }
 }
 
-pub fn parse_content<'a>(bytes: &'a [u8]) -> Result<JournalFile<'a>, errors::SyntaxError> {
+/// Parse a journal file content, stopping at the first syntax error.
+pub fn parse_content<'a>(bytes: &'a [u8]) -> Result<JournalFile<'a>, SyntaxError> {
+    let mut errors = Vec::new();
+    let journal = parse(bytes, &mut errors, false);
+    match errors.pop() {
+        Some(error) => Err(error),
+        None => Ok(journal),
+    }
+}
+
+/// Parse a journal file content, skipping the lines with syntax errors.
+///
+/// Returns the nodes that could be parsed along with all the errors found.
+pub fn parse_content_recovering<'a>(bytes: &'a [u8]) -> (JournalFile<'a>, Vec<SyntaxError>) {
+    let mut errors = Vec::new();
+    let journal = parse(bytes, &mut errors, true);
+    (journal, errors)
+}
+
+/// Parse a journal file content, pushing syntax errors to `errors`.
+///
+/// If `recover` is `false`, stops at the first error.
+fn parse<'a>(bytes: &'a [u8], errors: &mut Vec<SyntaxError>, recover: bool) -> JournalFile<'a> {
     #[cfg(any(test, feature = "tracing"))]
     {
         // TODO: if not using here, the `bytes` argument is propagared to all children
@@ -226,6 +720,7 @@ pub fn parse_content<'a>(bytes: &'a [u8]) -> Result<JournalFile<'a>, errors::Syn
     let mut byteno = 0;
     let bytes_length = bytes.len();
     while byteno < bytes_length {
+        data.line_start = Position::line_start(byteno, lineno);
         let newline_pos = memchr::memchr(b'\n', &bytes[byteno..]);
 
         let (line_end_including_newline, line_end) = match newline_pos {
@@ -242,9 +737,62 @@ pub fn parse_content<'a>(bytes: &'a [u8]) -> Result<JournalFile<'a>, errors::Syn
             None => (bytes_length, bytes_length), // last line without newline
         };
 
+        let line_end_position = data.line_start.in_line(line_end - byteno);
+
+        if let Some(region) = data.verbatim {
+            let line = unsafe { bytes.get_unchecked(byteno..line_end) };
+            let first_byte = line.first().copied();
+            let is_blank = line.iter().all(|b| b.is_ascii_whitespace());
+            let starts_node = !is_blank
+                && first_byte.is_some_and(|b| !b.is_ascii_whitespace() && b != b'#' && b != b';');
+
+            match region {
+                VerbatimRegion::Skip if is_blank || starts_node => {
+                    // the transaction has ended, process the line normally
+                    let end = data.prev_line_end;
+                    save_verbatim(&mut data, &mut journal, bytes, byteno, end);
+                }
+                VerbatimRegion::Off
+                    if first_byte.is_some_and(|b| b == b'#' || b == b';')
+                        && FormatterComment::from_comment_content(&line[1..])
+                            == Some(FormatterComment::On) =>
+                {
+                    save_verbatim(
+                        &mut data,
+                        &mut journal,
+                        bytes,
+                        line_end_including_newline,
+                        line_end_position,
+                    );
+                    data.prev_line_end = line_end_position;
+                    byteno = line_end_including_newline;
+                    lineno += 1;
+                    continue;
+                }
+                _ => {
+                    data.prev_line_end = line_end_position;
+                    byteno = line_end_including_newline;
+                    lineno += 1;
+                    continue;
+                }
+            }
+        }
+
         if line_end == byteno {
             // empty line
-            process_empty_line(&mut journal, &mut data, bytes);
+            if inside_multiline_comment {
+                if data.multiline_comment_byte_start == 0 {
+                    data.multiline_comment_byte_start = byteno;
+                }
+                data.multiline_comment_byte_end = line_end_including_newline;
+            } else {
+                let span = Span {
+                    start: data.line_start,
+                    end: line_end_position,
+                };
+                process_empty_line(&mut journal, &mut data, bytes, span);
+            }
+            data.prev_line_end = line_end_position;
             byteno = line_end_including_newline;
             lineno += 1;
             continue;
@@ -266,7 +814,7 @@ pub fn parse_content<'a>(bytes: &'a [u8]) -> Result<JournalFile<'a>, errors::Syn
 
         if inside_multiline_comment {
             if line == b"end comment" {
-                save_multiline_comment(&mut data, &mut journal, bytes);
+                save_multiline_comment(&mut data, &mut journal, bytes, line_end_position);
                 inside_multiline_comment = false;
             } else {
                 if data.multiline_comment_byte_start == 0 {
@@ -275,6 +823,7 @@ pub fn parse_content<'a>(bytes: &'a [u8]) -> Result<JournalFile<'a>, errors::Syn
                 data.multiline_comment_byte_end = line_end_including_newline;
             }
 
+            data.prev_line_end = line_end_position;
             byteno = line_end_including_newline;
             lineno += 1;
             continue;
@@ -296,12 +845,24 @@ pub fn parse_content<'a>(bytes: &'a [u8]) -> Result<JournalFile<'a>, errors::Syn
 
             if all_whitespace {
                 // empty line (only spaces or tabs)
-                process_empty_line(&mut journal, &mut data, bytes);
+                let span = Span {
+                    start: data.line_start,
+                    end: line_end_position,
+                };
+                process_empty_line(&mut journal, &mut data, bytes, span);
             } else if data.transaction_title_byte_start == 0 && data.transaction_title_byte_end == 0
             {
                 // probably single line comment that starts with a space,
                 // but could be also a subdirective
-                parse_single_line_comment_or_subdirective(line, lineno, &mut data, &mut journal)?;
+                if let Err(error) =
+                    parse_single_line_comment_or_subdirective(line, lineno, &mut data, &mut journal)
+                {
+                    errors.push(error);
+                    if !recover {
+                        return journal;
+                    }
+                    // skip the line
+                }
             } else {
                 // maybe inside transaction entry, but could be also a single
                 // line comment inside a transaction group
@@ -318,6 +879,28 @@ pub fn parse_content<'a>(bytes: &'a [u8]) -> Result<JournalFile<'a>, errors::Syn
                 .entered();
             }
             // single line comment
+            let formatter_comment = FormatterComment::from_comment_content(&line[1..]);
+            if formatter_comment == Some(FormatterComment::Skip) {
+                // the comment itself is formatted, only the transaction is kept
+                data.skip_next_transaction = true;
+            } else if formatter_comment == Some(FormatterComment::Off) {
+                if !data.directives_group_nodes.is_empty() {
+                    save_directives_group_nodes(&mut data, &mut journal);
+                } else if data.transaction_title_byte_start != 0
+                    || data.transaction_title_byte_end != 0
+                {
+                    save_transaction(&mut data, &mut journal, bytes);
+                }
+                data.verbatim = Some(VerbatimRegion::Off);
+                data.verbatim_byte_start = byteno;
+                data.verbatim_start = data.line_start;
+
+                data.prev_line_end = line_end_position;
+                byteno = line_end_including_newline;
+                lineno += 1;
+                continue;
+            }
+
             let prefix = CommentPrefix::from_byte(first_byte);
 
             let content = ByteStr::from(&line[1..]);
@@ -325,6 +908,7 @@ pub fn parse_content<'a>(bytes: &'a [u8]) -> Result<JournalFile<'a>, errors::Syn
                 content,
                 prefix,
                 indent: 0,
+                span: Span::in_line(data.line_start, 0, line.len()),
             };
 
             if data.directives_group_nodes.is_empty()
@@ -336,44 +920,77 @@ pub fn parse_content<'a>(bytes: &'a [u8]) -> Result<JournalFile<'a>, errors::Syn
             {
                 data.transaction_entries
                     .push(TransactionNode::SingleLineComment(comment));
+                data.transaction_end = line_end_position;
             } else {
                 data.directives_group_nodes
                     .push(DirectiveNode::SingleLineComment(comment));
             }
         } else if let Some(name) = unsafe { maybe_start_with_directive(line) } {
+            // a directive ends the current transaction
+            if data.transaction_title_byte_start != 0 || data.transaction_title_byte_end != 0 {
+                save_transaction(&mut data, &mut journal, bytes);
+            }
             parse_directive(name, line, &mut data);
         } else if line != b"comment" {
             // starts transaction
 
-            // if we are in a current transaction, save it adding a newline
-            if data.transaction_title_byte_start != 0 || data.transaction_title_byte_end != 0 {
-                process_empty_line(&mut journal, &mut data, bytes);
+            // if we are in a directives group, save it, and if we are in a
+            // current transaction, save it adding a newline
+            if !data.directives_group_nodes.is_empty() {
+                save_directives_group_nodes(&mut data, &mut journal);
+            } else if data.transaction_title_byte_start != 0 || data.transaction_title_byte_end != 0
+            {
+                let span = Span {
+                    start: data.line_start,
+                    end: data.line_start,
+                };
+                process_empty_line(&mut journal, &mut data, bytes, span);
+            }
+
+            if data.skip_next_transaction {
+                data.skip_next_transaction = false;
+                data.verbatim = Some(VerbatimRegion::Skip);
+                data.verbatim_byte_start = byteno;
+                data.verbatim_start = data.line_start;
+
+                data.prev_line_end = line_end_position;
+                byteno = line_end_including_newline;
+                lineno += 1;
+                continue;
             }
 
             data.transaction_title_byte_start = byteno;
+            data.transaction_start = data.line_start;
+            data.transaction_end = line_end_position;
             parse_transaction_title(line, &mut data);
         } else {
             inside_multiline_comment = true;
+            data.multiline_comment_start = data.line_start;
         }
 
+        data.prev_line_end = line_end_position;
         byteno = line_end_including_newline;
         lineno += 1;
     }
 
     // Hledger v1.40 traits not ended multiline comments as a multiline comment
-    if inside_multiline_comment {
+    if data.verbatim.is_some() {
+        let end = data.prev_line_end;
+        save_verbatim(&mut data, &mut journal, bytes, bytes_length, end);
+    } else if inside_multiline_comment {
         if data.multiline_comment_byte_start != 0 && data.multiline_comment_byte_end == 0 {
             data.multiline_comment_byte_end = byteno;
         }
 
-        save_multiline_comment(&mut data, &mut journal, bytes);
+        let end = data.prev_line_end;
+        save_multiline_comment(&mut data, &mut journal, bytes, end);
     } else if !data.directives_group_nodes.is_empty() {
         save_directives_group_nodes(&mut data, &mut journal);
     } else if data.transaction_title_byte_start != 0 || data.transaction_title_byte_end != 0 {
         save_transaction(&mut data, &mut journal, bytes);
     }
 
-    Ok(journal)
+    journal
 }
 
 #[cfg_attr(
@@ -399,13 +1016,14 @@ fn process_empty_line<'a>(
     journal: &mut Vec<JournalCstNode<'a>>,
     data: &mut ParserTempData<'a>,
     bytes: &'a [u8],
+    span: Span,
 ) {
     if !data.directives_group_nodes.is_empty() {
         save_directives_group_nodes(data, journal);
     } else if data.transaction_title_byte_start != 0 || data.transaction_title_byte_end != 0 {
         save_transaction(data, journal, bytes);
     }
-    journal.push(JournalCstNode::EmptyLine);
+    journal.push(JournalCstNode::EmptyLine { span });
 }
 
 #[cfg_attr(
@@ -463,12 +1081,25 @@ fn parse_directive<'a>(name: &'a [u8], line: &'a [u8], data: &mut ParserTempData
     let mut comment = None;
     if there_is_coment {
         // it should be a comment
-        comment = parse_inline_comment(line, line_length, comment_colno_padding, None);
+        comment = parse_inline_comment(
+            line,
+            line_length,
+            comment_colno_padding,
+            None,
+            data.line_start,
+        );
     }
 
-    let name = ByteStr::from(name);
-    let content = ByteStr::from(&line[start..content_end]);
-    save_directive(name, content, comment, data);
+    let content = &line[start..content_end];
+    data.decimal_marks.add_directive(name, content);
+    let span = Span::in_line(data.line_start, 0, line_length);
+    save_directive(
+        ByteStr::from(name),
+        ByteStr::from(content),
+        comment,
+        span,
+        data,
+    );
 }
 
 #[cfg_attr(
@@ -488,6 +1119,7 @@ fn save_directive<'a>(
     name: ByteStr<'a>,
     content: ByteStr<'a>,
     comment: Option<InlineComment<'a>>,
+    span: Span,
     data: &mut ParserTempData<'a>,
 ) {
     let content_len = content.chars_count();
@@ -497,6 +1129,7 @@ fn save_directive<'a>(
             name,
             content,
             comment,
+            span,
             name_chars_count: name_length as u16,
             content_chars_count: content_len as u16,
         }));
@@ -520,21 +1153,22 @@ fn parse_inline_comment<'a>(
     line_length: usize,
     colno_padding: usize,
     from_comment_prefix: Option<CommentPrefix>,
+    line_start: Position,
 ) -> Option<InlineComment<'a>> {
-    let (content_bytes, prefix) = if let Some(comment_prefix) = from_comment_prefix {
-        (&line[colno_padding..line_length], comment_prefix)
+    let (content_start, prefix) = if let Some(comment_prefix) = from_comment_prefix {
+        (colno_padding, comment_prefix)
     } else {
         // Use memchr2 to efficiently find comment markers
         let search_slice = &line[colno_padding..line_length];
         let pos = memchr::memchr2(b'#', b';', search_slice)?;
         let comment_prefix = CommentPrefix::from_byte(search_slice[pos]);
-        let start = colno_padding + pos + 1;
-        (&line[start..line_length], comment_prefix)
+        (colno_padding + pos + 1, comment_prefix)
     };
 
     Some(InlineComment {
-        content: ByteStr::from(content_bytes),
+        content: ByteStr::from(&line[content_start..line_length]),
         prefix,
+        span: Span::in_line(line_start, content_start - 1, line_length),
     })
 }
 
@@ -596,11 +1230,13 @@ fn parse_single_line_comment_or_subdirective<'a>(
     let end = line_length;
 
     let content = ByteStr::from(&line[content_start..end]);
+    let span = Span::in_line(data.line_start, 0, line_length);
     if let Some(prefix) = comment_prefix {
         let comment = IndentedComment {
             content,
             prefix,
             indent: (content_start - 1) as u16,
+            span,
         };
         if data.directives_group_nodes.is_empty() {
             journal.push(JournalCstNode::SingleLineComment(comment));
@@ -612,13 +1248,42 @@ fn parse_single_line_comment_or_subdirective<'a>(
                 .push(DirectiveNode::SingleLineComment(comment));
         }
     } else if is_subdirective {
+        let parent = data
+            .directives_group_nodes
+            .iter()
+            .rev()
+            .find_map(|node| match node {
+                DirectiveNode::Directive(directive) => Some(directive),
+                _ => None,
+            });
+        if parent.is_some_and(|directive| directive.name.as_bytes() == b"commodity") {
+            data.decimal_marks
+                .add_format_subdirective(content.as_bytes());
+        }
         data.directives_group_nodes
-            .push(DirectiveNode::Subdirective(content));
+            .push(DirectiveNode::Subdirective { content, span });
     }
 
     Ok(())
 }
 
+/// Save the current region that must not be formatted, ending at `byte_end`.
+fn save_verbatim<'a>(
+    data: &mut ParserTempData<'a>,
+    journal: &mut Vec<JournalCstNode<'a>>,
+    bytes: &'a [u8],
+    byte_end: usize,
+    end: Position,
+) {
+    let content = ByteStr::from(&bytes[data.verbatim_byte_start..byte_end]);
+    let span = Span {
+        start: data.verbatim_start,
+        end,
+    };
+    journal.push(JournalCstNode::Verbatim { content, span });
+    data.verbatim = None;
+}
+
 #[cfg_attr(
     any(test, feature = "tracing"),
     tracing::instrument(
@@ -640,10 +1305,15 @@ fn save_multiline_comment<'a>(
     data: &mut ParserTempData<'a>,
     journal: &mut Vec<JournalCstNode<'a>>,
     bytes: &'a [u8],
+    end: Position,
 ) {
     let content =
         ByteStr::from(&bytes[data.multiline_comment_byte_start..data.multiline_comment_byte_end]);
-    journal.push(JournalCstNode::MultilineComment { content });
+    let span = Span {
+        start: data.multiline_comment_start,
+        end,
+    };
+    journal.push(JournalCstNode::MultilineComment { content, span });
     data.multiline_comment_byte_start = 0;
     data.multiline_comment_byte_end = 0;
 }
@@ -669,9 +1339,16 @@ fn save_directives_group_nodes<'a>(
     data: &mut ParserTempData<'a>,
     journal: &mut Vec<JournalCstNode<'a>>,
 ) {
+    let nodes = core::mem::take(&mut data.directives_group_nodes);
+    // a group always starts with a directive
+    let span = Span {
+        start: nodes[0].span().start,
+        end: nodes[nodes.len() - 1].span().end,
+    };
     journal.push(JournalCstNode::DirectivesGroup {
-        nodes: core::mem::take(&mut data.directives_group_nodes),
+        nodes,
         max_name_content_len: data.directives_group_max_name_content_len,
+        span,
     });
     data.directives_group_max_name_content_len = 0;
 }
@@ -696,6 +1373,10 @@ fn save_directives_group_nodes<'a>(
     )
 )]
 fn parse_transaction_entry<'a>(line: &'a [u8], data: &mut ParserTempData<'a>) {
+    let line_start = data.line_start;
+    let span = Span::in_line(line_start, 0, line.len());
+    data.transaction_end = span.end;
+
     let first_byte = unsafe { *line.get_unchecked(0) };
     let at_indent = first_byte != b'\t';
     let mut indent = if at_indent { 0 } else { 4 };
@@ -725,6 +1406,7 @@ fn parse_transaction_entry<'a>(line: &'a [u8], data: &mut ParserTempData<'a>) {
                     } else {
                         CommentPrefix::Semicolon
                     }),
+                    line_start,
                 );
                 if let Some(comment) = maybe_comment {
                     // if the first comment is indented with >=2 and first entry indent
@@ -739,19 +1421,21 @@ fn parse_transaction_entry<'a>(line: &'a [u8], data: &mut ParserTempData<'a>) {
                             content: comment.content,
                             prefix: comment.prefix,
                             indent: indent as u16,
+                            span,
                         }));
                     return; // is comment only
                 }
                 break;
             } else {
                 entry_name_start = end - 1;
-                entry_name_end = entry_name_start;
+                entry_name_end = end;
                 prev_was_whitespace = false;
                 break;
             }
         }
     } else {
         end += 1; // skip first tab
+        entry_name_start = end;
     }
 
     while end < line_length {
@@ -769,14 +1453,20 @@ fn parse_transaction_entry<'a>(line: &'a [u8], data: &mut ParserTempData<'a>) {
             prev_was_whitespace = false;
             if c == b';' && entry_name_end == 0 {
                 // inside comment in transactions group
-                let maybe_comment =
-                    parse_inline_comment(line, line_length, end, Some(CommentPrefix::Semicolon));
+                let maybe_comment = parse_inline_comment(
+                    line,
+                    line_length,
+                    end,
+                    Some(CommentPrefix::Semicolon),
+                    line_start,
+                );
                 if let Some(comment) = maybe_comment {
                     data.transaction_entries
                         .push(TransactionNode::SingleLineComment(IndentedComment {
                             content: comment.content,
                             prefix: comment.prefix,
                             indent: indent as u16,
+                            span,
                         }));
                     return; // is comment only
                 }
@@ -819,6 +1509,7 @@ fn parse_transaction_entry<'a>(line: &'a [u8], data: &mut ParserTempData<'a>) {
                     } else {
                         CommentPrefix::Semicolon
                     }),
+                    line_start,
                 );
                 break;
             } else if !c.is_ascii_whitespace() {
@@ -836,6 +1527,7 @@ fn parse_transaction_entry<'a>(line: &'a [u8], data: &mut ParserTempData<'a>) {
                 } else {
                     CommentPrefix::Semicolon
                 }),
+                line_start,
             );
             break;
         } else {
@@ -848,73 +1540,104 @@ fn parse_transaction_entry<'a>(line: &'a [u8], data: &mut ParserTempData<'a>) {
     //     + indent + 1;
     let entry_value = &line[entry_value_start..entry_value_end];
     data.entry_value_parser.reset();
-    let p = data.entry_value_parser.parse(entry_value);
+    let p = data
+        .entry_value_parser
+        .parse(entry_value, &data.decimal_marks);
+
+    let kind = PostingKind::of(&entry_name);
+
+    // Cache the character counts of the entry
+    let name_chars_count = entry_name.chars_count() as u16;
+    let value_first_part_before_decimals_chars_count =
+        p.amount_before_decimals.chars_count() as u16;
+    let value_first_part_after_decimals_chars_count = p.amount_after_decimals.chars_count() as u16;
+    let lot_price_chars_count = p.lot_price.chars_count() as u16;
+    let lot_date_chars_count = p.lot_date.chars_count() as u16;
+    let value_second_part_before_decimals_chars_count = p.cost_before_decimals.chars_count() as u16;
+    let value_second_part_after_decimals_chars_count = p.cost_after_decimals.chars_count() as u16;
+    let assertion_before_decimals_chars_count = p.assertion_before_decimals.chars_count() as u16;
+    let assertion_after_decimals_chars_count = p.assertion_after_decimals.chars_count() as u16;
+    let assertion_cost_before_decimals_chars_count =
+        p.assertion_cost_before_decimals.chars_count() as u16;
+    let assertion_cost_after_decimals_chars_count =
+        p.assertion_cost_after_decimals.chars_count() as u16;
 
     data.max_entry_value_first_part_before_decimals_len = data
         .max_entry_value_first_part_before_decimals_len
-        .max(p.first_part_before_decimals.chars_count() as u16);
+        .max(value_first_part_before_decimals_chars_count);
     data.max_entry_value_first_part_after_decimals_len = data
         .max_entry_value_first_part_after_decimals_len
-        .max(p.first_part_after_decimals.chars_count() as u16);
+        .max(value_first_part_after_decimals_chars_count);
+
+    data.max_entry_lot_price_len = data.max_entry_lot_price_len.max(lot_price_chars_count);
+    data.max_entry_lot_date_len = data.max_entry_lot_date_len.max(lot_date_chars_count);
 
     data.max_entry_value_first_separator_len = data
         .max_entry_value_first_separator_len
-        .max(p.first_separator.len() as u16);
+        .max(p.cost_separator.len() as u16);
 
     data.max_entry_value_second_part_before_decimals_len = data
         .max_entry_value_second_part_before_decimals_len
-        .max(p.second_part_before_decimals.chars_count() as u16);
+        .max(value_second_part_before_decimals_chars_count);
     data.max_entry_value_second_part_after_decimals_len = data
         .max_entry_value_second_part_after_decimals_len
-        .max(p.second_part_after_decimals.chars_count() as u16);
-
-    data.max_entry_value_second_separator_len = data
-        .max_entry_value_second_separator_len
-        .max(p.second_separator.len() as u16);
-
-    data.max_entry_value_third_part_before_decimals_len = data
-        .max_entry_value_third_part_before_decimals_len
-        .max(p.third_part_before_decimals.chars_count() as u16);
-    data.max_entry_value_third_part_after_decimals_len = data
-        .max_entry_value_third_part_after_decimals_len
-        .max(p.third_part_after_decimals.chars_count() as u16);
-
-    // Cache the character counts we just computed
-    let name_chars_count = entry_name.chars_count() as u16;
-    let value_first_part_before_decimals_chars_count =
-        p.first_part_before_decimals.chars_count() as u16;
-    let value_first_part_after_decimals_chars_count =
-        p.first_part_after_decimals.chars_count() as u16;
-    let value_second_part_before_decimals_chars_count =
-        p.second_part_before_decimals.chars_count() as u16;
-    let value_second_part_after_decimals_chars_count =
-        p.second_part_after_decimals.chars_count() as u16;
-    let value_third_part_before_decimals_chars_count =
-        p.third_part_before_decimals.chars_count() as u16;
-    let value_third_part_after_decimals_chars_count =
-        p.third_part_after_decimals.chars_count() as u16;
+        .max(value_second_part_after_decimals_chars_count);
+
+    data.max_entry_assertion_operator_len = data
+        .max_entry_assertion_operator_len
+        .max(p.assertion_operator.len() as u16);
+
+    data.max_entry_assertion_before_decimals_len = data
+        .max_entry_assertion_before_decimals_len
+        .max(assertion_before_decimals_chars_count);
+    data.max_entry_assertion_after_decimals_len = data
+        .max_entry_assertion_after_decimals_len
+        .max(assertion_after_decimals_chars_count);
+
+    data.max_entry_assertion_cost_separator_len = data
+        .max_entry_assertion_cost_separator_len
+        .max(p.assertion_cost_separator.len() as u16);
+
+    data.max_entry_assertion_cost_before_decimals_len = data
+        .max_entry_assertion_cost_before_decimals_len
+        .max(assertion_cost_before_decimals_chars_count);
+    data.max_entry_assertion_cost_after_decimals_len = data
+        .max_entry_assertion_cost_after_decimals_len
+        .max(assertion_cost_after_decimals_chars_count);
 
     data.transaction_has_no_comment_entries = true;
     data.transaction_entries
         .push(TransactionNode::TransactionEntry(Box::new(
             TransactionEntry {
                 name: entry_name,
-                value_first_part_before_decimals: p.first_part_before_decimals,
-                value_first_part_after_decimals: p.first_part_after_decimals,
-                value_first_separator: p.first_separator,
-                value_second_part_before_decimals: p.second_part_before_decimals,
-                value_second_part_after_decimals: p.second_part_after_decimals,
-                value_second_separator: p.second_separator,
-                value_third_part_before_decimals: p.third_part_before_decimals,
-                value_third_part_after_decimals: p.third_part_after_decimals,
+                kind,
+                value_first_part_before_decimals: p.amount_before_decimals,
+                value_first_part_after_decimals: p.amount_after_decimals,
+                lot_price: p.lot_price,
+                lot_date: p.lot_date,
+                value_first_separator: p.cost_separator,
+                value_second_part_before_decimals: p.cost_before_decimals,
+                value_second_part_after_decimals: p.cost_after_decimals,
+                assertion_operator: p.assertion_operator,
+                assertion_before_decimals: p.assertion_before_decimals,
+                assertion_after_decimals: p.assertion_after_decimals,
+                assertion_cost_separator: p.assertion_cost_separator,
+                assertion_cost_before_decimals: p.assertion_cost_before_decimals,
+                assertion_cost_after_decimals: p.assertion_cost_after_decimals,
                 comment,
+                span,
+                indent: indent as u16,
                 name_chars_count,
                 value_first_part_before_decimals_chars_count,
                 value_first_part_after_decimals_chars_count,
+                lot_price_chars_count,
+                lot_date_chars_count,
                 value_second_part_before_decimals_chars_count,
                 value_second_part_after_decimals_chars_count,
-                value_third_part_before_decimals_chars_count,
-                value_third_part_after_decimals_chars_count,
+                assertion_before_decimals_chars_count,
+                assertion_after_decimals_chars_count,
+                assertion_cost_before_decimals_chars_count,
+                assertion_cost_after_decimals_chars_count,
             },
         )));
 }
@@ -933,16 +1656,13 @@ fn parse_transaction_title<'a>(line: &'a [u8], data: &mut ParserTempData<'a>) {
     let line_length = line.len();
     let mut end = 0;
     let mut comment_prefix = None;
-    // TODO: handle title + description to standarize?
+    // hledger reads `#` as part of the description, like in `invoice #42`
     while end < line_length {
         let c = line[end];
         end += 1;
         if c == b';' {
             comment_prefix = Some(CommentPrefix::Semicolon);
             break;
-        } else if c == b'#' {
-            comment_prefix = Some(CommentPrefix::Hash);
-            break;
         }
     }
 
@@ -958,11 +1678,17 @@ fn parse_transaction_title<'a>(line: &'a [u8], data: &mut ParserTempData<'a>) {
     }
 
     data.transaction_title_byte_end = data.transaction_title_byte_start + end;
+    data.transaction_header = TransactionHeader::parse(&line[..end]).map(Box::new);
     if comment_prefix.is_none() {
         return;
     }
-    data.transaction_title_comment =
-        parse_inline_comment(line, line_length, original_end, comment_prefix);
+    data.transaction_title_comment = parse_inline_comment(
+        line,
+        line_length,
+        original_end,
+        comment_prefix,
+        data.line_start,
+    );
 }
 
 #[cfg_attr(
@@ -989,10 +1715,10 @@ fn save_transaction<'a>(
     journal: &mut Vec<JournalCstNode<'a>>,
     bytes: &'a [u8],
 ) {
-    let title =
-        ByteStr::from(&bytes[data.transaction_title_byte_start..data.transaction_title_byte_end]);
-    journal.push(JournalCstNode::Transaction {
-        title,
+    let title = &bytes[data.transaction_title_byte_start..data.transaction_title_byte_end];
+    let transaction = JournalCstNode::Transaction {
+        title: ByteStr::from(title),
+        header: data.transaction_header.take(),
         title_comment: data.transaction_title_comment.take(),
         entries: core::mem::take(&mut data.transaction_entries),
         first_entry_indent: data.first_entry_indent,
@@ -1001,16 +1727,40 @@ fn save_transaction<'a>(
             .max_entry_value_first_part_before_decimals_len,
         max_entry_value_first_part_after_decimals_len: data
             .max_entry_value_first_part_after_decimals_len,
+        max_entry_lot_price_len: data.max_entry_lot_price_len,
+        max_entry_lot_date_len: data.max_entry_lot_date_len,
         max_entry_value_first_separator_len: data.max_entry_value_first_separator_len,
         max_entry_value_second_part_before_decimals_len: data
             .max_entry_value_second_part_before_decimals_len,
         max_entry_value_second_part_after_decimals_len: data
             .max_entry_value_second_part_after_decimals_len,
-        max_entry_value_second_separator_len: data.max_entry_value_second_separator_len,
-        max_entry_value_third_part_before_decimals_len: data
-            .max_entry_value_third_part_before_decimals_len,
-        max_entry_value_third_part_after_decimals_len: data
-            .max_entry_value_third_part_after_decimals_len,
+        max_entry_assertion_operator_len: data.max_entry_assertion_operator_len,
+        max_entry_assertion_before_decimals_len: data.max_entry_assertion_before_decimals_len,
+        max_entry_assertion_after_decimals_len: data.max_entry_assertion_after_decimals_len,
+        max_entry_assertion_cost_separator_len: data.max_entry_assertion_cost_separator_len,
+        max_entry_assertion_cost_before_decimals_len: data
+            .max_entry_assertion_cost_before_decimals_len,
+        max_entry_assertion_cost_after_decimals_len: data
+            .max_entry_assertion_cost_after_decimals_len,
+        span: Span {
+            start: data.transaction_start,
+            end: data.transaction_end,
+        },
+    };
+    journal.push(match title.first() {
+        Some(b'~') => {
+            let (period, description) = split_at_double_space(&title[1..]);
+            JournalCstNode::PeriodicTransaction {
+                period: ByteStr::from(period),
+                description: description.map(ByteStr::from),
+                transaction: Box::new(transaction),
+            }
+        }
+        Some(b'=') => JournalCstNode::AutoPostingRule {
+            query: ByteStr::from(trim_ascii_whitespace(&title[1..])),
+            transaction: Box::new(transaction),
+        },
+        _ => transaction,
     });
     data.transaction_title_byte_start = 0;
     data.transaction_title_byte_end = 0;
@@ -1019,12 +1769,34 @@ fn save_transaction<'a>(
     data.max_entry_name_len = 0;
     data.max_entry_value_first_part_before_decimals_len = 0;
     data.max_entry_value_first_part_after_decimals_len = 0;
+    data.max_entry_lot_price_len = 0;
+    data.max_entry_lot_date_len = 0;
     data.max_entry_value_first_separator_len = 0;
     data.max_entry_value_second_part_before_decimals_len = 0;
     data.max_entry_value_second_part_after_decimals_len = 0;
-    data.max_entry_value_second_separator_len = 0;
-    data.max_entry_value_third_part_before_decimals_len = 0;
-    data.max_entry_value_third_part_after_decimals_len = 0;
+    data.max_entry_assertion_operator_len = 0;
+    data.max_entry_assertion_before_decimals_len = 0;
+    data.max_entry_assertion_after_decimals_len = 0;
+    data.max_entry_assertion_cost_separator_len = 0;
+    data.max_entry_assertion_cost_before_decimals_len = 0;
+    data.max_entry_assertion_cost_after_decimals_len = 0;
+}
+
+/// Split a text at its first double space or tab, like the period expression
+/// and the description of the title of a periodic transaction.
+///
+/// Both parts are trimmed and the second one is `None` if it's empty.
+fn split_at_double_space(text: &[u8]) -> (&[u8], Option<&[u8]>) {
+    let rest = trim_ascii_whitespace(text);
+    let end = rest
+        .windows(2)
+        .position(|pair| pair == b"  " || pair[0] == b'\t')
+        .unwrap_or(rest.len());
+    let description = trim_ascii_whitespace(&rest[end..]);
+    (
+        &rest[..end],
+        (!description.is_empty()).then_some(description),
+    )
 }
 
 #[inline(always)]
@@ -1438,12 +2210,15 @@ unsafe fn maybe_start_with_directive(line: &[u8]) -> Option<&[u8]> {
 ///
 /// Where:
 ///
-/// - `N` is a number and optional commodity.
-/// - `sep` is either `=`, `==` or `==*`.
+/// - `N` is a number and optional commodity. The amount can be followed by
+///   a lot price (`{N}`, `{{N}}` or `{=N}`) and a lot date (`[date]`).
+/// - `sep` is either `=`, `==`, `=*` or `==*`.
 /// - Rest of characters are literals.
 ///
 /// In order to format the transaction entries, we must extract each part of the value
-/// with their size in unit and decimal parts.
+/// with their size in unit and decimal parts. The parts are split at up to two
+/// separators and then classified as the amount, its cost, the balance assertion
+/// or assignment and the cost of the asserted amount.
 #[derive(Default)]
 pub(crate) struct EntryValueParser {
     // Using u16 for positions within entry value (max 65,535 chars - more than sufficient)
@@ -1460,14 +2235,19 @@ pub(crate) struct EntryValueParser {
 }
 
 pub(crate) struct EntryValueParserReturn<'a> {
-    pub first_part_before_decimals: ByteStr<'a>,
-    pub first_part_after_decimals: ByteStr<'a>,
-    pub first_separator: ByteStr<'a>,
-    pub second_part_before_decimals: ByteStr<'a>,
-    pub second_part_after_decimals: ByteStr<'a>,
-    pub second_separator: ByteStr<'a>,
-    pub third_part_before_decimals: ByteStr<'a>,
-    pub third_part_after_decimals: ByteStr<'a>,
+    pub amount_before_decimals: ByteStr<'a>,
+    pub amount_after_decimals: ByteStr<'a>,
+    pub lot_price: ByteStr<'a>,
+    pub lot_date: ByteStr<'a>,
+    pub cost_separator: ByteStr<'a>,
+    pub cost_before_decimals: ByteStr<'a>,
+    pub cost_after_decimals: ByteStr<'a>,
+    pub assertion_operator: ByteStr<'a>,
+    pub assertion_before_decimals: ByteStr<'a>,
+    pub assertion_after_decimals: ByteStr<'a>,
+    pub assertion_cost_separator: ByteStr<'a>,
+    pub assertion_cost_before_decimals: ByteStr<'a>,
+    pub assertion_cost_after_decimals: ByteStr<'a>,
 }
 
 #[derive(Debug)]
@@ -1552,7 +2332,11 @@ impl EntryValueParser {
         self.third_part_value_end = end as u16;
     }
 
-    pub(crate) fn parse<'a>(&mut self, value: &'a [u8]) -> EntryValueParserReturn<'a> {
+    pub(crate) fn parse<'a>(
+        &mut self,
+        value: &'a [u8],
+        decimal_marks: &DecimalMarks,
+    ) -> EntryValueParserReturn<'a> {
         //let chars = value.chars();
         let value_length = value.len();
 
@@ -1568,10 +2352,19 @@ impl EntryValueParser {
         let mut third_part_value = String::with_capacity(value_length);
         */
 
+        // lot annotations are skipped as if they were a space
+        let lot = lot_annotations(value);
+        let lot_start = lot.as_ref().map_or(usize::MAX, |lot| lot.start);
+
         let mut end = 0;
         while end < value_length {
-            let c = value[end];
-            end += 1;
+            let c = if end == lot_start {
+                end = lot.as_ref().map_or(end, |lot| lot.end);
+                b' '
+            } else {
+                end += 1;
+                value[end - 1]
+            };
             match state {
                 FirstPartCommodityBefore => {
                     if c.is_ascii_whitespace() {
@@ -1598,6 +2391,10 @@ impl EntryValueParser {
                             state = FirstPartNumber;
                         }
                         current_commodity_is_quoted = true;
+                    } else if c == b'=' && self.is_first_part_value_empty() {
+                        // balance assignment, without amount
+                        self.update_first_separator(end);
+                        state = FirstSeparator;
                     } else {
                         self.update_first_part_value(end);
                     }
@@ -1817,32 +2614,137 @@ impl EntryValueParser {
         let third_part_value =
             &value[self.third_part_value_start as usize..self.third_part_value_end as usize];
 
-        let (first_part_value_before_decimals, first_part_value_after_decimals) =
-            split_value_in_before_decimals_after_decimals(first_part_value);
-        let (second_part_value_before_decimals, second_part_value_after_decimals) =
-            split_value_in_before_decimals_after_decimals(second_part_value);
-        let (third_part_value_before_decimals, third_part_value_after_decimals) =
-            split_value_in_before_decimals_after_decimals(third_part_value);
+        let first_part = split_value_in_before_decimals_after_decimals(
+            first_part_value,
+            decimal_marks.get(first_part_value),
+        );
+        let second_part = split_value_in_before_decimals_after_decimals(
+            second_part_value,
+            decimal_marks.get(second_part_value),
+        );
+        let third_part = split_value_in_before_decimals_after_decimals(
+            third_part_value,
+            decimal_marks.get(third_part_value),
+        );
+        let first_separator =
+            &value[self.first_separator_start as usize..self.first_separator_end as usize];
+        let second_separator =
+            &value[self.second_separator_start as usize..self.second_separator_end as usize];
+
+        // the balance assertion can be written before or after the cost
+        let empty: (&[u8], (&[u8], &[u8])) = (b"", (b"", b""));
+        let (cost, assertion, assertion_cost) = if first_separator.starts_with(b"=") {
+            (
+                empty,
+                (first_separator, second_part),
+                (second_separator, third_part),
+            )
+        } else {
+            (
+                (first_separator, second_part),
+                (second_separator, third_part),
+                empty,
+            )
+        };
 
+        let (lot_price, lot_date) = lot.map_or((&b""[..], &b""[..]), |lot| (lot.price, lot.date));
         EntryValueParserReturn {
-            first_part_before_decimals: ByteStr::from(first_part_value_before_decimals),
-            first_part_after_decimals: ByteStr::from(first_part_value_after_decimals),
-            first_separator: ByteStr::from(
-                &value[self.first_separator_start as usize..self.first_separator_end as usize],
-            ),
-            second_part_before_decimals: ByteStr::from(second_part_value_before_decimals),
-            second_part_after_decimals: ByteStr::from(second_part_value_after_decimals),
-            second_separator: ByteStr::from(
-                &value[self.second_separator_start as usize..self.second_separator_end as usize],
-            ),
-            third_part_before_decimals: ByteStr::from(third_part_value_before_decimals),
-            third_part_after_decimals: ByteStr::from(third_part_value_after_decimals),
+            amount_before_decimals: ByteStr::from(first_part.0),
+            amount_after_decimals: ByteStr::from(first_part.1),
+            lot_price: ByteStr::from(lot_price),
+            lot_date: ByteStr::from(lot_date),
+            cost_separator: ByteStr::from(cost.0),
+            cost_before_decimals: ByteStr::from(cost.1 .0),
+            cost_after_decimals: ByteStr::from(cost.1 .1),
+            assertion_operator: ByteStr::from(assertion.0),
+            assertion_before_decimals: ByteStr::from(assertion.1 .0),
+            assertion_after_decimals: ByteStr::from(assertion.1 .1),
+            assertion_cost_separator: ByteStr::from(assertion_cost.0),
+            assertion_cost_before_decimals: ByteStr::from(assertion_cost.1 .0),
+            assertion_cost_after_decimals: ByteStr::from(assertion_cost.1 .1),
         }
     }
 }
 
+/// Lot annotations of an amount
+struct LotAnnotations<'a> {
+    /// Position where the annotations start in the entry value
+    start: usize,
+    /// Position where the annotations end in the entry value
+    end: usize,
+    /// Lot price, like `{$10}`, `{{$100}}` or `{=$10}`
+    price: &'a [u8],
+    /// Lot date, like `[2024-01-15]`
+    date: &'a [u8],
+}
+
+/// Find the lot annotations that follow the amount of an entry value.
+///
+/// Each annotation can only appear once, in any order.
+fn lot_annotations(value: &[u8]) -> Option<LotAnnotations<'_>> {
+    let mut quoted = false;
+    let start = value.iter().position(|&c| {
+        if c == b'"' {
+            quoted = !quoted;
+        }
+        !quoted && matches!(c, b'{' | b'[' | b'@' | b'=')
+    })?;
+
+    let mut lot = LotAnnotations {
+        start,
+        end: start,
+        price: &[],
+        date: &[],
+    };
+    let mut i = start;
+    loop {
+        let rest = &value[i..];
+        let closing: &[u8] = if rest.starts_with(b"{{") {
+            b"}}"
+        } else if rest.starts_with(b"{") && lot.price.is_empty() {
+            b"}"
+        } else if rest.starts_with(b"[") && lot.date.is_empty() {
+            b"]"
+        } else {
+            break;
+        };
+        let Some(len) = rest
+            .windows(closing.len())
+            .position(|window| window == closing)
+            .map(|position| position + closing.len())
+        else {
+            break;
+        };
+        if closing == b"]" {
+            lot.date = &rest[..len];
+        } else if lot.price.is_empty() {
+            lot.price = &rest[..len];
+        } else {
+            break;
+        }
+        i += len;
+        lot.end = i;
+        i += rest[len..]
+            .iter()
+            .take_while(|c| c.is_ascii_whitespace())
+            .count();
+    }
+
+    (lot.end > lot.start).then_some(lot)
+}
+
+/// Split an amount in the parts before and from its decimal mark.
+///
+/// If the decimal mark has not been declared, it's guessed from the amount.
 #[inline(always)]
-fn split_value_in_before_decimals_after_decimals(value: &[u8]) -> (&[u8], &[u8]) {
+fn split_value_in_before_decimals_after_decimals(
+    value: &[u8],
+    decimal_mark: Option<u8>,
+) -> (&[u8], &[u8]) {
+    if let Some(split) = decimal_mark.and_then(|mark| split_at_decimal_mark(value, mark)) {
+        return split;
+    }
+
     // Use memchr2 for faster decimal point search (rightmost position)
     if let Some(pos) = memchr::memrchr2(b'.', b',', value) {
         let after = &value[pos + 1..];
@@ -1891,70 +2793,75 @@ fn split_value_in_before_decimals_after_decimals(value: &[u8]) -> (&[u8], &[u8])
 
 #[cfg(test)]
 mod test {
-    use super::{split_value_in_before_decimals_after_decimals, EntryValueParser};
+    use super::{split_value_in_before_decimals_after_decimals, DecimalMarks, EntryValueParser};
 
     #[test]
     fn test_split_value_in_before_decimals_after_decimals() {
-        let (before, after) = split_value_in_before_decimals_after_decimals("1000.50€".as_bytes());
+        let (before, after) =
+            split_value_in_before_decimals_after_decimals("1000.50€".as_bytes(), None);
         assert_eq!(before, b"1000");
         assert_eq!(after, ".50€".as_bytes());
 
-        let (before, after) = split_value_in_before_decimals_after_decimals(b"2000,75 USD");
+        let (before, after) = split_value_in_before_decimals_after_decimals(b"2000,75 USD", None);
         assert_eq!(before, b"2000");
         assert_eq!(after, b",75 USD");
 
-        let (before, after) = split_value_in_before_decimals_after_decimals(b"3000 JPY");
+        let (before, after) = split_value_in_before_decimals_after_decimals(b"3000 JPY", None);
         assert_eq!(before, b"3000");
         assert_eq!(after, b" JPY");
 
-        let (before, after) = split_value_in_before_decimals_after_decimals(b"4000");
+        let (before, after) = split_value_in_before_decimals_after_decimals(b"4000", None);
         assert_eq!(before, b"4000");
         assert_eq!(after, b"");
 
-        let (before, after) = split_value_in_before_decimals_after_decimals(b"4000.");
+        let (before, after) = split_value_in_before_decimals_after_decimals(b"4000.", None);
         assert_eq!(before, b"4000");
         assert_eq!(after, b".");
 
-        let (before, after) = split_value_in_before_decimals_after_decimals(b"5,000");
+        let (before, after) = split_value_in_before_decimals_after_decimals(b"5,000", None);
         assert_eq!(before, b"5,000");
         assert_eq!(after, b"");
 
-        let (before, after) = split_value_in_before_decimals_after_decimals("$-1".as_bytes());
+        let (before, after) = split_value_in_before_decimals_after_decimals("$-1".as_bytes(), None);
         assert_eq!(before, "$-1".as_bytes());
         assert_eq!(after, b"");
 
-        let (before, after) =
-            split_value_in_before_decimals_after_decimals("$-100000000000,000000000".as_bytes());
+        let (before, after) = split_value_in_before_decimals_after_decimals(
+            "$-100000000000,000000000".as_bytes(),
+            None,
+        );
         assert_eq!(before, "$-100000000000".as_bytes());
         assert_eq!(after, b",000000000");
 
-        let (before, after) = split_value_in_before_decimals_after_decimals("100€".as_bytes());
+        let (before, after) =
+            split_value_in_before_decimals_after_decimals("100€".as_bytes(), None);
         assert_eq!(before, b"100");
         assert_eq!(after, "€".as_bytes());
 
-        let (before, after) = split_value_in_before_decimals_after_decimals(b"0 gold");
+        let (before, after) = split_value_in_before_decimals_after_decimals(b"0 gold", None);
         assert_eq!(before, b"0");
         assert_eq!(after, b" gold");
 
         let (before, after) =
-            split_value_in_before_decimals_after_decimals(b"0 \"Chocolate Frogs\"");
+            split_value_in_before_decimals_after_decimals(b"0 \"Chocolate Frogs\"", None);
         assert_eq!(before, b"0");
         assert_eq!(after, b" \"Chocolate Frogs\"");
 
         let (before, after) =
-            split_value_in_before_decimals_after_decimals("$56424324€".as_bytes());
+            split_value_in_before_decimals_after_decimals("$56424324€".as_bytes(), None);
         assert_eq!(before, "$56424324".as_bytes());
         assert_eq!(after, "€".as_bytes());
 
-        let (before, after) = split_value_in_before_decimals_after_decimals(b"-10 gold");
+        let (before, after) = split_value_in_before_decimals_after_decimals(b"-10 gold", None);
         assert_eq!(before, b"-10");
         assert_eq!(after, b" gold");
 
-        let (before, after) = split_value_in_before_decimals_after_decimals(b"2.0 AAAA");
+        let (before, after) = split_value_in_before_decimals_after_decimals(b"2.0 AAAA", None);
         assert_eq!(before, b"2");
         assert_eq!(after, b".0 AAAA");
 
-        let (before, after) = split_value_in_before_decimals_after_decimals("$1.50".as_bytes());
+        let (before, after) =
+            split_value_in_before_decimals_after_decimals("$1.50".as_bytes(), None);
         assert_eq!(before, "$1".as_bytes());
         assert_eq!(after, b".50");
     }
@@ -1962,51 +2869,112 @@ mod test {
     #[test]
     fn test_entry_value_parser_stock_lot() {
         let mut parser = EntryValueParser::default();
-        let p = parser.parse("0.0 AAAA  =  2.0 AAAA  @   $1.50".as_bytes());
+        let p = parser.parse(
+            "0.0 AAAA  =  2.0 AAAA  @   $1.50".as_bytes(),
+            &DecimalMarks::default(),
+        );
 
-        assert_eq!(p.first_part_before_decimals, "0".as_bytes().into());
-        assert_eq!(p.first_part_after_decimals, ".0 AAAA".as_bytes().into());
-        assert_eq!(p.first_separator, "=".as_bytes().into());
-        assert_eq!(p.second_part_before_decimals, "2".as_bytes().into());
-        assert_eq!(p.second_part_after_decimals, ".0 AAAA".as_bytes().into());
-        assert_eq!(p.second_separator, "@".as_bytes().into());
-        assert_eq!(p.third_part_before_decimals, "$1".as_bytes().into());
-        assert_eq!(p.third_part_after_decimals, ".50".as_bytes().into());
+        assert_eq!(p.amount_before_decimals, "0".as_bytes().into());
+        assert_eq!(p.amount_after_decimals, ".0 AAAA".as_bytes().into());
+        assert_eq!(p.cost_separator, "".as_bytes().into());
+        assert_eq!(p.assertion_operator, "=".as_bytes().into());
+        assert_eq!(p.assertion_before_decimals, "2".as_bytes().into());
+        assert_eq!(p.assertion_after_decimals, ".0 AAAA".as_bytes().into());
+        assert_eq!(p.assertion_cost_separator, "@".as_bytes().into());
+        assert_eq!(p.assertion_cost_before_decimals, "$1".as_bytes().into());
+        assert_eq!(p.assertion_cost_after_decimals, ".50".as_bytes().into());
     }
 
     #[test]
     fn test_entry_value_parser_chocolate_balance() {
         let mut parser = EntryValueParser::default();
-        let p = parser.parse(br#"0 "Chocolate Frogs"  =       3 "Chocolate Frogs""#);
+        let p = parser.parse(
+            br#"0 "Chocolate Frogs"  =       3 "Chocolate Frogs""#,
+            &DecimalMarks::default(),
+        );
 
-        assert_eq!(p.first_part_before_decimals, "0".as_bytes().into());
+        assert_eq!(p.amount_before_decimals, "0".as_bytes().into());
         assert_eq!(
-            p.first_part_after_decimals,
+            p.amount_after_decimals,
             " \"Chocolate Frogs\"".as_bytes().into()
         );
-        assert_eq!(p.first_separator, "=".as_bytes().into());
-        assert_eq!(p.second_part_before_decimals, "3".as_bytes().into());
+        assert_eq!(p.assertion_operator, "=".as_bytes().into());
+        assert_eq!(p.assertion_before_decimals, "3".as_bytes().into());
         assert_eq!(
-            p.second_part_after_decimals,
+            p.assertion_after_decimals,
             " \"Chocolate Frogs\"".as_bytes().into()
         );
-        assert_eq!(p.second_separator, "".as_bytes().into());
-        assert_eq!(p.third_part_before_decimals, "".as_bytes().into());
-        assert_eq!(p.third_part_after_decimals, "".as_bytes().into());
+        assert_eq!(p.assertion_cost_separator, "".as_bytes().into());
+        assert_eq!(p.assertion_cost_before_decimals, "".as_bytes().into());
+        assert_eq!(p.assertion_cost_after_decimals, "".as_bytes().into());
     }
 
     #[test]
     fn test_entry_value_parser_third_part_with_extra_spaces() {
         let mut parser = EntryValueParser::default();
-        let p = parser.parse(b"1 USD  @   $1.50   ");
-
-        assert_eq!(p.first_part_before_decimals, "1".as_bytes().into());
-        assert_eq!(p.first_part_after_decimals, " USD".as_bytes().into());
-        assert_eq!(p.first_separator, "@".as_bytes().into());
-        assert_eq!(p.second_part_before_decimals, "$1".as_bytes().into());
-        assert_eq!(p.second_part_after_decimals, ".50".as_bytes().into());
-        assert_eq!(p.second_separator, "".as_bytes().into());
-        assert_eq!(p.third_part_before_decimals, "".as_bytes().into());
-        assert_eq!(p.third_part_after_decimals, "".as_bytes().into());
+        let p = parser.parse(b"1 USD  @   $1.50   ", &DecimalMarks::default());
+
+        assert_eq!(p.amount_before_decimals, "1".as_bytes().into());
+        assert_eq!(p.amount_after_decimals, " USD".as_bytes().into());
+        assert_eq!(p.cost_separator, "@".as_bytes().into());
+        assert_eq!(p.cost_before_decimals, "$1".as_bytes().into());
+        assert_eq!(p.cost_after_decimals, ".50".as_bytes().into());
+        assert_eq!(p.assertion_operator, "".as_bytes().into());
+        assert_eq!(p.assertion_before_decimals, "".as_bytes().into());
+        assert_eq!(p.assertion_after_decimals, "".as_bytes().into());
+    }
+
+    #[test]
+    fn test_entry_value_parser_cost_and_assertion() {
+        let mut parser = EntryValueParser::default();
+        let p = parser.parse(b"-10 EUR @ $1.10 ==* $1500", &DecimalMarks::default());
+
+        assert_eq!(p.amount_before_decimals, "-10".as_bytes().into());
+        assert_eq!(p.cost_separator, "@".as_bytes().into());
+        assert_eq!(p.cost_before_decimals, "$1".as_bytes().into());
+        assert_eq!(p.cost_after_decimals, ".10".as_bytes().into());
+        assert_eq!(p.assertion_operator, "==*".as_bytes().into());
+        assert_eq!(p.assertion_before_decimals, "$1500".as_bytes().into());
+        assert_eq!(p.assertion_cost_separator, "".as_bytes().into());
+    }
+
+    #[test]
+    fn test_entry_value_parser_lot_annotations() {
+        let mut parser = EntryValueParser::default();
+        let p = parser.parse(
+            b"10 AAPL {$150.00} [2024-01-15] @ $170",
+            &DecimalMarks::default(),
+        );
+
+        assert_eq!(p.amount_before_decimals, "10".as_bytes().into());
+        assert_eq!(p.amount_after_decimals, " AAPL".as_bytes().into());
+        assert_eq!(p.lot_price, "{$150.00}".as_bytes().into());
+        assert_eq!(p.lot_date, "[2024-01-15]".as_bytes().into());
+        assert_eq!(p.cost_separator, "@".as_bytes().into());
+        assert_eq!(p.cost_before_decimals, "$170".as_bytes().into());
+
+        let mut parser = EntryValueParser::default();
+        let p = parser.parse(b"-5 AAPL{{=$1500}} == 0 AAPL", &DecimalMarks::default());
+
+        assert_eq!(p.amount_before_decimals, "-5".as_bytes().into());
+        assert_eq!(p.amount_after_decimals, " AAPL".as_bytes().into());
+        assert_eq!(p.lot_price, "{{=$1500}}".as_bytes().into());
+        assert_eq!(p.lot_date, "".as_bytes().into());
+        assert_eq!(p.cost_separator, "".as_bytes().into());
+        assert_eq!(p.assertion_operator, "==".as_bytes().into());
+        assert_eq!(p.assertion_before_decimals, "0".as_bytes().into());
+    }
+
+    #[test]
+    fn test_entry_value_parser_balance_assignment() {
+        let mut parser = EntryValueParser::default();
+        let p = parser.parse(b"= $1500", &DecimalMarks::default());
+
+        assert_eq!(p.amount_before_decimals, "".as_bytes().into());
+        assert_eq!(p.amount_after_decimals, "".as_bytes().into());
+        assert_eq!(p.cost_separator, "".as_bytes().into());
+        assert_eq!(p.assertion_operator, "=".as_bytes().into());
+        assert_eq!(p.assertion_before_decimals, "$1500".as_bytes().into());
+        assert_eq!(p.assertion_after_decimals, "".as_bytes().into());
     }
 }
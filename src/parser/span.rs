@@ -0,0 +1,63 @@
+/// Position in a journal file content
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Position {
+    /// Byte offset from the start of the content, starting at 0
+    pub byteno: usize,
+    /// Line number, starting at 1
+    pub lineno: usize,
+    /// Column number, starting at 1 and counted in bytes like [`SyntaxError`] columns
+    ///
+    /// [`SyntaxError`]: crate::SyntaxError
+    pub colno: usize,
+}
+
+impl Position {
+    /// Position of the start of a line
+    #[inline]
+    pub(crate) fn line_start(byteno: usize, lineno: usize) -> Self {
+        Self {
+            byteno,
+            lineno,
+            colno: 1,
+        }
+    }
+
+    /// Position of the byte at index `index` of the line starting at `self`
+    #[inline]
+    pub(crate) fn in_line(&self, index: usize) -> Self {
+        Self {
+            byteno: self.byteno + index,
+            lineno: self.lineno,
+            colno: self.colno + index,
+        }
+    }
+}
+
+/// Location of a node in a journal file content
+///
+/// The end position is exclusive and never includes the newline
+/// character of the last line of the node.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    /// Position of the first byte of the node
+    pub start: Position,
+    /// Position just after the last byte of the node
+    pub end: Position,
+}
+
+impl Span {
+    /// Span of the bytes in range `start..end` of the line starting at `line_start`
+    #[inline]
+    pub(crate) fn in_line(line_start: Position, start: usize, end: usize) -> Self {
+        Self {
+            start: line_start.in_line(start),
+            end: line_start.in_line(end),
+        }
+    }
+
+    /// Range of bytes covered by the span
+    #[inline]
+    pub fn byte_range(&self) -> core::ops::Range<usize> {
+        self.start.byteno..self.end.byteno
+    }
+}
@@ -1,4 +1,37 @@
-use crate::parser::{errors::*, *};
+use crate::parser::{errors::*, span::*, *};
+
+/// Span of the columns `colno_start..colno_end` of the line
+/// number `lineno` that starts at byte `line_byteno`.
+fn line_span(lineno: usize, line_byteno: usize, colno_start: usize, colno_end: usize) -> Span {
+    Span {
+        start: Position {
+            byteno: line_byteno + colno_start - 1,
+            lineno,
+            colno: colno_start,
+        },
+        end: Position {
+            byteno: line_byteno + colno_end - 1,
+            lineno,
+            colno: colno_end,
+        },
+    }
+}
+
+/// Span between two `(byteno, lineno, colno)` positions.
+fn span(start: (usize, usize, usize), end: (usize, usize, usize)) -> Span {
+    Span {
+        start: Position {
+            byteno: start.0,
+            lineno: start.1,
+            colno: start.2,
+        },
+        end: Position {
+            byteno: end.0,
+            lineno: end.1,
+            colno: end.2,
+        },
+    }
+}
 
 fn assert_journal(content: &str, expected: Vec<JournalCstNode>) {
     let journal = parse_content(content.as_bytes());
@@ -18,6 +51,7 @@ fn single_line_comment_hash() {
             content: " comment".into(),
             prefix: CommentPrefix::Hash,
             indent: 0,
+            span: line_span(1, 0, 1, 10),
         })],
     );
 }
@@ -30,6 +64,7 @@ fn single_line_comment_semicolon() {
             content: " comment".into(),
             prefix: CommentPrefix::Semicolon,
             indent: 0,
+            span: line_span(1, 0, 1, 10),
         })],
     );
 }
@@ -42,6 +77,7 @@ fn single_line_comment_unicode() {
             content: " こんにちは世界".into(),
             prefix: CommentPrefix::Hash,
             indent: 0,
+            span: line_span(1, 0, 1, 24),
         })],
     );
 }
@@ -54,6 +90,7 @@ fn single_line_comment_indented() {
             content: " comment".into(),
             prefix: CommentPrefix::Hash,
             indent: 2,
+            span: line_span(1, 0, 1, 12),
         })],
     );
 
@@ -63,6 +100,7 @@ fn single_line_comment_indented() {
             content: " comment # foo ; bar".into(),
             prefix: CommentPrefix::Semicolon,
             indent: 4,
+            span: line_span(1, 0, 1, 26),
         })],
     );
 }
@@ -76,6 +114,7 @@ fn single_line_comment_tab_indented_hash() {
             prefix: CommentPrefix::Hash,
             // a tab is the second column in the line
             indent: 1,
+            span: line_span(1, 0, 1, 11),
         })],
     );
 }
@@ -88,6 +127,7 @@ fn single_line_comment_tab_indented_semicolon() {
             content: " comment # foo ; bar".into(),
             prefix: CommentPrefix::Semicolon,
             indent: 2,
+            span: line_span(1, 0, 1, 24),
         })],
     );
 }
@@ -98,6 +138,7 @@ fn multiline_comment() {
         "comment\ncontent\nend comment",
         vec![JournalCstNode::MultilineComment {
             content: "content\n".into(),
+            span: span((0, 1, 1), (27, 3, 12)),
         }],
     );
 }
@@ -109,6 +150,7 @@ fn multiline_comment_not_ended() {
         "comment\ncontent",
         vec![JournalCstNode::MultilineComment {
             content: "content".into(),
+            span: span((0, 1, 1), (15, 2, 8)),
         }],
     );
 }
@@ -149,12 +191,15 @@ fn directive_with_tabbed_comment() {
                 content: "bank".into(),
                 comment: Some(InlineComment {
                     prefix: CommentPrefix::Semicolon,
+                    span: line_span(1, 0, 14, 23),
                     content: " comment".into(),
                 }),
+                span: line_span(1, 0, 1, 23),
                 name_chars_count: 7,
                 content_chars_count: 4,
             })],
             max_name_content_len: 11,
+            span: line_span(1, 0, 1, 23),
         }],
     );
 }
@@ -171,6 +216,7 @@ fn directives_with_tabbed_delimiters() {
                     comment: None,
                     name_chars_count: 7,
                     content_chars_count: 11,
+                    span: line_span(1, 0, 1, 20),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "commodity".into(),
@@ -178,9 +224,11 @@ fn directives_with_tabbed_delimiters() {
                     comment: None,
                     name_chars_count: 9,
                     content_chars_count: 1,
+                    span: line_span(2, 20, 1, 12),
                 }),
             ],
             max_name_content_len: 18,
+            span: span((0, 1, 1), (31, 2, 12)),
         }],
     );
 }
@@ -196,23 +244,28 @@ fn directives_with_multiple_tabbed_comments() {
                     content: "foo".into(),
                     comment: Some(InlineComment {
                         prefix: CommentPrefix::Semicolon,
+                        span: line_span(1, 0, 14, 27),
                         content: " foo comment".into(),
                     }),
                     name_chars_count: 7,
                     content_chars_count: 3,
+                    span: line_span(1, 0, 1, 27),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "account".into(),
                     content: "bar".into(),
                     comment: Some(InlineComment {
                         prefix: CommentPrefix::Semicolon,
+                        span: line_span(2, 27, 15, 28),
                         content: " bar comment".into(),
                     }),
                     name_chars_count: 7,
                     content_chars_count: 3,
+                    span: line_span(2, 27, 1, 28),
                 }),
             ],
             max_name_content_len: 10,
+            span: span((0, 1, 1), (54, 2, 28)),
         }],
     );
 }
@@ -226,10 +279,12 @@ fn account_directive() {
                 name: "account".into(),
                 content: "Assets:Bank:Checking".into(),
                 comment: None,
+                span: line_span(1, 0, 1, 29),
                 name_chars_count: 7,
                 content_chars_count: 20,
             })],
             max_name_content_len: 27, // "account" (7) + "Assets:Bank:Checking" (20),
+            span: line_span(1, 0, 1, 29),
         }],
     );
 }
@@ -243,10 +298,12 @@ fn account_directive_with_whitespace() {
                 name: "account".into(),
                 content: "Assets Bank:Checking".into(),
                 comment: None,
+                span: line_span(1, 0, 1, 29),
                 name_chars_count: 7,
                 content_chars_count: 20,
             })],
             max_name_content_len: 27,
+            span: line_span(1, 0, 1, 29),
         }],
     );
 }
@@ -261,12 +318,15 @@ fn account_directive_with_comment() {
                 content: "Assets:Bank:Checking".into(),
                 comment: Some(InlineComment {
                     prefix: CommentPrefix::Semicolon,
+                    span: line_span(1, 0, 31, 40),
                     content: " comment".into(),
                 }),
+                span: line_span(1, 0, 1, 40),
                 name_chars_count: 7,
                 content_chars_count: 20,
             })],
             max_name_content_len: 27,
+            span: line_span(1, 0, 1, 40),
         }],
     );
 }
@@ -280,10 +340,12 @@ fn commodity_directive() {
                 name: "commodity".into(),
                 content: "$".into(),
                 comment: None,
+                span: line_span(1, 0, 1, 12),
                 name_chars_count: 9,
                 content_chars_count: 1,
             })],
             max_name_content_len: 10,
+            span: line_span(1, 0, 1, 12),
         }],
     );
 }
@@ -297,10 +359,12 @@ fn decimal_mark_directive() {
                 name: "decimal-mark".into(),
                 content: ",".into(),
                 comment: None,
+                span: line_span(1, 0, 1, 15),
                 name_chars_count: 12,
                 content_chars_count: 1,
             })],
             max_name_content_len: 13,
+            span: line_span(1, 0, 1, 15),
         }],
     );
 }
@@ -314,10 +378,12 @@ fn payee_directive() {
                 name: "payee".into(),
                 content: "Foo Bar".into(),
                 comment: None,
+                span: line_span(1, 0, 1, 14),
                 name_chars_count: 5,
                 content_chars_count: 7,
             })],
             max_name_content_len: 12,
+            span: line_span(1, 0, 1, 14),
         }],
     );
 }
@@ -331,10 +397,12 @@ fn tag_directive() {
                 name: "tag".into(),
                 content: "foo bar".into(),
                 comment: None,
+                span: line_span(1, 0, 1, 12),
                 name_chars_count: 3,
                 content_chars_count: 7,
             })],
             max_name_content_len: 10,
+            span: line_span(1, 0, 1, 12),
         }],
     );
 }
@@ -348,10 +416,12 @@ fn include_directive() {
                 name: "include".into(),
                 content: "/path/to/file".into(),
                 comment: None,
+                span: line_span(1, 0, 1, 22),
                 name_chars_count: 7,
                 content_chars_count: 13,
             })],
             max_name_content_len: 20,
+            span: line_span(1, 0, 1, 22),
         }],
     );
 }
@@ -365,10 +435,12 @@ fn p_directive() {
                 name: "P".into(),
                 content: "foobarbaz".into(),
                 comment: None,
+                span: line_span(1, 0, 1, 12),
                 name_chars_count: 1,
                 content_chars_count: 9,
             })],
             max_name_content_len: 10,
+            span: line_span(1, 0, 1, 12),
         }],
     );
 }
@@ -385,6 +457,7 @@ fn directives_group() {
                     comment: None,
                     name_chars_count: 7,
                     content_chars_count: 20,
+                    span: line_span(1, 0, 1, 29),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "commodity".into(),
@@ -392,6 +465,7 @@ fn directives_group() {
                     comment: None,
                     name_chars_count: 9,
                     content_chars_count: 1,
+                    span: line_span(2, 29, 1, 12),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "decimal-mark".into(),
@@ -399,9 +473,11 @@ fn directives_group() {
                     comment: None,
                     name_chars_count: 12,
                     content_chars_count: 1,
+                    span: line_span(3, 41, 1, 15),
                 }),
             ],
             max_name_content_len: 27,
+            span: span((0, 1, 1), (55, 3, 15)),
         }],
     );
 }
@@ -418,29 +494,35 @@ fn directives_group_with_comments() {
                     comment: None,
                     name_chars_count: 7,
                     content_chars_count: 7,
+                    span: line_span(1, 0, 1, 16),
                 }),
                 DirectiveNode::SingleLineComment(IndentedComment {
                     content: " comment".into(),
                     prefix: CommentPrefix::Semicolon,
                     indent: 0,
+                    span: line_span(2, 16, 1, 10),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "commodity".into(),
                     content: "$100.00".into(),
                     comment: Some(InlineComment {
                         prefix: CommentPrefix::Semicolon,
+                        span: line_span(3, 26, 20, 29),
                         content: " comment".into(),
                     }),
                     name_chars_count: 9,
                     content_chars_count: 7,
+                    span: line_span(3, 26, 1, 29),
                 }),
                 DirectiveNode::SingleLineComment(IndentedComment {
                     content: " other comment".into(),
                     prefix: CommentPrefix::Hash,
                     indent: 2,
+                    span: line_span(4, 55, 1, 18),
                 }),
             ],
             max_name_content_len: 16,
+            span: span((0, 1, 1), (72, 4, 18)),
         }],
     )
 }
@@ -455,12 +537,15 @@ fn directive_with_unicode_content_and_comment() {
                 content: "资产:现金".into(),
                 comment: Some(InlineComment {
                     prefix: CommentPrefix::Semicolon,
+                    span: line_span(1, 0, 24, 35),
                     content: " 注释✨".into(),
                 }),
+                span: line_span(1, 0, 1, 35),
                 name_chars_count: 7,
                 content_chars_count: 5,
             })],
             max_name_content_len: 12,
+            span: line_span(1, 0, 1, 35),
         }],
     );
 }
@@ -477,10 +562,15 @@ fn subdirective() {
                     comment: None,
                     name_chars_count: 7,
                     content_chars_count: 20,
+                    span: line_span(1, 0, 1, 29),
                 }),
-                DirectiveNode::Subdirective("subdirective foo bar".into()),
+                DirectiveNode::Subdirective {
+                    content: "subdirective foo bar".into(),
+                    span: line_span(2, 29, 1, 23),
+                },
             ],
             max_name_content_len: 27,
+            span: span((0, 1, 1), (51, 2, 23)),
         }],
     );
 }
@@ -523,6 +613,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 7,
                     content_chars_count: 20,
+                    span: line_span(1, 0, 1, 29),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "commodity".into(),
@@ -530,6 +621,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 9,
                     content_chars_count: 1,
+                    span: line_span(2, 29, 1, 12),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "decimal-mark".into(),
@@ -537,6 +629,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 12,
                     content_chars_count: 1,
+                    span: line_span(3, 41, 1, 15),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "payee".into(),
@@ -544,6 +637,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 5,
                     content_chars_count: 7,
+                    span: line_span(4, 56, 1, 14),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "tag".into(),
@@ -551,6 +645,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 3,
                     content_chars_count: 7,
+                    span: line_span(5, 70, 1, 12),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "include".into(),
@@ -558,6 +653,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 7,
                     content_chars_count: 13,
+                    span: line_span(6, 82, 1, 22),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "P".into(),
@@ -565,6 +661,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 1,
                     content_chars_count: 9,
+                    span: line_span(7, 104, 1, 12),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "apply account".into(),
@@ -572,6 +669,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 13,
                     content_chars_count: 13,
+                    span: line_span(8, 116, 1, 28),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "D".into(),
@@ -579,6 +677,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 1,
                     content_chars_count: 10,
+                    span: line_span(9, 144, 1, 13),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "Y".into(),
@@ -586,6 +685,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 1,
                     content_chars_count: 4,
+                    span: line_span(10, 157, 1, 7),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "apply fixed".into(),
@@ -593,6 +693,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 11,
                     content_chars_count: 0,
+                    span: line_span(11, 164, 1, 12),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "apply tag".into(),
@@ -600,6 +701,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 9,
                     content_chars_count: 9,
+                    span: line_span(12, 176, 1, 20),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "assert".into(),
@@ -607,6 +709,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 6,
                     content_chars_count: 26,
+                    span: line_span(13, 196, 1, 34),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "capture".into(),
@@ -614,6 +717,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 7,
                     content_chars_count: 16,
+                    span: line_span(14, 230, 1, 25),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "check".into(),
@@ -621,6 +725,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 5,
                     content_chars_count: 20,
+                    span: line_span(15, 255, 1, 27),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "define".into(),
@@ -628,6 +733,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 6,
                     content_chars_count: 12,
+                    span: line_span(16, 282, 1, 20),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "bucket / A".into(),
@@ -635,6 +741,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 10,
                     content_chars_count: 13,
+                    span: line_span(17, 302, 1, 25),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "end apply fixed".into(),
@@ -642,6 +749,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 15,
                     content_chars_count: 0,
+                    span: line_span(18, 327, 1, 16),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "end apply tag".into(),
@@ -649,6 +757,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 13,
                     content_chars_count: 0,
+                    span: line_span(19, 343, 1, 14),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "end apply year".into(),
@@ -656,6 +765,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 14,
                     content_chars_count: 0,
+                    span: line_span(20, 357, 1, 15),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "end tag".into(),
@@ -663,6 +773,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 7,
                     content_chars_count: 0,
+                    span: line_span(21, 372, 1, 8),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "eval".into(),
@@ -670,6 +781,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 4,
                     content_chars_count: 6,
+                    span: line_span(22, 380, 1, 12),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "expr".into(),
@@ -677,6 +789,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 4,
                     content_chars_count: 6,
+                    span: line_span(23, 392, 1, 12),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "python".into(),
@@ -684,6 +797,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 6,
                     content_chars_count: 22,
+                    span: line_span(24, 404, 1, 30),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "value".into(),
@@ -691,6 +805,7 @@ value $100.00
                     comment: None,
                     name_chars_count: 5,
                     content_chars_count: 7,
+                    span: line_span(25, 434, 1, 14),
                 }),
                 DirectiveNode::Directive(Directive {
                     name: "--command-line-flags".into(),
@@ -698,9 +813,11 @@ value $100.00
                     comment: None,
                     name_chars_count: 20,
                     content_chars_count: 6,
+                    span: line_span(26, 448, 1, 28),
                 }),
             ],
             max_name_content_len: 32,
+            span: span((0, 1, 1), (475, 26, 28)),
         }],
     )
 }
@@ -769,4 +886,443 @@ fn regression_tab_indented_entries() {
     let content = "2015-10-16 test\n\tassets:cash  $10\n\texpenses:food\n";
     let result = parse_content(content.as_bytes());
     assert!(result.is_ok());
+
+    // the tab is the indentation, not part of the entry name
+    let JournalCstNode::Transaction { entries, .. } = &result.unwrap()[0] else {
+        panic!("expected a transaction");
+    };
+    let TransactionNode::TransactionEntry(entry) = &entries[0] else {
+        panic!("expected a transaction entry");
+    };
+    assert_eq!(entry.name.as_bytes(), b"assets:cash");
+}
+
+#[test]
+fn transaction_and_directive_without_empty_line() {
+    let content = "account assets\n2024-01-01 rent\n  expenses:rent  $500\n  assets\ncommodity $\n";
+    let journal = parse_content(content.as_bytes()).unwrap();
+    assert!(matches!(
+        journal.as_slice(),
+        [
+            JournalCstNode::DirectivesGroup { .. },
+            JournalCstNode::Transaction { entries, .. },
+            JournalCstNode::DirectivesGroup { .. },
+        ] if entries.len() == 2
+    ));
+}
+
+#[test]
+fn posting_kinds() {
+    let content = "2024-01-01 budget\n    expenses:food  $10\n    (budget:food)  $-10\n    * [assets:envelope]  $5\n    (assets) cash\n";
+    let journal = parse_content(content.as_bytes()).unwrap();
+    let JournalCstNode::Transaction { entries, .. } = &journal[0] else {
+        panic!("expected a transaction");
+    };
+    let kinds = entries
+        .iter()
+        .map(|entry| match entry {
+            TransactionNode::TransactionEntry(entry) => entry.kind,
+            TransactionNode::SingleLineComment(_) => panic!("expected a transaction entry"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            PostingKind::Real,
+            PostingKind::Virtual,
+            PostingKind::BalancedVirtual,
+            PostingKind::Real,
+        ]
+    );
+}
+
+#[test]
+fn transaction_spans() {
+    let content = "2015-10-16 food\n  expenses:food  $10\n  assets:cash\n2015-10-17 rent\n  expenses:rent  $500\n\n; end\n";
+    let journal = parse_content(content.as_bytes()).unwrap();
+    let spans = journal.iter().map(|node| node.span()).collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        vec![
+            span((0, 1, 1), (50, 3, 14)),
+            // inserted between adjacent transactions
+            span((51, 4, 1), (51, 4, 1)),
+            span((51, 4, 1), (88, 5, 22)),
+            line_span(6, 89, 1, 1),
+            line_span(7, 90, 1, 6),
+        ]
+    );
+
+    let JournalCstNode::Transaction { entries, .. } = &journal[0] else {
+        panic!("expected a transaction");
+    };
+    let spans = entries.iter().map(|node| node.span()).collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        vec![line_span(2, 16, 1, 21), line_span(3, 37, 1, 14)]
+    );
+    assert_eq!(&content[spans[0].byte_range()], "  expenses:food  $10");
+}
+
+#[test]
+fn transaction_with_comments_spans() {
+    let content = "2015-10-16 food  ; title\n  ; note\n  expenses:food  $10  ; entry\n";
+    let journal = parse_content(content.as_bytes()).unwrap();
+    let JournalCstNode::Transaction {
+        title_comment,
+        entries,
+        span: transaction_span,
+        ..
+    } = &journal[0]
+    else {
+        panic!("expected a transaction");
+    };
+    assert_eq!(*transaction_span, span((0, 1, 1), (63, 3, 30)));
+    assert_eq!(
+        title_comment.as_ref().unwrap().span,
+        line_span(1, 0, 18, 25)
+    );
+    assert_eq!(entries[0].span(), line_span(2, 25, 1, 9));
+    let TransactionNode::TransactionEntry(entry) = &entries[1] else {
+        panic!("expected a transaction entry");
+    };
+    assert_eq!(entry.span, line_span(3, 34, 1, 30));
+    assert_eq!(
+        entry.comment.as_ref().unwrap().span,
+        line_span(3, 34, 23, 30)
+    );
+}
+
+/// Parse the header of the first transaction of `content`
+fn parse_header(content: &str) -> Option<Box<TransactionHeader<'_>>> {
+    let mut journal = parse_content(content.as_bytes()).unwrap();
+    let node = match journal.remove(0) {
+        JournalCstNode::PeriodicTransaction { transaction, .. }
+        | JournalCstNode::AutoPostingRule { transaction, .. } => *transaction,
+        node => node,
+    };
+    let JournalCstNode::Transaction { header, .. } = node else {
+        panic!("expected a transaction");
+    };
+    header
+}
+
+#[test]
+fn transaction_header_fields() {
+    assert_eq!(
+        parse_header(
+            "2024-01-02=2024-01-05 * (123) Gringott's Bank | withdrawal  ; foo
+"
+        ),
+        Some(Box::new(TransactionHeader {
+            date: "2024-01-02".into(),
+            secondary_date: Some("2024-01-05".into()),
+            status: Some(TransactionStatus::Cleared),
+            code: Some("123".into()),
+            description: "Gringott's Bank | withdrawal".into(),
+            payee: "Gringott's Bank".into(),
+            note: Some("withdrawal".into()),
+        }))
+    );
+    assert_eq!(
+        parse_header(
+            "2024/1/2!food
+"
+        ),
+        Some(Box::new(TransactionHeader {
+            date: "2024/1/2".into(),
+            secondary_date: None,
+            status: Some(TransactionStatus::Pending),
+            code: None,
+            description: "food".into(),
+            payee: "food".into(),
+            note: None,
+        }))
+    );
+    assert_eq!(
+        parse_header(
+            "2024-01-02   
+"
+        ),
+        Some(Box::new(TransactionHeader {
+            date: "2024-01-02".into(),
+            secondary_date: None,
+            status: None,
+            code: None,
+            description: "".into(),
+            payee: "".into(),
+            note: None,
+        }))
+    );
+}
+
+#[test]
+fn transaction_header_without_date() {
+    assert_eq!(
+        parse_header(
+            "~ monthly
+"
+        ),
+        None
+    );
+    assert_eq!(
+        parse_header(
+            "= expenses:food
+"
+        ),
+        None
+    );
+    assert_eq!(
+        parse_header(
+            "2024-01-02x food
+"
+        ),
+        None
+    );
+}
+
+#[test]
+fn periodic_transaction_titles() {
+    let journal = parse_content(
+        b"~  monthly   from 2024/1  ; budget
+    (budget:food)  $-500
+
+~\tweekly\tgroceries   run
+    expenses:food  $50
+
+~ yearly
+    expenses:tax  $100
+",
+    )
+    .unwrap();
+    let titles = journal
+        .iter()
+        .filter_map(|node| match node {
+            JournalCstNode::PeriodicTransaction {
+                period,
+                description,
+                ..
+            } => Some((
+                period.as_bytes(),
+                description.as_ref().map(ByteStr::as_bytes),
+            )),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        titles,
+        [
+            (&b"monthly"[..], Some(&b"from 2024/1"[..])),
+            (b"weekly", Some(b"groceries   run")),
+            (b"yearly", None),
+        ]
+    );
+    let JournalCstNode::Transaction {
+        title,
+        title_comment,
+        ..
+    } = journal[0].inner()
+    else {
+        panic!("expected a transaction");
+    };
+    assert_eq!(title.as_bytes(), b"~  monthly   from 2024/1");
+    assert!(title_comment.is_some());
+    assert_eq!(journal[0].span(), span((0, 1, 1), (59, 2, 25)));
+}
+
+#[test]
+fn auto_posting_rule_query() {
+    let journal = parse_content(
+        b"=  expenses:food    desc:\"whole  foods\"  ; rule
+    (budget:food)  *-1
+",
+    )
+    .unwrap();
+    let JournalCstNode::AutoPostingRule { query, transaction } = &journal[0] else {
+        panic!("expected an auto posting rule");
+    };
+    assert_eq!(query.as_bytes(), b"expenses:food    desc:\"whole  foods\"");
+    let JournalCstNode::Transaction { entries, .. } = transaction.as_ref() else {
+        panic!("expected a transaction");
+    };
+    let TransactionNode::TransactionEntry(entry) = &entries[0] else {
+        panic!("expected a transaction entry");
+    };
+    assert_eq!(entry.value_first_part_before_decimals.as_bytes(), b"*-1");
+}
+
+#[test]
+fn multiline_comment_with_empty_lines() {
+    assert_journal(
+        "comment\nfoo\n\nbar\nend comment\n",
+        vec![JournalCstNode::MultilineComment {
+            content: "foo\n\nbar\n".into(),
+            span: span((0, 1, 1), (28, 5, 12)),
+        }],
+    );
+}
+
+#[test]
+fn syntax_error_display() {
+    let error = parse_content(b"  foo").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "1:3: Unexpected character 'f', expected '#', ';' or newline"
+    );
+}
+
+#[test]
+fn syntax_error_display_with_context() {
+    let content = b"  foo\r\n";
+    let error = parse_content(content).unwrap_err();
+    assert_eq!(
+        error.display_with_context(content, "[STDIN]").to_string(),
+        "hledger-fmt error: [STDIN]:1:3:
+1 |   foo
+  |   ^
+Unexpected character 'f'
+Expected '#', ';' or newline"
+    );
+}
+
+#[test]
+fn syntax_error_display_with_context_out_of_content() {
+    let error = SyntaxError {
+        lineno: 10,
+        colno_start: 2,
+        colno_end: 4,
+        message: "Unexpected end of file".to_string(),
+        expected: "something",
+    };
+    assert_eq!(
+        error
+            .display_with_context(b"foo\n", "main.journal")
+            .to_string(),
+        "hledger-fmt error: main.journal:10:2:
+   |\x20
+10 |\x20
+   |  ^^
+Unexpected end of file
+Expected something"
+    );
+}
+
+#[test]
+fn recover_from_syntax_errors() {
+    let content = "  foo\n2015-10-16 food\n  expenses:food  $10\n\n  bar baz\n; comment\n";
+    let (journal, errors) = parse_content_recovering(content.as_bytes());
+    assert_eq!(
+        errors,
+        vec![
+            SyntaxError {
+                lineno: 1,
+                colno_start: 3,
+                colno_end: 4,
+                message: "Unexpected character 'f'".to_string(),
+                expected: "'#', ';' or newline",
+            },
+            SyntaxError {
+                lineno: 5,
+                colno_start: 3,
+                colno_end: 4,
+                message: "Unexpected character 'b'".to_string(),
+                expected: "'#', ';' or newline",
+            },
+        ]
+    );
+    let spans = journal.iter().map(|node| node.span()).collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        vec![
+            span((6, 2, 1), (42, 3, 21)),
+            line_span(4, 43, 1, 1),
+            line_span(6, 54, 1, 10),
+        ]
+    );
+
+    // without recovery, only the first error is returned
+    assert_journal_err(
+        content,
+        SyntaxError {
+            lineno: 1,
+            colno_start: 3,
+            colno_end: 4,
+            message: "Unexpected character 'f'".to_string(),
+            expected: "'#', ';' or newline",
+        },
+    );
+}
+
+#[test]
+fn recover_without_syntax_errors() {
+    let content = "account foo\n\n2015-10-16 food\n  expenses:food  $10\n";
+    let (journal, errors) = parse_content_recovering(content.as_bytes());
+    assert!(errors.is_empty());
+    assert_eq!(journal, parse_content(content.as_bytes()).unwrap());
+}
+
+#[test]
+fn formatter_off_on_comments() {
+    let content = "; hledger-fmt: off\naccount  foo\n\n2015-10-16 food\n;hledger-fmt:on\n# foo";
+    assert_journal(
+        content,
+        vec![
+            JournalCstNode::Verbatim {
+                content: "; hledger-fmt: off\naccount  foo\n\n2015-10-16 food\n;hledger-fmt:on\n"
+                    .into(),
+                span: span((0, 1, 1), (64, 5, 16)),
+            },
+            JournalCstNode::SingleLineComment(IndentedComment {
+                content: " foo".into(),
+                prefix: CommentPrefix::Hash,
+                indent: 0,
+                span: line_span(6, 65, 1, 6),
+            }),
+        ],
+    );
+}
+
+#[test]
+fn formatter_off_without_on() {
+    let content = "# hledger-fmt: off\n  foo\n";
+    assert_journal(
+        content,
+        vec![JournalCstNode::Verbatim {
+            content: content.into(),
+            span: span((0, 1, 1), (24, 2, 6)),
+        }],
+    );
+}
+
+#[test]
+fn formatter_skip_comment() {
+    let content =
+        "; hledger-fmt: skip\n\n2015-10-16 food\n  expenses:food     $10\n; comment\n\n; other\n";
+    let journal = parse_content(content.as_bytes()).unwrap();
+    assert!(matches!(journal[0], JournalCstNode::SingleLineComment(_)));
+    assert_eq!(journal[1].span(), line_span(2, 20, 1, 1));
+    assert_eq!(
+        journal[2],
+        JournalCstNode::Verbatim {
+            content: "2015-10-16 food\n  expenses:food     $10\n; comment\n".into(),
+            span: span((21, 3, 1), (70, 5, 10)),
+        }
+    );
+    assert_eq!(journal[3].span(), line_span(6, 71, 1, 1));
+    assert_eq!(journal[4].span(), line_span(7, 72, 1, 8));
+    assert_eq!(journal.len(), 5);
+}
+
+#[test]
+fn formatter_skip_comment_before_directive() {
+    let content = "; hledger-fmt: skip\naccount assets\n2015-10-16 food\n  expenses:food     $10\n";
+    let journal = parse_content(content.as_bytes()).unwrap();
+    assert!(matches!(
+        journal.as_slice(),
+        [
+            JournalCstNode::SingleLineComment(_),
+            JournalCstNode::DirectivesGroup { .. },
+            JournalCstNode::Verbatim { content, .. },
+        ] if content.as_bytes() == b"2015-10-16 food\n  expenses:food     $10\n"
+    ));
 }
@@ -0,0 +1,399 @@
+//! Parser of timeclock files, made of clock-in and clock-out lines like
+//!
+//! ```text
+//! i 2024-01-01 09:00:00 client:acme  meeting  ; billable
+//! o 2024-01-01 10:30:00
+//! ```
+
+use crate::byte_str::ByteStr;
+use crate::date::split_date;
+use crate::{format, Vec};
+
+use super::errors::SyntaxError;
+use super::span::{Position, Span};
+use super::{
+    parse_inline_comment, split_at_double_space, CommentPrefix, IndentedComment, InlineComment,
+};
+
+/// A timeclock file
+pub type TimeclockFile<'a> = Vec<TimeclockNode<'a>>;
+
+/// Each line of a timeclock file
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum TimeclockNode<'a> {
+    /// An empty line
+    #[non_exhaustive]
+    EmptyLine {
+        /// Location of the line
+        span: Span,
+    },
+    /// A comment line
+    Comment(IndentedComment<'a>),
+    /// A clock-in, clock-out or other timeclock entry
+    Entry(TimeclockEntry<'a>),
+}
+
+impl TimeclockNode<'_> {
+    /// Location of the node in the timeclock file content
+    #[inline]
+    pub fn span(&self) -> Span {
+        match self {
+            TimeclockNode::EmptyLine { span } => *span,
+            TimeclockNode::Comment(comment) => comment.span,
+            TimeclockNode::Entry(entry) => entry.span,
+        }
+    }
+}
+
+/// Code at the start of a timeclock entry
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeclockCode {
+    /// 'i', clock in
+    In = b'i',
+    /// 'o', clock out
+    Out = b'o',
+    /// 'O', clock out and mark the session as cleared
+    FinalOut = b'O',
+    /// 'h', set the required hours
+    SetRequiredHours = b'h',
+    /// 'b', set the balance
+    SetBalance = b'b',
+}
+
+impl TimeclockCode {
+    #[inline]
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            b'i' => Some(Self::In),
+            b'o' => Some(Self::Out),
+            b'O' => Some(Self::FinalOut),
+            b'h' => Some(Self::SetRequiredHours),
+            b'b' => Some(Self::SetBalance),
+            _ => None,
+        }
+    }
+}
+
+/// A timeclock entry
+///
+/// The syntax is:
+///
+/// ```text
+/// <code> <date> <time> [<time zone>] <account>  <description>  ; comment
+/// ```
+///
+/// The account, the description and the comment are optional.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub struct TimeclockEntry<'a> {
+    /// Entry code
+    pub code: TimeclockCode,
+    /// Date of the timestamp
+    pub date: ByteStr<'a>,
+    /// Time of the timestamp
+    pub time: ByteStr<'a>,
+    /// Time zone of the timestamp, like `+0100`
+    pub time_zone: Option<ByteStr<'a>>,
+    /// Account, empty if the entry has none
+    pub account: ByteStr<'a>,
+    /// Description, separated from the account by two or more spaces or a tab
+    pub description: Option<ByteStr<'a>>,
+    /// Comment of the entry
+    pub comment: Option<InlineComment<'a>>,
+    /// Location of the entry line
+    pub span: Span,
+}
+
+/// Parse a timeclock file content.
+///
+/// Returns the first syntax error found, if any.
+pub fn parse_timeclock_content(bytes: &[u8]) -> Result<TimeclockFile<'_>, SyntaxError> {
+    let mut errors = Vec::new();
+    let nodes = parse(bytes, &mut errors, false);
+    match errors.pop() {
+        Some(error) => Err(error),
+        None => Ok(nodes),
+    }
+}
+
+/// Parse a timeclock file content, skipping the lines with syntax errors.
+///
+/// Returns the nodes that could be parsed along with all the errors found.
+pub fn parse_timeclock_content_recovering(bytes: &[u8]) -> (TimeclockFile<'_>, Vec<SyntaxError>) {
+    let mut errors = Vec::new();
+    let nodes = parse(bytes, &mut errors, true);
+    (nodes, errors)
+}
+
+/// Parse a timeclock file content, pushing syntax errors to `errors`.
+///
+/// If `recover` is `false`, stops at the first error.
+fn parse<'a>(bytes: &'a [u8], errors: &mut Vec<SyntaxError>, recover: bool) -> TimeclockFile<'a> {
+    let mut nodes = Vec::with_capacity(16);
+    let mut lineno = 1;
+    let mut byteno = 0;
+    while byteno < bytes.len() {
+        let line_start = Position::line_start(byteno, lineno);
+        let (line_end_including_newline, mut line_end) =
+            match memchr::memchr(b'\n', &bytes[byteno..]) {
+                Some(pos) => (byteno + pos + 1, byteno + pos),
+                None => (bytes.len(), bytes.len()),
+            };
+        if line_end > byteno && bytes[line_end - 1] == b'\r' {
+            line_end -= 1; // CRLF
+        }
+
+        match parse_line(&bytes[byteno..line_end], line_start) {
+            Ok(node) => nodes.push(node),
+            Err(error) => {
+                errors.push(error);
+                if !recover {
+                    break;
+                }
+            }
+        }
+        byteno = line_end_including_newline;
+        lineno += 1;
+    }
+    nodes
+}
+
+fn parse_line(line: &[u8], line_start: Position) -> Result<TimeclockNode<'_>, SyntaxError> {
+    let mut indent = 0;
+    let mut start = 0;
+    while start < line.len() && line[start].is_ascii_whitespace() {
+        indent += if line[start] == b'\t' { 4 } else { 1 };
+        start += 1;
+    }
+    let Some(&first_byte) = line.get(start) else {
+        return Ok(TimeclockNode::EmptyLine {
+            span: Span::in_line(line_start, 0, line.len()),
+        });
+    };
+
+    if first_byte == b';' || first_byte == b'#' {
+        return Ok(TimeclockNode::Comment(IndentedComment {
+            content: ByteStr::from(&line[start + 1..]),
+            indent,
+            prefix: CommentPrefix::from_byte(first_byte),
+            span: Span::in_line(line_start, 0, line.len()),
+        }));
+    }
+
+    let code = match TimeclockCode::from_byte(first_byte) {
+        Some(code) if start == 0 => code,
+        _ => {
+            return Err(unexpected_character(
+                line,
+                start,
+                line_start,
+                if start == 0 {
+                    "timeclock code ('i', 'o', 'O', 'h' or 'b'), '#' or ';'"
+                } else {
+                    "'#', ';' or newline"
+                },
+            ))
+        }
+    };
+    if line.get(1).is_some_and(|b| !b.is_ascii_whitespace()) {
+        return Err(unexpected_character(line, 1, line_start, "space"));
+    }
+
+    // the comment starts at the first ';', descriptions can contain '#'
+    let content_end = memchr::memchr(b';', line).unwrap_or(line.len());
+    let comment = if content_end < line.len() {
+        parse_inline_comment(
+            line,
+            line.len(),
+            content_end + 1,
+            Some(CommentPrefix::Semicolon),
+            line_start,
+        )
+    } else {
+        None
+    };
+
+    let (date, rest) = next_word(line, 1, content_end);
+    if date.is_empty() {
+        return Err(missing(line, content_end, line_start, "date"));
+    }
+    if !split_date(date).is_some_and(|(_, rest)| rest.is_empty()) {
+        return Err(SyntaxError {
+            message: format!("Invalid date {:?}", crate::String::from_utf8_lossy(date)),
+            lineno: line_start.lineno,
+            colno_start: rest - date.len() + 1,
+            colno_end: rest + 1,
+            expected: "date",
+        });
+    }
+    let (time, mut rest) = next_word(line, rest, content_end);
+    if time.is_empty() {
+        return Err(missing(line, content_end, line_start, "time"));
+    }
+    let (time_zone, after_time_zone) = next_word(line, rest, content_end);
+    let time_zone = if time_zone.len() == 5
+        && matches!(time_zone[0], b'+' | b'-')
+        && time_zone[1..].iter().all(u8::is_ascii_digit)
+    {
+        rest = after_time_zone;
+        Some(ByteStr::from(time_zone))
+    } else {
+        None
+    };
+
+    let (account, description) = split_at_double_space(&line[rest..content_end]);
+    Ok(TimeclockNode::Entry(TimeclockEntry {
+        code,
+        date: ByteStr::from(date),
+        time: ByteStr::from(time),
+        time_zone,
+        account: ByteStr::from(account),
+        description: description.map(ByteStr::from),
+        comment,
+        span: Span::in_line(line_start, 0, line.len()),
+    }))
+}
+
+/// Word of `line` after the whitespace at `start` and index where it ends
+#[inline]
+fn next_word(line: &[u8], start: usize, end: usize) -> (&[u8], usize) {
+    let word_start = line[start..end]
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .map_or(end, |i| start + i);
+    let word_end = line[word_start..end]
+        .iter()
+        .position(u8::is_ascii_whitespace)
+        .map_or(end, |i| word_start + i);
+    (&line[word_start..word_end], word_end)
+}
+
+#[cold]
+fn unexpected_character(
+    line: &[u8],
+    index: usize,
+    line_start: Position,
+    expected: &'static str,
+) -> SyntaxError {
+    let character = crate::String::from_utf8_lossy(&line[index..])
+        .chars()
+        .next()
+        .unwrap_or(char::REPLACEMENT_CHARACTER);
+    let char_end = index + character.len_utf8().min(line.len() - index);
+    SyntaxError {
+        message: format!("Unexpected character {character:?}"),
+        lineno: line_start.lineno,
+        colno_start: index + 1,
+        colno_end: char_end + 1,
+        expected,
+    }
+}
+
+#[cold]
+fn missing(line: &[u8], index: usize, line_start: Position, expected: &'static str) -> SyntaxError {
+    SyntaxError {
+        message: if index == line.len() {
+            "Unexpected end of line".into()
+        } else {
+            "Unexpected comment".into()
+        },
+        lineno: line_start.lineno,
+        colno_start: index + 1,
+        colno_end: index + 2,
+        expected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_entry(line: &str) -> TimeclockEntry<'_> {
+        match parse_timeclock_content(line.as_bytes()) {
+            Ok(mut nodes) => match nodes.remove(0) {
+                TimeclockNode::Entry(entry) => entry,
+                node => panic!("expected an entry, found {node:?}"),
+            },
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    #[test]
+    fn entries() {
+        let entry = parse_entry("i 2024/01/02 09:00:00 client:acme corp   weekly  meeting ; #1");
+        assert_eq!(entry.code, TimeclockCode::In);
+        assert_eq!(entry.date.as_bytes(), b"2024/01/02");
+        assert_eq!(entry.time.as_bytes(), b"09:00:00");
+        assert_eq!(entry.time_zone, None);
+        assert_eq!(entry.account.as_bytes(), b"client:acme corp");
+        assert_eq!(
+            entry.description.as_ref().map(ByteStr::as_bytes),
+            Some(&b"weekly  meeting"[..])
+        );
+        assert_eq!(entry.comment.unwrap().content.as_bytes(), b" #1");
+
+        let entry = parse_entry("O 2024-01-02 10:00 -0500\r\n");
+        assert_eq!(entry.code, TimeclockCode::FinalOut);
+        assert_eq!(entry.time.as_bytes(), b"10:00");
+        assert_eq!(
+            entry.time_zone.as_ref().map(ByteStr::as_bytes),
+            Some(&b"-0500"[..])
+        );
+        assert!(entry.account.is_empty());
+        assert_eq!(entry.description, None);
+        assert_eq!(entry.comment, None);
+        assert_eq!(entry.span, Span::in_line(Position::line_start(0, 1), 0, 24));
+
+        // descriptions can contain '#'
+        let entry = parse_entry("i 2024-01-02 10:00 project\tissue #12");
+        assert_eq!(entry.account.as_bytes(), b"project");
+        assert_eq!(
+            entry.description.as_ref().map(ByteStr::as_bytes),
+            Some(&b"issue #12"[..])
+        );
+    }
+
+    #[test]
+    fn comments_and_empty_lines() {
+        let nodes = parse_timeclock_content(b"; foo\n\n  # bar\n").unwrap();
+        assert_eq!(nodes.len(), 3);
+        assert!(matches!(&nodes[0], TimeclockNode::Comment(comment)
+            if comment.content.as_bytes() == b" foo" && comment.indent == 0));
+        assert!(matches!(nodes[1], TimeclockNode::EmptyLine { .. }));
+        assert!(matches!(&nodes[2], TimeclockNode::Comment(comment)
+            if comment.prefix == CommentPrefix::Hash && comment.indent == 2));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let error = parse_timeclock_content(b"i 2024-01-01 09:00\nx 2024-01-01\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "2:1: Unexpected character 'x', expected timeclock code \
+             ('i', 'o', 'O', 'h' or 'b'), '#' or ';'"
+        );
+        let error = parse_timeclock_content(b"i 2024-01-01 ; late").unwrap_err();
+        assert_eq!(error.to_string(), "1:14: Unexpected comment, expected time");
+        let error = parse_timeclock_content(b"o").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1:2: Unexpected end of line, expected date"
+        );
+        let error = parse_timeclock_content(b"i monday 09:00").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1:3: Invalid date \"monday\", expected date"
+        );
+
+        let (nodes, errors) =
+            parse_timeclock_content_recovering(b"in 2024-01-01\ni 2024-01-01 09:00\n  foo\n");
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[1].to_string(),
+            "3:3: Unexpected character 'f', expected '#', ';' or newline"
+        );
+    }
+}
@@ -15,7 +15,7 @@ pub(crate) fn init_file_tracing(path: &std::path::Path) {
         .with_level(false)
         .with_ansi(false);
 
-    _ = std::fs::File::create(path).expect(&format!("failed to create file {path:?}"));
+    _ = std::fs::File::create(path).unwrap_or_else(|_| panic!("failed to create file {path:?}"));
     let file_appender = tracing_appender::rolling::never(
         path.parent().unwrap_or_else(|| {
             panic!("Invalid trace file path: {:?}", path);
@@ -0,0 +1,670 @@
+//! Verification that formatting doesn't change the meaning of a journal
+//! and that formatting it again is a no-op.
+//!
+//! For equivalence, both contents are parsed and their nodes are converted
+//! to streams of tokens (transaction titles, account names, amounts,
+//! comments...) split at whitespace, so the streams are only equal if the
+//! contents are equivalent ignoring whitespace. Amounts of commodities with
+//! a declared style are compared by their values, read from each content
+//! with the decimal mark of the style, so normalizing their style doesn't
+//! make the contents different but changing their values does.
+//!
+//! Only what the parser reads is compared, so content dropped by the parser
+//! from both contents can't be detected.
+
+#[cfg(test)]
+mod tests;
+
+use crate::date::{extend_date, split_date, DateFormat};
+use crate::formatter::amount_style::AmountStyles;
+use crate::formatter::timeclock::format_timeclock_content_with_options;
+use crate::formatter::{format_content_with_options, FormatJournalOptions};
+use crate::parser::timeclock::{parse_timeclock_content, TimeclockFile, TimeclockNode};
+use crate::parser::{
+    errors::SyntaxError, parse_content, Directive, DirectiveNode, IndentedComment, InlineComment,
+    JournalCstNode, JournalFile, TransactionNode,
+};
+use crate::{format, ByteStr, Cow, String, Vec};
+use core::fmt;
+
+/// Error returned by [`verify_equivalent`].
+#[derive(Debug, PartialEq)]
+pub enum VerifyError {
+    /// The original content has a syntax error
+    Original(SyntaxError),
+    /// The formatted content has a syntax error
+    Formatted(SyntaxError),
+    /// The formatted content is not equivalent to the original one
+    Mismatch {
+        /// Description of the first differing token of the original content,
+        /// `None` if it has fewer tokens than the formatted content
+        original: Option<String>,
+        /// Description of the first differing token of the formatted content,
+        /// `None` if it has fewer tokens than the original content
+        formatted: Option<String>,
+    },
+}
+
+impl fmt::Display for VerifyError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Original(error) => {
+                write!(f, "original content has a syntax error: {error}")
+            }
+            VerifyError::Formatted(error) => {
+                write!(f, "formatted content has a syntax error: {error}")
+            }
+            VerifyError::Mismatch {
+                original,
+                formatted,
+            } => {
+                f.write_str("formatted content is not equivalent to the original: ")?;
+                match (original, formatted) {
+                    (Some(original), Some(formatted)) => {
+                        write!(f, "{original} became {formatted}")
+                    }
+                    (Some(original), None) => write!(f, "{original} is missing"),
+                    (None, Some(formatted)) => write!(f, "unexpected {formatted}"),
+                    (None, None) => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VerifyError {}
+
+/// Error returned by [`check_idempotent`].
+#[derive(Debug, PartialEq)]
+pub enum IdempotencyError {
+    /// The original content has a syntax error
+    Original(SyntaxError),
+    /// The formatted content has a syntax error
+    Formatted(SyntaxError),
+    /// Formatting the formatted content changes it
+    Mismatch {
+        /// Number of the first differing line, starting at 1
+        lineno: usize,
+        /// Line of the formatted content, `None` if it has fewer lines
+        /// than the content formatted twice
+        first: Option<String>,
+        /// Line of the content formatted twice, `None` if it has fewer lines
+        /// than the formatted content
+        second: Option<String>,
+    },
+}
+
+impl fmt::Display for IdempotencyError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdempotencyError::Original(error) => {
+                write!(f, "original content has a syntax error: {error}")
+            }
+            IdempotencyError::Formatted(error) => {
+                write!(f, "formatted content has a syntax error: {error}")
+            }
+            IdempotencyError::Mismatch {
+                lineno,
+                first,
+                second,
+            } => {
+                write!(f, "formatting is not idempotent at line {lineno}: ")?;
+                match (first, second) {
+                    (Some(first), Some(second)) => {
+                        write!(f, "`{first}` became `{second}`")
+                    }
+                    (Some(first), None) => write!(f, "`{first}` is missing"),
+                    (None, Some(second)) => write!(f, "unexpected `{second}`"),
+                    (None, None) => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IdempotencyError {}
+
+/// Kinds of tokens, so moving text from a field to another is detected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TokenKind {
+    Title,
+    Date,
+    Account,
+    Amount,
+    Separator,
+    Lot,
+    Comment,
+    MultilineComment,
+    DirectiveName,
+    DirectiveContent,
+    Subdirective,
+    Verbatim,
+    TimeclockCode,
+    Time,
+    Description,
+}
+
+impl TokenKind {
+    fn name(self) -> &'static str {
+        match self {
+            TokenKind::Title => "transaction title",
+            TokenKind::Date => "date",
+            TokenKind::Account => "account",
+            TokenKind::Amount => "amount",
+            TokenKind::Separator => "separator",
+            TokenKind::Lot => "lot annotation",
+            TokenKind::Comment => "comment",
+            TokenKind::MultilineComment => "multiline comment",
+            TokenKind::DirectiveName => "directive",
+            TokenKind::DirectiveContent => "directive content",
+            TokenKind::Subdirective => "subdirective",
+            TokenKind::Verbatim => "unformatted content",
+            TokenKind::TimeclockCode => "timeclock code",
+            TokenKind::Time => "time",
+            TokenKind::Description => "description",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Token<'a> {
+    kind: TokenKind,
+    /// Compared text
+    text: Cow<'a, [u8]>,
+    /// Text as written in the content, if it's not the compared one
+    written: Option<Vec<u8>>,
+    lineno: usize,
+}
+
+impl Token<'_> {
+    fn describe(&self) -> String {
+        format!(
+            "{} `{}` at line {}",
+            self.kind.name(),
+            String::from_utf8_lossy(self.written.as_deref().unwrap_or(&self.text)),
+            self.lineno
+        )
+    }
+}
+
+/// Check that a formatted journal content is equivalent to the original one.
+///
+/// Both contents are parsed and compared ignoring whitespace, so any change
+/// to transaction titles, account names, amounts, comments or directives
+/// is reported. Content that the parser doesn't read isn't compared.
+///
+/// ```
+/// use hledger_fmt::verify_equivalent;
+///
+/// let original = b"2024-01-01 rent\n  expenses:rent     $500\n";
+/// assert!(verify_equivalent(original, b"2024-01-01 rent\n  expenses:rent  $500\n").is_ok());
+///
+/// let error = verify_equivalent(original, b"2024-01-01 rent\n  expenses:rent  $50\n").unwrap_err();
+/// assert_eq!(
+///     error.to_string(),
+///     "formatted content is not equivalent to the original: \
+///      amount `$500` at line 2 became amount `$50` at line 2",
+/// );
+/// ```
+#[inline]
+pub fn verify_equivalent(original: &[u8], formatted: &[u8]) -> Result<(), VerifyError> {
+    let original = parse_content(original).map_err(VerifyError::Original)?;
+    let formatted = parse_content(formatted).map_err(VerifyError::Formatted)?;
+    verify_parsed_equivalent(&original, &formatted)
+}
+
+/// Check that two parsed journals are equivalent ignoring whitespace.
+pub(crate) fn verify_parsed_equivalent(
+    original: &JournalFile,
+    formatted: &JournalFile,
+) -> Result<(), VerifyError> {
+    compare_tokens(&tokens(original), &tokens(formatted))
+}
+
+/// Check that a formatted timeclock file content is equivalent to the
+/// original one.
+///
+/// See [`verify_equivalent`].
+#[inline]
+pub fn verify_timeclock_equivalent(original: &[u8], formatted: &[u8]) -> Result<(), VerifyError> {
+    let original = parse_timeclock_content(original).map_err(VerifyError::Original)?;
+    let formatted = parse_timeclock_content(formatted).map_err(VerifyError::Formatted)?;
+    compare_tokens(&timeclock_tokens(&original), &timeclock_tokens(&formatted))
+}
+
+/// Report the first differing token of two streams of tokens.
+fn compare_tokens(
+    original_tokens: &[Token<'_>],
+    formatted_tokens: &[Token<'_>],
+) -> Result<(), VerifyError> {
+    let mut original_iter = original_tokens.iter();
+    let mut formatted_iter = formatted_tokens.iter();
+    loop {
+        match (original_iter.next(), formatted_iter.next()) {
+            (None, None) => return Ok(()),
+            (Some(original), Some(formatted))
+                if original.kind == formatted.kind && original.text == formatted.text => {}
+            (original, formatted) => {
+                return Err(VerifyError::Mismatch {
+                    original: original.map(Token::describe),
+                    formatted: formatted.map(Token::describe),
+                });
+            }
+        }
+    }
+}
+
+/// Check that formatting the formatted journal content again is a no-op.
+///
+/// The content is formatted twice with `options` and the first line that
+/// differs between both outputs is reported.
+///
+/// ```
+/// use hledger_fmt::{check_idempotent, FormatJournalOptions};
+///
+/// let content = b"2024-01-01 rent\n  expenses:rent     $500\n  assets:bank\n";
+/// assert!(check_idempotent(content, FormatJournalOptions::new()).is_ok());
+/// ```
+#[inline]
+pub fn check_idempotent(
+    content: &[u8],
+    options: FormatJournalOptions,
+) -> Result<(), IdempotencyError> {
+    let parsed = parse_content(content).map_err(IdempotencyError::Original)?;
+    let options = options.with_estimated_length(content.len());
+    let formatted = format_content_with_options(&parsed, &options);
+    check_formatted_idempotent(&formatted, &options)
+}
+
+/// Check that formatting an already formatted content with `options`
+/// doesn't change it.
+pub(crate) fn check_formatted_idempotent(
+    formatted: &[u8],
+    options: &FormatJournalOptions,
+) -> Result<(), IdempotencyError> {
+    let parsed = parse_content(formatted).map_err(IdempotencyError::Formatted)?;
+    let formatted_twice = format_content_with_options(&parsed, options);
+    compare_lines(formatted, &formatted_twice)
+}
+
+/// Check that formatting the formatted timeclock file content again is a no-op.
+///
+/// See [`check_idempotent`].
+#[inline]
+pub fn check_timeclock_idempotent(
+    content: &[u8],
+    options: FormatJournalOptions,
+) -> Result<(), IdempotencyError> {
+    let parsed = parse_timeclock_content(content).map_err(IdempotencyError::Original)?;
+    let options = options.with_estimated_length(content.len());
+    let formatted = format_timeclock_content_with_options(&parsed, &options);
+    check_formatted_timeclock_idempotent(&formatted, &options)
+}
+
+/// Check that formatting an already formatted timeclock file content with
+/// `options` doesn't change it.
+pub(crate) fn check_formatted_timeclock_idempotent(
+    formatted: &[u8],
+    options: &FormatJournalOptions,
+) -> Result<(), IdempotencyError> {
+    let parsed = parse_timeclock_content(formatted).map_err(IdempotencyError::Formatted)?;
+    let formatted_twice = format_timeclock_content_with_options(&parsed, options);
+    compare_lines(formatted, &formatted_twice)
+}
+
+/// Report the first differing line of a formatted content and the content
+/// formatted twice.
+fn compare_lines(formatted: &[u8], formatted_twice: &[u8]) -> Result<(), IdempotencyError> {
+    if formatted_twice == formatted {
+        return Ok(());
+    }
+
+    let mut first_lines = formatted.split(|b| *b == b'\n');
+    let mut second_lines = formatted_twice.split(|b| *b == b'\n');
+    let mut lineno = 1;
+    loop {
+        match (first_lines.next(), second_lines.next()) {
+            (Some(first), Some(second)) if first == second => lineno += 1,
+            (first, second) => {
+                return Err(IdempotencyError::Mismatch {
+                    lineno,
+                    first: first.map(|line| String::from_utf8_lossy(line).into_owned()),
+                    second: second.map(|line| String::from_utf8_lossy(line).into_owned()),
+                });
+            }
+        }
+    }
+}
+
+/// Convert the lines of a timeclock file to a stream of tokens
+fn timeclock_tokens<'a>(nodes: &TimeclockFile<'a>) -> Vec<Token<'a>> {
+    let mut tokens = Vec::new();
+    for node in nodes {
+        match node {
+            TimeclockNode::EmptyLine { .. } => {}
+            TimeclockNode::Comment(comment) => push_indented_comment(&mut tokens, comment),
+            TimeclockNode::Entry(entry) => {
+                let lineno = entry.span.start.lineno;
+                tokens.push(Token {
+                    kind: TokenKind::TimeclockCode,
+                    text: Cow::Owned(Vec::from([entry.code as u8])),
+                    written: None,
+                    lineno,
+                });
+                push_date(&mut tokens, &entry.date, lineno);
+                push_words(&mut tokens, TokenKind::Time, entry.time.as_bytes(), lineno);
+                if let Some(time_zone) = &entry.time_zone {
+                    push_words(&mut tokens, TokenKind::Time, time_zone.as_bytes(), lineno);
+                }
+                push_words(
+                    &mut tokens,
+                    TokenKind::Account,
+                    entry.account.as_bytes(),
+                    lineno,
+                );
+                if let Some(description) = &entry.description {
+                    push_words(
+                        &mut tokens,
+                        TokenKind::Description,
+                        description.as_bytes(),
+                        lineno,
+                    );
+                }
+                push_inline_comment(&mut tokens, &entry.comment, lineno);
+            }
+        }
+    }
+    tokens
+}
+
+/// Convert the nodes of a journal to a stream of tokens
+fn tokens<'a>(nodes: &JournalFile<'a>) -> Vec<Token<'a>> {
+    let mut tokens = Vec::new();
+    let mut styles = AmountStyles::default();
+    for node in nodes {
+        styles.add_node(node);
+        match node {
+            JournalCstNode::EmptyLine { .. } => {}
+            JournalCstNode::SingleLineComment(comment) => {
+                push_indented_comment(&mut tokens, comment);
+            }
+            JournalCstNode::MultilineComment { content, span } => {
+                push_words(
+                    &mut tokens,
+                    TokenKind::MultilineComment,
+                    content.as_bytes(),
+                    span.start.lineno,
+                );
+            }
+            JournalCstNode::Verbatim { content, span } => {
+                push_words(
+                    &mut tokens,
+                    TokenKind::Verbatim,
+                    content.as_bytes(),
+                    span.start.lineno,
+                );
+            }
+            JournalCstNode::DirectivesGroup { nodes, .. } => {
+                for node in nodes {
+                    match node {
+                        DirectiveNode::Directive(Directive {
+                            name,
+                            content,
+                            comment,
+                            span,
+                            ..
+                        }) => {
+                            let lineno = span.start.lineno;
+                            push_words(
+                                &mut tokens,
+                                TokenKind::DirectiveName,
+                                name.as_bytes(),
+                                lineno,
+                            );
+                            let mut content = content.as_bytes();
+                            if name.as_bytes() == b"P" {
+                                if let Some((date, rest)) = split_date(content) {
+                                    push_date(&mut tokens, date, lineno);
+                                    content = rest;
+                                }
+                            }
+                            push_words(&mut tokens, TokenKind::DirectiveContent, content, lineno);
+                            push_inline_comment(&mut tokens, comment, lineno);
+                        }
+                        DirectiveNode::Subdirective { content, span } => {
+                            push_words(
+                                &mut tokens,
+                                TokenKind::Subdirective,
+                                content.as_bytes(),
+                                span.start.lineno,
+                            );
+                        }
+                        DirectiveNode::SingleLineComment(comment) => {
+                            push_indented_comment(&mut tokens, comment);
+                        }
+                    }
+                }
+            }
+            JournalCstNode::Transaction { .. }
+            | JournalCstNode::PeriodicTransaction { .. }
+            | JournalCstNode::AutoPostingRule { .. } => {
+                // periodic transactions and auto posting rules are compared by
+                // their transaction, whose title is the whole title
+                let JournalCstNode::Transaction {
+                    title,
+                    header,
+                    title_comment,
+                    entries,
+                    span,
+                    ..
+                } = node.inner()
+                else {
+                    continue;
+                };
+                let lineno = span.start.lineno;
+                let mut title = title.as_bytes();
+                if let Some(header) = header {
+                    push_date(&mut tokens, &header.date, lineno);
+                    let mut dates_len = header.date.len();
+                    if let Some(secondary_date) = &header.secondary_date {
+                        push_date(&mut tokens, secondary_date, lineno);
+                        dates_len += secondary_date.len() + 1;
+                    }
+                    title = &title[dates_len..];
+                }
+                push_words(&mut tokens, TokenKind::Title, title, lineno);
+                push_inline_comment(&mut tokens, title_comment, lineno);
+                for entry in entries {
+                    match entry {
+                        TransactionNode::TransactionEntry(entry) => {
+                            let lineno = entry.span.start.lineno;
+                            push_words(
+                                &mut tokens,
+                                TokenKind::Account,
+                                entry.name.as_bytes(),
+                                lineno,
+                            );
+                            push_amount(
+                                &mut tokens,
+                                &styles,
+                                [
+                                    &entry.value_first_part_before_decimals,
+                                    &entry.value_first_part_after_decimals,
+                                ],
+                                lineno,
+                            );
+                            push_words(
+                                &mut tokens,
+                                TokenKind::Lot,
+                                entry.lot_price.as_bytes(),
+                                lineno,
+                            );
+                            push_words(
+                                &mut tokens,
+                                TokenKind::Lot,
+                                entry.lot_date.as_bytes(),
+                                lineno,
+                            );
+                            push_words(
+                                &mut tokens,
+                                TokenKind::Separator,
+                                entry.value_first_separator.as_bytes(),
+                                lineno,
+                            );
+                            push_amount(
+                                &mut tokens,
+                                &styles,
+                                [
+                                    &entry.value_second_part_before_decimals,
+                                    &entry.value_second_part_after_decimals,
+                                ],
+                                lineno,
+                            );
+                            push_words(
+                                &mut tokens,
+                                TokenKind::Separator,
+                                entry.assertion_operator.as_bytes(),
+                                lineno,
+                            );
+                            push_amount(
+                                &mut tokens,
+                                &styles,
+                                [
+                                    &entry.assertion_before_decimals,
+                                    &entry.assertion_after_decimals,
+                                ],
+                                lineno,
+                            );
+                            push_words(
+                                &mut tokens,
+                                TokenKind::Separator,
+                                entry.assertion_cost_separator.as_bytes(),
+                                lineno,
+                            );
+                            push_amount(
+                                &mut tokens,
+                                &styles,
+                                [
+                                    &entry.assertion_cost_before_decimals,
+                                    &entry.assertion_cost_after_decimals,
+                                ],
+                                lineno,
+                            );
+                            push_inline_comment(&mut tokens, &entry.comment, lineno);
+                        }
+                        TransactionNode::SingleLineComment(comment) => {
+                            push_indented_comment(&mut tokens, comment);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    tokens
+}
+
+fn push_indented_comment<'a>(tokens: &mut Vec<Token<'a>>, comment: &IndentedComment<'a>) {
+    push_words(
+        tokens,
+        TokenKind::Comment,
+        comment.content.as_bytes(),
+        comment.span.start.lineno,
+    );
+}
+
+fn push_inline_comment<'a>(
+    tokens: &mut Vec<Token<'a>>,
+    comment: &Option<InlineComment<'a>>,
+    lineno: usize,
+) {
+    if let Some(comment) = comment {
+        push_words(
+            tokens,
+            TokenKind::Comment,
+            comment.content.as_bytes(),
+            lineno,
+        );
+    }
+}
+
+/// Push a date as a token, written in the same format whatever its original one
+fn push_date(tokens: &mut Vec<Token<'_>>, date: &[u8], lineno: usize) {
+    let mut normalized = Vec::with_capacity(date.len() + 2);
+    extend_date(&mut normalized, date, DateFormat::Iso);
+    tokens.push(Token {
+        kind: TokenKind::Date,
+        text: Cow::Owned(normalized),
+        written: None,
+        lineno,
+    });
+}
+
+/// Push the words of a text as tokens
+fn push_words<'a>(tokens: &mut Vec<Token<'a>>, kind: TokenKind, text: &'a [u8], lineno: usize) {
+    tokens.extend(
+        text.split(|b| b.is_ascii_whitespace())
+            .filter(|word| !word.is_empty())
+            .map(|text| Token {
+                kind,
+                text: Cow::Borrowed(text),
+                written: None,
+                lineno,
+            }),
+    );
+}
+
+/// Push an amount split at its decimal mark as a token compared by its value
+/// if its commodity has a declared style, or its words as tokens otherwise
+fn push_amount<'a>(
+    tokens: &mut Vec<Token<'a>>,
+    styles: &AmountStyles,
+    parts: [&ByteStr<'a>; 2],
+    lineno: usize,
+) {
+    let [first, second] = parts;
+    let mut amount = Vec::with_capacity(first.len() + second.len());
+    amount.extend_from_slice(first);
+    amount.extend_from_slice(second);
+    match styles.value(&amount) {
+        Some(value) => tokens.push(Token {
+            kind: TokenKind::Amount,
+            text: Cow::Owned(value),
+            written: Some(amount),
+            lineno,
+        }),
+        None => push_joined_words(tokens, TokenKind::Amount, parts, lineno),
+    }
+}
+
+/// Push the words of a value split in two parts, like an amount split
+/// at its decimal mark, as tokens
+fn push_joined_words<'a>(
+    tokens: &mut Vec<Token<'a>>,
+    kind: TokenKind,
+    parts: [&ByteStr<'a>; 2],
+    lineno: usize,
+) {
+    let [first, second] = parts;
+    if second.is_empty() {
+        push_words(tokens, kind, first.as_bytes(), lineno);
+    } else {
+        let mut joined = Vec::with_capacity(first.len() + second.len());
+        joined.extend_from_slice(first);
+        joined.extend_from_slice(second);
+        tokens.extend(
+            joined
+                .split(|b| b.is_ascii_whitespace())
+                .filter(|word| !word.is_empty())
+                .map(|text| Token {
+                    kind,
+                    text: Cow::Owned(text.to_vec()),
+                    written: None,
+                    lineno,
+                }),
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use super::*;
+
+fn mismatch(original: &str, formatted: &str) -> Option<(Option<String>, Option<String>)> {
+    match verify_equivalent(original.as_bytes(), formatted.as_bytes()) {
+        Ok(()) => None,
+        Err(VerifyError::Mismatch {
+            original,
+            formatted,
+        }) => Some((original, formatted)),
+        Err(e) => panic!("unexpected error: {e}"),
+    }
+}
+
+#[test]
+fn equivalent_ignoring_whitespace() {
+    let original = "; comment\naccount   assets  ; note\n\n2024-01-01  rent  ; title comment\n  expenses:rent      $ 500.00  ; posting\n  assets\n";
+    let formatted = "; comment\naccount assets  ; note\n\n2024-01-01 rent  ; title comment\n    expenses:rent  $ 500.00  ; posting\n    assets\n";
+    assert_eq!(mismatch(original, formatted), None);
+}
+
+#[test]
+fn equivalent_dates_in_other_format() {
+    let original = "P 2024/1/5 EUR $1.10\n\n2024.1.5=1/7 food\n  expenses:food  $10\n";
+    let formatted = "P 2024-01-05 EUR $1.10\n\n2024-01-05=01-07 food\n  expenses:food  $10\n";
+    assert_eq!(mismatch(original, formatted), None);
+    assert_eq!(
+        mismatch(
+            original,
+            "P 2024-01-05 EUR $1.10\n\n2024-01-06=01-07 food\n  expenses:food  $10\n"
+        ),
+        Some((
+            Some("date `2024-01-05` at line 3".to_string()),
+            Some("date `2024-01-06` at line 3".to_string()),
+        ))
+    );
+}
+
+#[test]
+fn equivalent_restyled_amounts() {
+    let original = "commodity $1,000.00\n\n2024-01-01 rent\n  expenses:rent  1000$\n";
+    let formatted = "commodity $1,000.00\n\n2024-01-01 rent\n  expenses:rent  $1,000.00\n";
+    assert_eq!(mismatch(original, formatted), None);
+    assert_eq!(
+        mismatch(
+            original,
+            "commodity $1,000.00\n\n2024-01-01 rent\n  expenses:rent  $1,000.01\n"
+        ),
+        Some((
+            Some("amount `1000$` at line 4".to_string()),
+            Some("amount `$1,000.01` at line 4".to_string()),
+        ))
+    );
+}
+
+/// Amounts are compared by the values read from each content, not by how
+/// they would be restyled.
+#[test]
+fn restyled_amount_with_another_decimal_mark() {
+    assert_eq!(
+        mismatch(
+            "commodity 1.000,00 EUR\n\n2024-01-01 rent\n  expenses:rent  EUR 1234.5\n",
+            "commodity 1.000,00 EUR\n\n2024-01-01 rent\n  expenses:rent  1.234,50 EUR\n",
+        ),
+        Some((
+            Some("amount `EUR` at line 4".to_string()),
+            Some("amount `1.234,50 EUR` at line 4".to_string()),
+        ))
+    );
+}
+
+#[test]
+fn changed_amount() {
+    assert_eq!(
+        mismatch(
+            "2024-01-01 rent\n  expenses:rent  $500.00\n",
+            "2024-01-01 rent\n  expenses:rent  $500.01\n",
+        ),
+        Some((
+            Some("amount `$500.00` at line 2".to_string()),
+            Some("amount `$500.01` at line 2".to_string()),
+        ))
+    );
+}
+
+#[test]
+fn changed_account() {
+    assert_eq!(
+        mismatch(
+            "2024-01-01 rent\n  expenses:rent  $500\n",
+            "2024-01-01 rent\n  expenses:food  $500\n",
+        ),
+        Some((
+            Some("account `expenses:rent` at line 2".to_string()),
+            Some("account `expenses:food` at line 2".to_string()),
+        ))
+    );
+}
+
+#[test]
+fn text_moved_to_another_field() {
+    assert_eq!(
+        mismatch(
+            "2024-01-01 rent\n  expenses:rent  $500  ; note\n",
+            "2024-01-01 rent\n  expenses:rent  $500 note\n",
+        ),
+        Some((
+            Some("comment `note` at line 2".to_string()),
+            Some("amount `note` at line 2".to_string()),
+        ))
+    );
+}
+
+#[test]
+fn missing_and_unexpected_tokens() {
+    assert_eq!(
+        mismatch("; foo bar\n", "; foo\n"),
+        Some((Some("comment `bar` at line 1".to_string()), None))
+    );
+    assert_eq!(
+        mismatch("; foo\n", "; foo\n\n; bar\n"),
+        Some((None, Some("comment `bar` at line 3".to_string())))
+    );
+}
+
+#[test]
+fn formatted_syntax_error() {
+    let result = verify_equivalent(b"; foo\n", b"  foo\n");
+    assert!(
+        matches!(result, Err(VerifyError::Formatted(_))),
+        "{result:?}"
+    );
+}
+
+#[test]
+fn error_display() {
+    let error = VerifyError::Mismatch {
+        original: Some("comment `bar` at line 1".to_string()),
+        formatted: None,
+    };
+    assert_eq!(
+        error.to_string(),
+        "formatted content is not equivalent to the original: comment `bar` at line 1 is missing"
+    );
+}
+
+#[test]
+fn idempotent_formatting() {
+    let content = b"; comment\naccount   assets\n\n2024-01-01  rent\n  expenses:rent      $500  ; note\n  assets\n";
+    assert_eq!(
+        check_idempotent(content, FormatJournalOptions::new()),
+        Ok(())
+    );
+}
+
+#[test]
+fn idempotency_mismatch_reports_first_differing_line() {
+    let formatted = b"2024-01-01 rent\n  expenses:rent     $500\n";
+    assert_eq!(
+        check_formatted_idempotent(formatted, &FormatJournalOptions::new()),
+        Err(IdempotencyError::Mismatch {
+            lineno: 2,
+            first: Some("  expenses:rent     $500".to_string()),
+            second: Some("  expenses:rent  $500".to_string()),
+        })
+    );
+}
+
+#[test]
+fn idempotency_error_display() {
+    let error = IdempotencyError::Mismatch {
+        lineno: 3,
+        first: None,
+        second: Some("; foo".to_string()),
+    };
+    assert_eq!(
+        error.to_string(),
+        "formatting is not idempotent at line 3: unexpected `; foo`"
+    );
+}
+
+/// Formatting every file of the fuzzing corpus twice must be a no-op.
+#[test]
+fn corpus_is_idempotent() {
+    let mut paths: Vec<_> = std::fs::read_dir("fuzz/corpus")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let content = std::fs::read(&path).unwrap();
+        if let Err(e) = check_idempotent(&content, FormatJournalOptions::new()) {
+            panic!("{}: {e}", path.display());
+        }
+    }
+}